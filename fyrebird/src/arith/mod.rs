@@ -86,6 +86,16 @@ pub fn rotate_vec2_deg(v: Vec2, angle: Deg) -> Vec2 {
     Vec2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
 }
 
+/// 2D cross product, the z component of the 3D cross product of both vectors
+pub fn cross_vec2(a: Vec2, b: Vec2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+/// Perpendicular vector rotated 90 degrees clockwise
+pub fn perp_vec2(v: Vec2) -> Vec2 {
+    Vec2::new(v.y, -v.x)
+}
+
 pub fn rotate_vec3(v: Vec3, rotation: Quaternion) -> Vec3 {
     rotation * v
 }
@@ -96,6 +106,7 @@ pub fn rotate_around_axis(v: Vec3, axis: Vec3, angle: Rad) -> Vec3 {
 }
 
 // Generic number interval implementation
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Interval<T>
 where
     T: Num + PartialOrd + Copy,
@@ -1,3 +1,5 @@
+use cgmath::InnerSpace;
+
 use super::{Point2, Point3, Vec2, Vec3};
 
 pub trait Ray {
@@ -5,11 +7,30 @@ pub trait Ray {
     fn at(&self, t: f32) -> Self::Point;
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Ray2D {
     origin: Point2,
     direction: Vec2,
 }
 
+impl Ray2D {
+    /// Create a ray, the direction is normalized so `at(t)` is `t` units away from the origin
+    pub fn new(origin: Point2, direction: Vec2) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    pub fn origin(&self) -> Point2 {
+        self.origin
+    }
+
+    pub fn direction(&self) -> Vec2 {
+        self.direction
+    }
+}
+
 impl Ray for Ray2D {
     type Point = Point2;
     fn at(&self, t: f32) -> Self::Point {
@@ -17,11 +38,30 @@ impl Ray for Ray2D {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Ray3D {
     origin: Point3,
     direction: Vec3,
 }
 
+impl Ray3D {
+    /// Create a ray, the direction is normalized so `at(t)` is `t` units away from the origin
+    pub fn new(origin: Point3, direction: Vec3) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    pub fn origin(&self) -> Point3 {
+        self.origin
+    }
+
+    pub fn direction(&self) -> Vec3 {
+        self.direction
+    }
+}
+
 impl Ray for Ray3D {
     type Point = Point3;
     fn at(&self, t: f32) -> Self::Point {
@@ -34,3 +34,22 @@ impl<'a> System<'a> for BehaviorSystem {
         }
     }
 }
+
+/// Calls `Behavior::fixed_update` on every behavior, run by the fixed update
+pub struct FixedBehaviorSystem;
+
+impl<'a> System<'a> for FixedBehaviorSystem {
+    type SystemData = (
+        Entities<'a>,
+        Write<'a, World>,
+        WriteStorage<'a, BehaviorContainer>,
+    );
+
+    fn run(&mut self, (entities, mut world, mut behaviors): Self::SystemData) {
+        for (entity, behavior_container) in (&entities, &mut behaviors).join() {
+            for behavior in &mut behavior_container.behaviors {
+                behavior.fixed_update(entity, &mut world);
+            }
+        }
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+
+use specs::Entity;
+
+use super::BroadPhase;
+use crate::collision::shapes::Aabb;
+
+const NULL: usize = usize::MAX;
+
+#[derive(Debug, Clone)]
+struct Node {
+    /// Fattened bounds for leaves, union of the children for internal nodes
+    aabb: Aabb,
+    parent: usize,
+    child1: usize,
+    child2: usize,
+    /// Leaves have a height of 0
+    height: i32,
+    entity: Option<Entity>,
+}
+
+impl Node {
+    fn is_leaf(&self) -> bool {
+        self.child1 == NULL
+    }
+}
+
+/// Incrementally balanced bounding volume hierarchy.
+/// Leaves store fattened bounds so small movements don't touch the tree.
+pub struct DynamicAabbTree {
+    nodes: Vec<Node>,
+    free: Vec<usize>,
+    root: usize,
+    leaves: HashMap<Entity, usize>,
+    margin: f32,
+}
+
+impl Default for DynamicAabbTree {
+    fn default() -> Self {
+        Self::new(0.1)
+    }
+}
+
+impl DynamicAabbTree {
+    /// Create a tree whose leaves are grown by `margin` on every side
+    pub fn new(margin: f32) -> Self {
+        Self {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: NULL,
+            leaves: HashMap::new(),
+            margin,
+        }
+    }
+
+    /// Height of the tree, 0 when it only has a root leaf
+    pub fn height(&self) -> i32 {
+        if self.root == NULL {
+            0
+        } else {
+            self.nodes[self.root].height
+        }
+    }
+
+    /// Fattened bounds stored for an entity
+    pub fn fat_aabb(&self, entity: Entity) -> Option<Aabb> {
+        self.leaves.get(&entity).map(|&leaf| self.nodes[leaf].aabb)
+    }
+
+    fn allocate(&mut self, node: Node) -> usize {
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn release(&mut self, index: usize) {
+        self.nodes[index].entity = None;
+        self.free.push(index);
+    }
+
+    fn insert_leaf(&mut self, leaf: usize) {
+        if self.root == NULL {
+            self.root = leaf;
+            self.nodes[leaf].parent = NULL;
+            return;
+        }
+
+        // Walk down picking the cheapest sibling by the surface area heuristic
+        let leaf_aabb = self.nodes[leaf].aabb;
+        let mut index = self.root;
+
+        while !self.nodes[index].is_leaf() {
+            let node = &self.nodes[index];
+            let area = node.aabb.perimeter();
+            let combined = node.aabb.union(&leaf_aabb).perimeter();
+            let cost = 2.0 * combined;
+            let inheritance = 2.0 * (combined - area);
+
+            let descend_cost = |child: usize| {
+                let child = &self.nodes[child];
+                let union = leaf_aabb.union(&child.aabb).perimeter();
+                if child.is_leaf() {
+                    union + inheritance
+                } else {
+                    union - child.aabb.perimeter() + inheritance
+                }
+            };
+            let cost1 = descend_cost(node.child1);
+            let cost2 = descend_cost(node.child2);
+
+            if cost < cost1 && cost < cost2 {
+                break;
+            }
+
+            index = if cost1 < cost2 {
+                node.child1
+            } else {
+                node.child2
+            };
+        }
+
+        let sibling = index;
+        let old_parent = self.nodes[sibling].parent;
+        let new_parent = self.allocate(Node {
+            aabb: leaf_aabb.union(&self.nodes[sibling].aabb),
+            parent: old_parent,
+            child1: sibling,
+            child2: leaf,
+            height: self.nodes[sibling].height + 1,
+            entity: None,
+        });
+
+        if old_parent == NULL {
+            self.root = new_parent;
+        } else if self.nodes[old_parent].child1 == sibling {
+            self.nodes[old_parent].child1 = new_parent;
+        } else {
+            self.nodes[old_parent].child2 = new_parent;
+        }
+
+        self.nodes[sibling].parent = new_parent;
+        self.nodes[leaf].parent = new_parent;
+
+        self.refit(self.nodes[leaf].parent);
+    }
+
+    fn remove_leaf(&mut self, leaf: usize) {
+        if leaf == self.root {
+            self.root = NULL;
+            return;
+        }
+
+        let parent = self.nodes[leaf].parent;
+        let grand_parent = self.nodes[parent].parent;
+        let sibling = if self.nodes[parent].child1 == leaf {
+            self.nodes[parent].child2
+        } else {
+            self.nodes[parent].child1
+        };
+
+        self.nodes[sibling].parent = grand_parent;
+        self.release(parent);
+
+        if grand_parent == NULL {
+            self.root = sibling;
+            return;
+        }
+
+        if self.nodes[grand_parent].child1 == parent {
+            self.nodes[grand_parent].child1 = sibling;
+        } else {
+            self.nodes[grand_parent].child2 = sibling;
+        }
+
+        self.refit(grand_parent);
+    }
+
+    /// Rebalances and recomputes bounds from `index` up to the root
+    fn refit(&mut self, mut index: usize) {
+        while index != NULL {
+            index = self.balance(index);
+
+            let child1 = self.nodes[index].child1;
+            let child2 = self.nodes[index].child2;
+            self.nodes[index].height = 1 + self.nodes[child1].height.max(self.nodes[child2].height);
+            self.nodes[index].aabb = self.nodes[child1].aabb.union(&self.nodes[child2].aabb);
+
+            index = self.nodes[index].parent;
+        }
+    }
+
+    fn replace_child(&mut self, parent: usize, old: usize, new: usize) {
+        if parent == NULL {
+            self.root = new;
+        } else if self.nodes[parent].child1 == old {
+            self.nodes[parent].child1 = new;
+        } else {
+            self.nodes[parent].child2 = new;
+        }
+    }
+
+    /// Rotates the subtree at `a` if it is unbalanced. Returns the new subtree root.
+    fn balance(&mut self, a: usize) -> usize {
+        if self.nodes[a].is_leaf() || self.nodes[a].height < 2 {
+            return a;
+        }
+
+        let b = self.nodes[a].child1;
+        let c = self.nodes[a].child2;
+        let balance = self.nodes[c].height - self.nodes[b].height;
+
+        if balance > 1 {
+            self.rotate_up(a, c, b, false)
+        } else if balance < -1 {
+            self.rotate_up(a, b, c, true)
+        } else {
+            a
+        }
+    }
+
+    /// Moves the taller child `up` of `a` into its place; `other` is the remaining child of `a`
+    fn rotate_up(&mut self, a: usize, up: usize, other: usize, up_is_child1: bool) -> usize {
+        let f = self.nodes[up].child1;
+        let g = self.nodes[up].child2;
+
+        self.nodes[up].child1 = a;
+        self.nodes[up].parent = self.nodes[a].parent;
+        self.nodes[a].parent = up;
+        self.replace_child(self.nodes[up].parent, a, up);
+
+        // The taller grandchild stays under `up`, the shorter one moves to `a`
+        let (keep, give) = if self.nodes[f].height > self.nodes[g].height {
+            (f, g)
+        } else {
+            (g, f)
+        };
+
+        self.nodes[up].child2 = keep;
+        if up_is_child1 {
+            self.nodes[a].child1 = give;
+        } else {
+            self.nodes[a].child2 = give;
+        }
+        self.nodes[give].parent = a;
+
+        self.nodes[a].aabb = self.nodes[other].aabb.union(&self.nodes[give].aabb);
+        self.nodes[up].aabb = self.nodes[a].aabb.union(&self.nodes[keep].aabb);
+        self.nodes[a].height = 1 + self.nodes[other].height.max(self.nodes[give].height);
+        self.nodes[up].height = 1 + self.nodes[a].height.max(self.nodes[keep].height);
+
+        up
+    }
+}
+
+impl BroadPhase for DynamicAabbTree {
+    fn insert(&mut self, entity: Entity, aabb: Aabb) {
+        if self.leaves.contains_key(&entity) {
+            self.update(entity, aabb);
+            return;
+        }
+
+        let leaf = self.allocate(Node {
+            aabb: aabb.expanded(self.margin),
+            parent: NULL,
+            child1: NULL,
+            child2: NULL,
+            height: 0,
+            entity: Some(entity),
+        });
+        self.leaves.insert(entity, leaf);
+        self.insert_leaf(leaf);
+    }
+
+    fn update(&mut self, entity: Entity, aabb: Aabb) -> bool {
+        let Some(&leaf) = self.leaves.get(&entity) else {
+            self.insert(entity, aabb);
+            return true;
+        };
+
+        if self.nodes[leaf].aabb.contains(&aabb) {
+            return false;
+        }
+
+        self.remove_leaf(leaf);
+        self.nodes[leaf].aabb = aabb.expanded(self.margin);
+        self.insert_leaf(leaf);
+        true
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        if let Some(leaf) = self.leaves.remove(&entity) {
+            self.remove_leaf(leaf);
+            self.release(leaf);
+        }
+    }
+
+    fn contains(&self, entity: Entity) -> bool {
+        self.leaves.contains_key(&entity)
+    }
+
+    fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    fn query(&self, aabb: &Aabb, callback: &mut dyn FnMut(Entity)) {
+        if self.root == NULL {
+            return;
+        }
+
+        let mut stack = vec![self.root];
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.aabb.overlaps(aabb) {
+                continue;
+            }
+
+            match node.entity {
+                Some(entity) => callback(entity),
+                None => {
+                    stack.push(node.child1);
+                    stack.push(node.child2);
+                }
+            }
+        }
+    }
+
+    fn collect_pairs(&self, pairs: &mut Vec<(Entity, Entity)>) {
+        for (&entity, &leaf) in &self.leaves {
+            self.query(&self.nodes[leaf].aabb, &mut |other| {
+                // Each pair is seen from both leaves, keep it once
+                if entity < other {
+                    pairs.push((entity, other));
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,136 @@
+pub mod aabb_tree;
+pub mod spatial_hash;
+
+pub use aabb_tree::DynamicAabbTree;
+pub use spatial_hash::SpatialHash;
+
+use std::collections::HashMap;
+
+use specs::Entity;
+
+use super::obstacle::Obstacle;
+use super::shapes::Aabb;
+
+/// Acceleration structure finding the colliders whose bounds may overlap
+pub trait BroadPhase: Send + Sync {
+    /// Starts tracking an entity with the given world space bounds
+    fn insert(&mut self, entity: Entity, aabb: Aabb);
+
+    /// Moves a tracked entity. Returns true if the structure had to be modified.
+    fn update(&mut self, entity: Entity, aabb: Aabb) -> bool;
+
+    /// Stops tracking an entity
+    fn remove(&mut self, entity: Entity);
+
+    fn contains(&self, entity: Entity) -> bool;
+
+    /// Number of tracked entities
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Calls `callback` for every tracked entity whose bounds overlap `aabb`
+    fn query(&self, aabb: &Aabb, callback: &mut dyn FnMut(Entity));
+
+    /// Appends every pair of entities whose bounds overlap, each pair once
+    fn collect_pairs(&self, pairs: &mut Vec<(Entity, Entity)>);
+}
+
+/// Orders a pair so the same two entities always produce the same key
+pub(crate) fn ordered_pair(a: Entity, b: Entity) -> (Entity, Entity) {
+    if a < b { (a, b) } else { (b, a) }
+}
+
+/// Resource holding the broad phase the `CollisionSystem` maintains across frames, along with
+/// the `Obstacle` of every collider it tracks. Scene queries, continuous collision, soft bodies
+/// and particles find the colliders near them through it.
+/// Insert it with a different `BroadPhase` to replace the default dynamic AABB tree.
+pub struct CollisionWorld {
+    broad_phase: Box<dyn BroadPhase>,
+    obstacles: HashMap<Entity, Obstacle>,
+    candidate_pairs: Vec<(Entity, Entity)>,
+}
+
+impl Default for CollisionWorld {
+    fn default() -> Self {
+        Self::new(DynamicAabbTree::default())
+    }
+}
+
+impl CollisionWorld {
+    pub fn new(broad_phase: impl BroadPhase + 'static) -> Self {
+        Self {
+            broad_phase: Box::new(broad_phase),
+            obstacles: HashMap::new(),
+            candidate_pairs: Vec::new(),
+        }
+    }
+
+    pub fn broad_phase(&self) -> &dyn BroadPhase {
+        self.broad_phase.as_ref()
+    }
+
+    /// Pairs whose bounds overlapped during the last collision step, sorted by entity
+    pub fn candidate_pairs(&self) -> &[(Entity, Entity)] {
+        &self.candidate_pairs
+    }
+
+    /// Inserts or moves the obstacle of an entity.
+    /// Returns true if the broad phase had to be modified.
+    pub fn track(&mut self, obstacle: Obstacle) -> bool {
+        let (entity, aabb) = (obstacle.entity, obstacle.aabb);
+        if self.obstacles.insert(entity, obstacle).is_none() {
+            self.broad_phase.insert(entity, aabb);
+            true
+        } else {
+            self.broad_phase.update(entity, aabb)
+        }
+    }
+
+    /// Removes every tracked entity for which `keep` returns false
+    pub fn retain(&mut self, mut keep: impl FnMut(Entity) -> bool) {
+        let broad_phase = &mut self.broad_phase;
+        self.obstacles.retain(|&entity, _| {
+            let kept = keep(entity);
+            if !kept {
+                broad_phase.remove(entity);
+            }
+            kept
+        });
+    }
+
+    /// Obstacle of a tracked entity
+    pub fn obstacle(&self, entity: Entity) -> Option<&Obstacle> {
+        self.obstacles.get(&entity)
+    }
+
+    /// Calls `visit` for every obstacle whose bounds overlap `area`
+    pub fn query<'s>(&'s self, area: &Aabb, mut visit: impl FnMut(&'s Obstacle)) {
+        let obstacles = &self.obstacles;
+        self.broad_phase.query(area, &mut |entity| {
+            if let Some(obstacle) = obstacles.get(&entity)
+                && obstacle.aabb.overlaps(area)
+            {
+                visit(obstacle);
+            }
+        });
+    }
+
+    /// Obstacles whose bounds overlap `area`, sorted by entity
+    pub fn obstacles(&self, area: &Aabb) -> Vec<&Obstacle> {
+        let mut found = Vec::new();
+        self.query(area, |obstacle| found.push(obstacle));
+        found.sort_unstable_by_key(|obstacle| obstacle.entity);
+        found
+    }
+
+    /// Recomputes the candidate pair list from the broad phase
+    pub fn update_pairs(&mut self) -> &[(Entity, Entity)] {
+        self.candidate_pairs.clear();
+        self.broad_phase.collect_pairs(&mut self.candidate_pairs);
+        self.candidate_pairs.sort_unstable();
+        &self.candidate_pairs
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::{HashMap, HashSet};
+
+use specs::Entity;
+
+use super::{BroadPhase, ordered_pair};
+use crate::collision::shapes::Aabb;
+
+type Cell = (i32, i32);
+
+struct Proxy {
+    aabb: Aabb,
+    min: Cell,
+    max: Cell,
+}
+
+/// Uniform grid hashing entities into every cell their bounds touch.
+/// Works best when most colliders are about the size of a cell.
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<Entity>>,
+    proxies: HashMap<Entity, Proxy>,
+}
+
+impl Default for SpatialHash {
+    fn default() -> Self {
+        Self::new(4.0)
+    }
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            proxies: HashMap::new(),
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    fn cell_range(&self, aabb: &Aabb) -> (Cell, Cell) {
+        let cell = |v: f32| (v / self.cell_size).floor() as i32;
+        (
+            (cell(aabb.min.x), cell(aabb.min.y)),
+            (cell(aabb.max.x), cell(aabb.max.y)),
+        )
+    }
+
+    fn cells_in(min: Cell, max: Cell) -> impl Iterator<Item = Cell> {
+        (min.0..=max.0).flat_map(move |x| (min.1..=max.1).map(move |y| (x, y)))
+    }
+
+    fn add_to_cells(&mut self, entity: Entity, min: Cell, max: Cell) {
+        for cell in Self::cells_in(min, max) {
+            self.cells.entry(cell).or_default().push(entity);
+        }
+    }
+
+    fn remove_from_cells(&mut self, entity: Entity, min: Cell, max: Cell) {
+        for cell in Self::cells_in(min, max) {
+            if let Some(entities) = self.cells.get_mut(&cell) {
+                entities.retain(|&e| e != entity);
+                if entities.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+    }
+}
+
+impl BroadPhase for SpatialHash {
+    fn insert(&mut self, entity: Entity, aabb: Aabb) {
+        if self.proxies.contains_key(&entity) {
+            self.update(entity, aabb);
+            return;
+        }
+
+        let (min, max) = self.cell_range(&aabb);
+        self.add_to_cells(entity, min, max);
+        self.proxies.insert(entity, Proxy { aabb, min, max });
+    }
+
+    fn update(&mut self, entity: Entity, aabb: Aabb) -> bool {
+        let (min, max) = self.cell_range(&aabb);
+        let Some(proxy) = self.proxies.get_mut(&entity) else {
+            self.insert(entity, aabb);
+            return true;
+        };
+
+        proxy.aabb = aabb;
+        if proxy.min == min && proxy.max == max {
+            return false;
+        }
+
+        let (old_min, old_max) = (proxy.min, proxy.max);
+        proxy.min = min;
+        proxy.max = max;
+        self.remove_from_cells(entity, old_min, old_max);
+        self.add_to_cells(entity, min, max);
+        true
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        if let Some(proxy) = self.proxies.remove(&entity) {
+            self.remove_from_cells(entity, proxy.min, proxy.max);
+        }
+    }
+
+    fn contains(&self, entity: Entity) -> bool {
+        self.proxies.contains_key(&entity)
+    }
+
+    fn len(&self) -> usize {
+        self.proxies.len()
+    }
+
+    fn query(&self, aabb: &Aabb, callback: &mut dyn FnMut(Entity)) {
+        let (min, max) = self.cell_range(aabb);
+        let mut visited = HashSet::new();
+
+        for cell in Self::cells_in(min, max) {
+            for &entity in self.cells.get(&cell).into_iter().flatten() {
+                if visited.insert(entity) && self.proxies[&entity].aabb.overlaps(aabb) {
+                    callback(entity);
+                }
+            }
+        }
+    }
+
+    fn collect_pairs(&self, pairs: &mut Vec<(Entity, Entity)>) {
+        let mut found = HashSet::new();
+
+        for entities in self.cells.values() {
+            for (i, &a) in entities.iter().enumerate() {
+                let aabb_a = &self.proxies[&a].aabb;
+
+                for &b in &entities[i + 1..] {
+                    if aabb_a.overlaps(&self.proxies[&b].aabb) {
+                        found.insert(ordered_pair(a, b));
+                    }
+                }
+            }
+        }
+
+        pairs.extend(found);
+    }
+}
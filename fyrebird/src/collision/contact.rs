@@ -0,0 +1,124 @@
+use specs::Entity;
+
+use crate::arith::{Point2, Vec2, point2, vec2};
+
+/// Single point of contact between two shapes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContactPoint {
+    /// World position, halfway between both shape surfaces
+    pub position: Point2,
+    /// Overlap depth along the manifold normal, positive when the shapes intersect
+    pub penetration: f32,
+    /// Identifies the pair of features that produced this point, stable across frames
+    pub id: u32,
+}
+
+impl Default for ContactPoint {
+    fn default() -> Self {
+        Self {
+            position: point2(0.0, 0.0),
+            penetration: 0.0,
+            id: 0,
+        }
+    }
+}
+
+/// Contact geometry between two shapes, holding up to two points
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContactManifold {
+    /// Unit normal pointing from shape A towards shape B
+    pub normal: Vec2,
+    points: [ContactPoint; 2],
+    point_count: usize,
+}
+
+impl ContactManifold {
+    pub fn new(normal: Vec2) -> Self {
+        Self {
+            normal,
+            points: [ContactPoint::default(); 2],
+            point_count: 0,
+        }
+    }
+
+    /// Adds a contact point. Points past the second one are ignored.
+    pub fn push(&mut self, point: ContactPoint) {
+        if self.point_count < self.points.len() {
+            self.points[self.point_count] = point;
+            self.point_count += 1;
+        }
+    }
+
+    pub fn points(&self) -> &[ContactPoint] {
+        &self.points[..self.point_count]
+    }
+
+    /// Deepest penetration among the contact points
+    pub fn penetration(&self) -> f32 {
+        self.points()
+            .iter()
+            .map(|p| p.penetration)
+            .fold(0.0, f32::max)
+    }
+
+    /// Same manifold seen from shape B, with the normal reversed
+    pub fn flipped(mut self) -> Self {
+        self.normal = -self.normal;
+        self
+    }
+}
+
+impl Default for ContactManifold {
+    fn default() -> Self {
+        Self::new(vec2(0.0, 1.0))
+    }
+}
+
+/// Contact between the colliders of two entities
+#[derive(Debug, Clone, Copy)]
+pub struct Contact {
+    pub entity_a: Entity,
+    pub entity_b: Entity,
+    pub manifold: ContactManifold,
+}
+
+/// Resource with every contact found by the `CollisionSystem` this step
+#[derive(Debug, Default)]
+pub struct Contacts {
+    contacts: Vec<Contact>,
+}
+
+impl Contacts {
+    pub fn push(&mut self, contact: Contact) {
+        self.contacts.push(contact);
+    }
+
+    pub fn clear(&mut self) {
+        self.contacts.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Contact> {
+        self.contacts.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.contacts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contacts.is_empty()
+    }
+
+    /// Contact between two entities, in any order, with the normal pointing from `a` to `b`
+    pub fn between(&self, a: Entity, b: Entity) -> Option<ContactManifold> {
+        self.contacts.iter().find_map(|c| {
+            if c.entity_a == a && c.entity_b == b {
+                Some(c.manifold)
+            } else if c.entity_a == b && c.entity_b == a {
+                Some(c.manifold.flipped())
+            } else {
+                None
+            }
+        })
+    }
+}
@@ -0,0 +1,315 @@
+use cgmath::{EuclideanSpace, InnerSpace};
+
+use crate::arith::{EPSILON, Point2, Vec2, cross_vec2, vec2};
+
+use super::narrow_phase;
+use super::shapes::WorldShape;
+
+/// Separation under which two shapes are considered touching
+pub const CONTACT_TOLERANCE: f32 = 0.005;
+const MAX_GJK_ITERATIONS: usize = 20;
+const MAX_CAST_ITERATIONS: usize = 30;
+
+/// Closest points between two shapes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Distance {
+    /// Closest point on the surface of shape A
+    pub point_a: Point2,
+    /// Closest point on the surface of shape B
+    pub point_b: Point2,
+    /// Unit vector from A towards B, zero when the shapes overlap
+    pub normal: Vec2,
+    /// Gap between the surfaces, 0 when the shapes overlap
+    pub distance: f32,
+}
+
+/// Result of sweeping one shape against another
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CastHit {
+    /// Fraction of the translation travelled before touching, in `[0, 1]`
+    pub fraction: f32,
+    /// Contact point on the target surface
+    pub point: Point2,
+    /// Unit normal of the target surface, pointing towards the moving shape
+    pub normal: Vec2,
+}
+
+/// Vertices and rounding radius of a shape's convex core
+fn core(shape: &WorldShape) -> (Vec<Point2>, f32) {
+    match shape {
+        WorldShape::Circle { center, radius } => (vec![*center], *radius),
+        WorldShape::Capsule { a, b, radius } => (vec![*a, *b], *radius),
+        WorldShape::Polygon(polygon) => (polygon.vertices.clone(), 0.0),
+    }
+}
+
+fn support(points: &[Point2], direction: Vec2) -> usize {
+    let mut best = 0;
+    let mut best_value = f32::NEG_INFINITY;
+
+    for (i, p) in points.iter().enumerate() {
+        let value = direction.dot(p.to_vec());
+        if value > best_value {
+            best = i;
+            best_value = value;
+        }
+    }
+
+    best
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SimplexVertex {
+    /// Support point on A
+    wa: Point2,
+    /// Support point on B
+    wb: Point2,
+    /// Minkowski difference `wb - wa`
+    w: Vec2,
+    /// Barycentric weight of the closest point
+    weight: f32,
+    index_a: usize,
+    index_b: usize,
+}
+
+impl SimplexVertex {
+    fn new(a: &[Point2], b: &[Point2], index_a: usize, index_b: usize) -> Self {
+        Self {
+            wa: a[index_a],
+            wb: b[index_b],
+            w: b[index_b] - a[index_a],
+            weight: 1.0,
+            index_a,
+            index_b,
+        }
+    }
+}
+
+/// Keeps the sub-simplex of a segment closest to the origin
+fn solve2(v: &mut Vec<SimplexVertex>) {
+    let (w1, w2) = (v[0].w, v[1].w);
+    let e12 = w2 - w1;
+
+    let d12_2 = -w1.dot(e12);
+    if d12_2 <= 0.0 {
+        v.truncate(1);
+        v[0].weight = 1.0;
+        return;
+    }
+
+    let d12_1 = w2.dot(e12);
+    if d12_1 <= 0.0 {
+        v.swap_remove(0);
+        v[0].weight = 1.0;
+        return;
+    }
+
+    let inv = 1.0 / (d12_1 + d12_2);
+    v[0].weight = d12_1 * inv;
+    v[1].weight = d12_2 * inv;
+}
+
+/// Keeps the sub-simplex of a triangle closest to the origin
+fn solve3(v: &mut Vec<SimplexVertex>) {
+    let (w1, w2, w3) = (v[0].w, v[1].w, v[2].w);
+
+    let e12 = w2 - w1;
+    let d12_1 = w2.dot(e12);
+    let d12_2 = -w1.dot(e12);
+
+    let e13 = w3 - w1;
+    let d13_1 = w3.dot(e13);
+    let d13_2 = -w1.dot(e13);
+
+    let e23 = w3 - w2;
+    let d23_1 = w3.dot(e23);
+    let d23_2 = -w2.dot(e23);
+
+    let n123 = cross_vec2(e12, e13);
+    let d123_1 = n123 * cross_vec2(w2, w3);
+    let d123_2 = n123 * cross_vec2(w3, w1);
+    let d123_3 = n123 * cross_vec2(w1, w2);
+
+    let keep = |v: &mut Vec<SimplexVertex>, kept: &[(usize, f32)]| {
+        let vertices: Vec<_> = kept
+            .iter()
+            .map(|&(i, weight)| SimplexVertex { weight, ..v[i] })
+            .collect();
+        *v = vertices;
+    };
+
+    if d12_2 <= 0.0 && d13_2 <= 0.0 {
+        keep(v, &[(0, 1.0)]);
+    } else if d12_1 > 0.0 && d12_2 > 0.0 && d123_3 <= 0.0 {
+        let inv = 1.0 / (d12_1 + d12_2);
+        keep(v, &[(0, d12_1 * inv), (1, d12_2 * inv)]);
+    } else if d13_1 > 0.0 && d13_2 > 0.0 && d123_2 <= 0.0 {
+        let inv = 1.0 / (d13_1 + d13_2);
+        keep(v, &[(0, d13_1 * inv), (2, d13_2 * inv)]);
+    } else if d12_1 <= 0.0 && d23_2 <= 0.0 {
+        keep(v, &[(1, 1.0)]);
+    } else if d13_1 <= 0.0 && d23_1 <= 0.0 {
+        keep(v, &[(2, 1.0)]);
+    } else if d23_1 > 0.0 && d23_2 > 0.0 && d123_1 <= 0.0 {
+        let inv = 1.0 / (d23_1 + d23_2);
+        keep(v, &[(1, d23_1 * inv), (2, d23_2 * inv)]);
+    } else {
+        // The origin is inside the triangle
+        let inv = 1.0 / (d123_1 + d123_2 + d123_3);
+        keep(
+            v,
+            &[(0, d123_1 * inv), (1, d123_2 * inv), (2, d123_3 * inv)],
+        );
+    }
+}
+
+/// Closest points between two convex point sets (GJK). Returns equal points when they overlap.
+fn closest_core_points(a: &[Point2], b: &[Point2]) -> (Point2, Point2) {
+    let mut simplex = vec![SimplexVertex::new(a, b, 0, 0)];
+
+    for _ in 0..MAX_GJK_ITERATIONS {
+        let previous: Vec<_> = simplex.iter().map(|v| (v.index_a, v.index_b)).collect();
+
+        match simplex.len() {
+            2 => solve2(&mut simplex),
+            3 => solve3(&mut simplex),
+            _ => {}
+        }
+
+        if simplex.len() == 3 {
+            break;
+        }
+
+        // Search towards the origin from the closest feature of the simplex
+        let direction = match simplex.as_slice() {
+            [v] => -v.w,
+            [v1, v2, ..] => {
+                let e12 = v2.w - v1.w;
+                if cross_vec2(e12, -v1.w) > 0.0 {
+                    vec2(-e12.y, e12.x)
+                } else {
+                    vec2(e12.y, -e12.x)
+                }
+            }
+            [] => break,
+        };
+
+        if direction.magnitude2() < EPSILON * EPSILON {
+            break;
+        }
+
+        let vertex = SimplexVertex::new(a, b, support(a, -direction), support(b, direction));
+        if previous.contains(&(vertex.index_a, vertex.index_b)) {
+            break;
+        }
+        simplex.push(vertex);
+    }
+
+    let weighted = |point: fn(&SimplexVertex) -> Point2| {
+        let sum = simplex
+            .iter()
+            .fold(vec2(0.0, 0.0), |acc, v| acc + point(v).to_vec() * v.weight);
+        Point2::from_vec(sum)
+    };
+
+    if simplex.len() == 3 {
+        let p = weighted(|v| v.wa);
+        return (p, p);
+    }
+
+    (weighted(|v| v.wa), weighted(|v| v.wb))
+}
+
+/// Distance between the surfaces of two shapes
+pub fn distance(a: &WorldShape, b: &WorldShape) -> Distance {
+    let (core_a, radius_a) = core(a);
+    let (core_b, radius_b) = core(b);
+    let (pa, pb) = closest_core_points(&core_a, &core_b);
+
+    let d = pb - pa;
+    let core_distance = d.magnitude();
+    let radii = radius_a + radius_b;
+
+    if core_distance > radii && core_distance > EPSILON {
+        let normal = d / core_distance;
+        Distance {
+            point_a: pa + normal * radius_a,
+            point_b: pb - normal * radius_b,
+            normal,
+            distance: core_distance - radii,
+        }
+    } else {
+        let p = pa + d * 0.5;
+        Distance {
+            point_a: p,
+            point_b: p,
+            normal: vec2(0.0, 0.0),
+            distance: 0.0,
+        }
+    }
+}
+
+/// Sweeps `moving` along `translation` against `target` by conservative advancement.
+/// Shapes overlapping at the start report a hit with a fraction of 0, unless they are
+/// merely touching and the translation doesn't push them into each other.
+pub fn cast(moving: &WorldShape, translation: Vec2, target: &WorldShape) -> Option<CastHit> {
+    let length = translation.magnitude();
+    let direction = if length > EPSILON {
+        translation / length
+    } else {
+        vec2(0.0, 0.0)
+    };
+
+    if let Some(manifold) = narrow_phase::collide(moving, target) {
+        let pushing = direction.dot(manifold.normal) > EPSILON;
+        if manifold.penetration() <= CONTACT_TOLERANCE && !pushing {
+            return None;
+        }
+
+        let point = manifold
+            .points()
+            .first()
+            .map_or_else(|| moving.aabb().center(), |p| p.position);
+        return Some(CastHit {
+            fraction: 0.0,
+            point,
+            normal: -manifold.normal,
+        });
+    }
+
+    if length < EPSILON {
+        return None;
+    }
+
+    let mut travelled = 0.0;
+
+    for _ in 0..MAX_CAST_ITERATIONS {
+        let mut gap = distance(&moving.translated(direction * travelled), target);
+        if gap.normal == vec2(0.0, 0.0) {
+            // Touching surfaces have no normal, take it from slightly behind
+            let behind = direction * (travelled - CONTACT_TOLERANCE);
+            gap.normal = distance(&moving.translated(behind), target).normal;
+        }
+        let approach = direction.dot(gap.normal);
+
+        if approach <= EPSILON {
+            return None;
+        }
+
+        if gap.distance < CONTACT_TOLERANCE {
+            return Some(CastHit {
+                fraction: travelled / length,
+                point: gap.point_b,
+                normal: -gap.normal,
+            });
+        }
+
+        // Moving by the gap along the normal can't make the shapes pass through each other
+        travelled += (gap.distance - CONTACT_TOLERANCE * 0.5) / approach;
+        if travelled > length {
+            return None;
+        }
+    }
+
+    None
+}
@@ -1,16 +1,215 @@
+pub mod broad_phase;
+pub mod contact;
+pub mod distance;
+pub mod narrow_phase;
+pub mod obstacle;
+pub mod overlap;
+pub mod query;
 pub mod shapes;
 
+#[cfg(test)]
+mod tests;
+
 use specs::{Component, VecStorage};
-use specs::{Entities, ReadStorage, System};
+use specs::{Entities, Join, Read, ReadStorage, System, Write};
 use specs_derive::Component;
 
+use crate::arith::{Vec2, vec2};
+use crate::components::Transform2D;
+use crate::event::EventSystem;
+use crate::physics::material::MaterialHandle;
+use broad_phase::CollisionWorld;
+use contact::{Contact, Contacts};
+use obstacle::Obstacle;
+use overlap::{Overlap, OverlapBegin, OverlapEnd, OverlapStay, Overlaps};
+use shapes::{Aabb, Circle, Shape, WorldShape};
+
+/// Layer every collider belongs to unless told otherwise
+pub const DEFAULT_LAYER: u32 = 1;
+/// Mask accepting every layer
+pub const ALL_LAYERS: u32 = u32::MAX;
+
+/// Keeps the broad phase and obstacles in `CollisionWorld` up to date and stores the manifolds
+/// of every touching pair of solid colliders in the `Contacts` resource.
+/// Overlaps, triggers included, go to the `Overlaps` resource and their
+/// begin/stay/end transitions are dispatched through the `EventSystem`.
 pub struct CollisionSystem;
 
 impl<'a> System<'a> for CollisionSystem {
-    type SystemData = (Entities<'a>, ReadStorage<'a, Collider>);
-    fn run(&mut self, data: Self::SystemData) {}
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Transform2D>,
+        ReadStorage<'a, Collider>,
+        Write<'a, CollisionWorld>,
+        Write<'a, Contacts>,
+        Write<'a, Overlaps>,
+        Read<'a, EventSystem>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, transforms, colliders, mut collision_world, mut contacts, mut overlaps, events): Self::SystemData,
+    ) {
+        contacts.clear();
+
+        // Drop proxies of deleted entities and of entities that lost their collider
+        collision_world.retain(|entity| {
+            entities.is_alive(entity) && colliders.contains(entity) && transforms.contains(entity)
+        });
+
+        for (entity, transform, collider) in (&entities, &transforms, &colliders).join() {
+            collision_world.track(Obstacle::new(entity, collider, transform));
+        }
+
+        collision_world.update_pairs();
+        let mut current = Vec::new();
+        for &(entity_a, entity_b) in collision_world.candidate_pairs() {
+            let (Some(a), Some(b)) = (
+                collision_world.obstacle(entity_a),
+                collision_world.obstacle(entity_b),
+            ) else {
+                continue;
+            };
+
+            // Broad phase bounds may be fattened, check the tight ones first
+            if !a.accepts(b.layer, b.mask) || !a.aabb.overlaps(&b.aabb) {
+                continue;
+            }
+
+            if let Some(manifold) = narrow_phase::collide(&a.shape, &b.shape) {
+                let is_trigger = a.is_trigger || b.is_trigger;
+
+                // Triggers only report overlaps, they never produce a physical response
+                if !is_trigger {
+                    contacts.push(Contact {
+                        entity_a,
+                        entity_b,
+                        manifold,
+                    });
+                }
+
+                current.push(Overlap {
+                    entity_a,
+                    entity_b,
+                    is_trigger,
+                });
+            }
+        }
+
+        let transitions = overlaps.update(current);
+        for o in transitions.began {
+            events.dispatch(OverlapBegin {
+                entity_a: o.entity_a,
+                entity_b: o.entity_b,
+                is_trigger: o.is_trigger,
+            });
+        }
+        for o in transitions.stayed {
+            events.dispatch(OverlapStay {
+                entity_a: o.entity_a,
+                entity_b: o.entity_b,
+                is_trigger: o.is_trigger,
+            });
+        }
+        for o in transitions.ended {
+            events.dispatch(OverlapEnd {
+                entity_a: o.entity_a,
+                entity_b: o.entity_b,
+                is_trigger: o.is_trigger,
+            });
+        }
+    }
 }
 
-#[derive(Debug, Component, Default)]
+/// Collision shape attached to an entity, positioned relative to its `Transform2D`
+#[derive(Debug, Component, Clone)]
 #[storage(VecStorage)]
-pub struct Collider;
+pub struct Collider {
+    pub shape: Shape,
+    /// Offset from the transform origin in local space (scaled and rotated with the entity)
+    pub offset: Vec2,
+    /// Bits of the layers this collider belongs to
+    pub layer: u32,
+    /// Bits of the layers this collider interacts with
+    pub mask: u32,
+    /// Triggers detect overlaps without producing contacts
+    pub is_trigger: bool,
+    /// Surface properties and density, resolved through the `PhysicsMaterials` resource
+    pub material: MaterialHandle,
+}
+
+impl Default for Collider {
+    fn default() -> Self {
+        Self {
+            shape: Circle::new(0.5).into(),
+            offset: vec2(0.0, 0.0),
+            layer: DEFAULT_LAYER,
+            mask: ALL_LAYERS,
+            is_trigger: false,
+            material: MaterialHandle::default(),
+        }
+    }
+}
+
+impl Collider {
+    pub fn new(shape: impl Into<Shape>) -> Self {
+        Self {
+            shape: shape.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Sets the local offset relative to the entity's transform
+    pub fn with_offset(mut self, offset: Vec2) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Sets the layers this collider belongs to and the layers it interacts with
+    pub fn with_layers(mut self, layer: u32, mask: u32) -> Self {
+        self.layer = layer;
+        self.mask = mask;
+        self
+    }
+
+    /// Turns the collider into a trigger
+    pub fn trigger(mut self) -> Self {
+        self.is_trigger = true;
+        self
+    }
+
+    /// Sets the material used when resolving contacts and computing the body mass
+    pub fn with_material(mut self, material: MaterialHandle) -> Self {
+        self.material = material;
+        self
+    }
+
+    /// Two colliders interact only if each one's mask accepts the other's layer
+    pub fn can_collide_with(&self, other: &Collider) -> bool {
+        self.layer & other.mask != 0 && other.layer & self.mask != 0
+    }
+
+    /// Shape resolved into world space for the given transform
+    pub fn world_shape(&self, transform: &Transform2D) -> WorldShape {
+        self.shape.to_world(transform, self.offset)
+    }
+
+    /// Mass and moment of inertia about the entity origin for the given density, in local space.
+    /// The shape is scaled by `scale` like it is for contacts, the offset moves its center.
+    pub fn mass_properties(&self, density: f32, scale: Vec2) -> (f32, f32) {
+        let scaled = self.shape.to_world(
+            &Transform2D {
+                scale,
+                ..Default::default()
+            },
+            self.offset,
+        );
+        let mass = scaled.area() * density;
+        (mass, scaled.inertia(mass))
+    }
+
+    /// World space bounding box for the given transform
+    pub fn world_aabb(&self, transform: &Transform2D) -> Aabb {
+        self.world_shape(transform).aabb()
+    }
+}
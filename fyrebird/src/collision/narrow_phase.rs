@@ -0,0 +1,537 @@
+use cgmath::{EuclideanSpace, InnerSpace};
+
+use crate::arith::{EPSILON, Point2, Vec2, cross_vec2, perp_vec2, vec2};
+
+use super::contact::{ContactManifold, ContactPoint};
+use super::shapes::{WorldPolygon, WorldShape};
+
+/// Sine of the angle under which two edges are treated as parallel
+const PARALLEL_TOLERANCE: f32 = 0.005;
+/// Separation bias favouring shape A's reference face, keeps the choice stable between frames
+const REFERENCE_FACE_TOLERANCE: f32 = 0.0005;
+
+/// Narrow phase test between two world space shapes.
+/// Returns `None` if they don't touch, otherwise the manifold with its normal pointing from `a` to `b`.
+pub fn collide(a: &WorldShape, b: &WorldShape) -> Option<ContactManifold> {
+    use WorldShape::{Capsule, Circle, Polygon};
+
+    match (a, b) {
+        (
+            Circle {
+                center: ca,
+                radius: ra,
+            },
+            Circle {
+                center: cb,
+                radius: rb,
+            },
+        ) => collide_circles(*ca, *ra, *cb, *rb),
+        (
+            Capsule {
+                a: a0,
+                b: a1,
+                radius: ra,
+            },
+            Circle { center, radius },
+        ) => collide_circles(
+            closest_point_on_segment(*center, *a0, *a1),
+            *ra,
+            *center,
+            *radius,
+        ),
+        (
+            Capsule {
+                a: a0,
+                b: a1,
+                radius: ra,
+            },
+            Capsule {
+                a: b0,
+                b: b1,
+                radius: rb,
+            },
+        ) => collide_capsules(*a0, *a1, *ra, *b0, *b1, *rb),
+        (Polygon(polygon), Circle { center, radius }) => {
+            collide_polygon_circle(polygon, *center, *radius)
+        }
+        (Polygon(polygon), Capsule { a, b, radius }) => {
+            collide_polygon_capsule(polygon, *a, *b, *radius)
+        }
+        (Polygon(pa), Polygon(pb)) => collide_hulls(&Hull::from(pa), &Hull::from(pb)),
+        (Circle { .. }, Capsule { .. } | Polygon(_)) | (Capsule { .. }, Polygon(_)) => {
+            collide(b, a).map(ContactManifold::flipped)
+        }
+    }
+}
+
+/// Closest point to `p` on the segment `ab`
+pub(crate) fn closest_point_on_segment(p: Point2, a: Point2, b: Point2) -> Point2 {
+    let ab = b - a;
+    let len2 = ab.magnitude2();
+
+    if len2 < EPSILON {
+        return a;
+    }
+
+    let t = ((p - a).dot(ab) / len2).clamp(0.0, 1.0);
+    a + ab * t
+}
+
+/// Closest pair of points between segments `p1q1` and `p2q2`
+pub(crate) fn closest_points_on_segments(
+    p1: Point2,
+    q1: Point2,
+    p2: Point2,
+    q2: Point2,
+) -> (Point2, Point2) {
+    let d1 = q1 - p1;
+    let d2 = q2 - p2;
+    let r = p1 - p2;
+    let a = d1.magnitude2();
+    let e = d2.magnitude2();
+    let f = d2.dot(r);
+
+    if a <= EPSILON && e <= EPSILON {
+        return (p1, p2);
+    }
+
+    let (s, t) = if a <= EPSILON {
+        (0.0, (f / e).clamp(0.0, 1.0))
+    } else {
+        let c = d1.dot(r);
+
+        if e <= EPSILON {
+            ((-c / a).clamp(0.0, 1.0), 0.0)
+        } else {
+            let b = d1.dot(d2);
+            let denom = a * e - b * b;
+            let s = if denom > EPSILON {
+                ((b * f - c * e) / denom).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let t = (b * s + f) / e;
+
+            if t < 0.0 {
+                ((-c / a).clamp(0.0, 1.0), 0.0)
+            } else if t > 1.0 {
+                (((b - c) / a).clamp(0.0, 1.0), 1.0)
+            } else {
+                (s, t)
+            }
+        }
+    };
+
+    (p1 + d1 * s, p2 + d2 * t)
+}
+
+fn midpoint(a: Point2, b: Point2) -> Point2 {
+    a + (b - a) * 0.5
+}
+
+/// Parameter range along `a0a1` covered by the projection of `b0b1`, if both segments are parallel
+fn parallel_overlap(a0: Point2, a1: Point2, b0: Point2, b1: Point2) -> Option<(f32, f32)> {
+    let da = a1 - a0;
+    let db = b1 - b0;
+
+    if da.magnitude2() < EPSILON
+        || db.magnitude2() < EPSILON
+        || cross_vec2(da.normalize(), db.normalize()).abs() > PARALLEL_TOLERANCE
+    {
+        return None;
+    }
+
+    let len2 = da.magnitude2();
+    let t0 = (b0 - a0).dot(da) / len2;
+    let t1 = (b1 - a0).dot(da) / len2;
+    let lo = t0.min(t1).max(0.0);
+    let hi = t0.max(t1).min(1.0);
+
+    (hi - lo > 1e-3).then_some((lo, hi))
+}
+
+fn collide_circles(ca: Point2, ra: f32, cb: Point2, rb: f32) -> Option<ContactManifold> {
+    let d = cb - ca;
+    let dist2 = d.magnitude2();
+    let total = ra + rb;
+
+    if dist2 > total * total {
+        return None;
+    }
+
+    let dist = dist2.sqrt();
+    let normal = if dist > EPSILON {
+        d / dist
+    } else {
+        vec2(0.0, 1.0)
+    };
+
+    let mut manifold = ContactManifold::new(normal);
+    manifold.push(ContactPoint {
+        position: midpoint(ca + normal * ra, cb - normal * rb),
+        penetration: total - dist,
+        id: 0,
+    });
+    Some(manifold)
+}
+
+fn collide_capsules(
+    a0: Point2,
+    a1: Point2,
+    ra: f32,
+    b0: Point2,
+    b1: Point2,
+    rb: f32,
+) -> Option<ContactManifold> {
+    let (pa, pb) = closest_points_on_segments(a0, a1, b0, b1);
+    let total = ra + rb;
+    let d = pb - pa;
+    let dist2 = d.magnitude2();
+
+    if dist2 > total * total {
+        return None;
+    }
+
+    let dist = dist2.sqrt();
+    let da = a1 - a0;
+    let db = b1 - b0;
+
+    let normal = if dist > EPSILON {
+        d / dist
+    } else {
+        // Crossing cores: separate along A's side facing B
+        let axis = if da.magnitude2() > EPSILON {
+            perp_vec2(da).normalize()
+        } else if db.magnitude2() > EPSILON {
+            perp_vec2(db).normalize()
+        } else {
+            vec2(0.0, 1.0)
+        };
+        let to_b = midpoint(b0, b1) - midpoint(a0, a1);
+        if axis.dot(to_b) < 0.0 { -axis } else { axis }
+    };
+
+    let mut manifold = ContactManifold::new(normal);
+
+    // Capsules lying side by side touch along an interval, keep both ends of it
+    if let Some((lo, hi)) = parallel_overlap(a0, a1, b0, b1) {
+        for (id, t) in [lo, hi].into_iter().enumerate() {
+            let p = a0 + da * t;
+            let q = closest_point_on_segment(p, b0, b1);
+            let separation = (q - p).dot(normal);
+
+            if separation <= total {
+                manifold.push(ContactPoint {
+                    position: midpoint(p + normal * ra, q - normal * rb),
+                    penetration: total - separation,
+                    id: id as u32,
+                });
+            }
+        }
+    }
+
+    if manifold.points().is_empty() {
+        manifold.push(ContactPoint {
+            position: midpoint(pa + normal * ra, pb - normal * rb),
+            penetration: total - dist,
+            id: 2,
+        });
+    }
+
+    Some(manifold)
+}
+
+fn collide_polygon_circle(
+    polygon: &WorldPolygon,
+    center: Point2,
+    radius: f32,
+) -> Option<ContactManifold> {
+    let count = polygon.vertices.len();
+    let mut separation = f32::NEG_INFINITY;
+    let mut edge = 0;
+
+    for i in 0..count {
+        let s = polygon.normals[i].dot(center - polygon.vertices[i]);
+        if s > radius {
+            return None;
+        }
+        if s > separation {
+            separation = s;
+            edge = i;
+        }
+    }
+
+    let v1 = polygon.vertices[edge];
+    let v2 = polygon.vertices[(edge + 1) % count];
+    let face_normal = polygon.normals[edge];
+
+    // Outside the polygon, the center may be closest to one of the face vertices
+    let vertex = if separation <= EPSILON {
+        None
+    } else if (center - v1).dot(v2 - v1) <= 0.0 {
+        Some(v1)
+    } else if (center - v2).dot(v1 - v2) <= 0.0 {
+        Some(v2)
+    } else {
+        None
+    };
+
+    let (normal, surface) = match vertex {
+        Some(v) => {
+            let d = center - v;
+            if d.magnitude2() > radius * radius {
+                return None;
+            }
+            (d.normalize(), v)
+        }
+        None => (face_normal, center - face_normal * separation),
+    };
+
+    let distance = (center - surface).dot(normal);
+    let mut manifold = ContactManifold::new(normal);
+    manifold.push(ContactPoint {
+        position: midpoint(surface, center - normal * radius),
+        penetration: radius - distance,
+        id: edge as u32,
+    });
+    Some(manifold)
+}
+
+fn collide_polygon_capsule(
+    polygon: &WorldPolygon,
+    a: Point2,
+    b: Point2,
+    radius: f32,
+) -> Option<ContactManifold> {
+    let axis = b - a;
+    if axis.magnitude2() < EPSILON {
+        return collide_polygon_circle(polygon, a, radius);
+    }
+
+    let side = perp_vec2(axis).normalize();
+    let capsule_vertices = [a, b];
+    let capsule_normals = [side, -side];
+    let capsule = Hull {
+        vertices: &capsule_vertices,
+        normals: &capsule_normals,
+        radius,
+    };
+    let core = Hull::from(polygon);
+
+    let separation = max_separation(&core, &capsule)
+        .1
+        .max(max_separation(&capsule, &core).1);
+
+    if separation > radius {
+        return None;
+    }
+
+    // Overlapping cores are handled like two polygons, the radius only deepens the contact
+    if separation <= 0.0 {
+        return collide_hulls(&core, &capsule);
+    }
+
+    // Otherwise the contact comes from the closest pair of features
+    let count = polygon.vertices.len();
+    let (edge, pa, pb) = (0..count)
+        .map(|i| {
+            let (p, q) = closest_points_on_segments(
+                polygon.vertices[i],
+                polygon.vertices[(i + 1) % count],
+                a,
+                b,
+            );
+            (i, p, q)
+        })
+        .min_by(|x, y| {
+            (x.2 - x.1)
+                .magnitude2()
+                .total_cmp(&(y.2 - y.1).magnitude2())
+        })?;
+
+    let d = pb - pa;
+    let dist = d.magnitude();
+    if dist > radius {
+        return None;
+    }
+
+    let normal = d / dist;
+    let mut manifold = ContactManifold::new(normal);
+    let v1 = polygon.vertices[edge];
+    let v2 = polygon.vertices[(edge + 1) % count];
+
+    // A capsule lying flat on an edge touches along an interval, keep both ends of it
+    if polygon.normals[edge].dot(normal) > 1.0 - PARALLEL_TOLERANCE
+        && let Some((lo, hi)) = parallel_overlap(v1, v2, a, b)
+    {
+        for (id, t) in [lo, hi].into_iter().enumerate() {
+            let p = v1 + (v2 - v1) * t;
+            let q = closest_point_on_segment(p, a, b);
+            let separation = (q - p).dot(normal);
+
+            if separation <= radius {
+                manifold.push(ContactPoint {
+                    position: midpoint(p, q - normal * radius),
+                    penetration: radius - separation,
+                    id: ((edge as u32) << 8) | id as u32,
+                });
+            }
+        }
+    }
+
+    if manifold.points().is_empty() {
+        manifold.push(ContactPoint {
+            position: midpoint(pa, pb - normal * radius),
+            penetration: radius - dist,
+            id: ((edge as u32) << 8) | 2,
+        });
+    }
+
+    Some(manifold)
+}
+
+/// Convex core with a rounding radius. Polygons have no radius, capsules are two vertex hulls.
+struct Hull<'a> {
+    vertices: &'a [Point2],
+    normals: &'a [Vec2],
+    radius: f32,
+}
+
+impl<'a> From<&'a WorldPolygon> for Hull<'a> {
+    fn from(polygon: &'a WorldPolygon) -> Self {
+        Self {
+            vertices: &polygon.vertices,
+            normals: &polygon.normals,
+            radius: 0.0,
+        }
+    }
+}
+
+/// Face of `a` with the largest separation from the core of `b`
+fn max_separation(a: &Hull, b: &Hull) -> (usize, f32) {
+    let mut best = (0, f32::NEG_INFINITY);
+
+    for (i, (normal, v)) in a.normals.iter().zip(a.vertices).enumerate() {
+        let separation = b
+            .vertices
+            .iter()
+            .map(|w| normal.dot(w - v))
+            .fold(f32::INFINITY, f32::min);
+
+        if separation > best.1 {
+            best = (i, separation);
+        }
+    }
+
+    best
+}
+
+#[derive(Clone, Copy)]
+struct ClipVertex {
+    point: Point2,
+    id: u32,
+}
+
+/// Keeps the part of the segment behind the plane `dot(normal, p) = offset`
+fn clip_segment(
+    segment: [ClipVertex; 2],
+    normal: Vec2,
+    offset: f32,
+    clip_id: u32,
+) -> Option<[ClipVertex; 2]> {
+    let [v0, v1] = segment;
+    let d0 = normal.dot(v0.point.to_vec()) - offset;
+    let d1 = normal.dot(v1.point.to_vec()) - offset;
+
+    match (d0 <= 0.0, d1 <= 0.0) {
+        (true, true) => Some(segment),
+        (false, false) => None,
+        (inside0, _) => {
+            let t = d0 / (d0 - d1);
+            let clipped = ClipVertex {
+                point: v0.point + (v1.point - v0.point) * t,
+                id: clip_id,
+            };
+            Some(if inside0 {
+                [v0, clipped]
+            } else {
+                [clipped, v1]
+            })
+        }
+    }
+}
+
+/// SAT test followed by clipping of the incident edge against the reference face
+fn collide_hulls(a: &Hull, b: &Hull) -> Option<ContactManifold> {
+    let total = a.radius + b.radius;
+
+    let (edge_a, separation_a) = max_separation(a, b);
+    if separation_a > total {
+        return None;
+    }
+
+    let (edge_b, separation_b) = max_separation(b, a);
+    if separation_b > total {
+        return None;
+    }
+
+    let (reference, incident, edge, flip) =
+        if separation_b > separation_a + REFERENCE_FACE_TOLERANCE {
+            (b, a, edge_b, true)
+        } else {
+            (a, b, edge_a, false)
+        };
+
+    let ref_normal = reference.normals[edge];
+    let ref_v1 = reference.vertices[edge];
+    let ref_v2 = reference.vertices[(edge + 1) % reference.vertices.len()];
+
+    // The incident edge is the one most opposed to the reference normal
+    let incident_edge = (0..incident.normals.len()).min_by(|&i, &j| {
+        ref_normal
+            .dot(incident.normals[i])
+            .total_cmp(&ref_normal.dot(incident.normals[j]))
+    })?;
+    let next = (incident_edge + 1) % incident.vertices.len();
+    let incident_segment = [
+        ClipVertex {
+            point: incident.vertices[incident_edge],
+            id: incident_edge as u32,
+        },
+        ClipVertex {
+            point: incident.vertices[next],
+            id: next as u32,
+        },
+    ];
+
+    // Clip against the side planes of the reference face
+    let tangent = (ref_v2 - ref_v1).normalize();
+    let clipped = clip_segment(
+        incident_segment,
+        -tangent,
+        -tangent.dot(ref_v1.to_vec()) + total,
+        0x40,
+    )?;
+    let clipped = clip_segment(clipped, tangent, tangent.dot(ref_v2.to_vec()) + total, 0x41)?;
+
+    let front_offset = ref_normal.dot(ref_v1.to_vec());
+    let normal = if flip { -ref_normal } else { ref_normal };
+    let mut manifold = ContactManifold::new(normal);
+
+    for vertex in clipped {
+        let separation = ref_normal.dot(vertex.point.to_vec()) - front_offset;
+
+        if separation <= total {
+            let on_reference = vertex.point - ref_normal * (separation - reference.radius);
+            let on_incident = vertex.point - ref_normal * incident.radius;
+
+            manifold.push(ContactPoint {
+                position: midpoint(on_reference, on_incident),
+                penetration: total - separation,
+                id: ((flip as u32) << 16) | ((edge as u32) << 8) | vertex.id,
+            });
+        }
+    }
+
+    (!manifold.points().is_empty()).then_some(manifold)
+}
@@ -0,0 +1,44 @@
+use specs::Entity;
+
+use crate::components::Transform2D;
+use crate::physics::material::MaterialHandle;
+
+use super::Collider;
+use super::shapes::{Aabb, WorldShape};
+
+/// Collider resolved into world space, as held by the `CollisionWorld`.
+/// The `CollisionWorld` is refreshed by the `CollisionSystem` every fixed step and by the
+/// `Physics2DSystem` for the bodies it moves. A transform changed by hand is picked up on the
+/// next step.
+#[derive(Debug, Clone)]
+pub struct Obstacle {
+    pub entity: Entity,
+    pub shape: WorldShape,
+    /// Tight bounds of the shape
+    pub aabb: Aabb,
+    pub layer: u32,
+    pub mask: u32,
+    pub is_trigger: bool,
+    pub material: MaterialHandle,
+}
+
+impl Obstacle {
+    pub fn new(entity: Entity, collider: &Collider, transform: &Transform2D) -> Self {
+        let shape = collider.world_shape(transform);
+        Self {
+            entity,
+            aabb: shape.aabb(),
+            shape,
+            layer: collider.layer,
+            mask: collider.mask,
+            is_trigger: collider.is_trigger,
+            material: collider.material,
+        }
+    }
+
+    /// Returns true if something on `layer` interacting with `mask` collides with the obstacle,
+    /// like `Collider::can_collide_with`
+    pub fn accepts(&self, layer: u32, mask: u32) -> bool {
+        self.layer & mask != 0 && layer & self.mask != 0
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::HashSet;
+
+use specs::Entity;
+
+/// Pair of colliders touching during the current step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Overlap {
+    pub entity_a: Entity,
+    pub entity_b: Entity,
+    /// True if at least one of the colliders is a trigger
+    pub is_trigger: bool,
+}
+
+impl Overlap {
+    /// The entity on the other side of the pair, if `entity` is part of it
+    pub fn other(&self, entity: Entity) -> Option<Entity> {
+        if self.entity_a == entity {
+            Some(self.entity_b)
+        } else if self.entity_b == entity {
+            Some(self.entity_a)
+        } else {
+            None
+        }
+    }
+}
+
+/// Sent through the `EventSystem` on the first step two colliders touch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverlapBegin {
+    pub entity_a: Entity,
+    pub entity_b: Entity,
+    pub is_trigger: bool,
+}
+
+/// Sent through the `EventSystem` on every following step the colliders keep touching
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverlapStay {
+    pub entity_a: Entity,
+    pub entity_b: Entity,
+    pub is_trigger: bool,
+}
+
+/// Sent through the `EventSystem` on the first step the colliders stop touching,
+/// including when one of them is deleted or loses its collider
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverlapEnd {
+    pub entity_a: Entity,
+    pub entity_b: Entity,
+    pub is_trigger: bool,
+}
+
+/// Resource with every overlapping pair of colliders, triggers included, for the current step
+#[derive(Debug, Default)]
+pub struct Overlaps {
+    current: Vec<Overlap>,
+}
+
+/// Changes between two consecutive steps
+#[derive(Debug, Default)]
+pub struct OverlapTransitions {
+    pub began: Vec<Overlap>,
+    pub stayed: Vec<Overlap>,
+    pub ended: Vec<Overlap>,
+}
+
+impl Overlaps {
+    pub fn iter(&self) -> impl Iterator<Item = &Overlap> {
+        self.current.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.current.is_empty()
+    }
+
+    pub fn is_overlapping(&self, a: Entity, b: Entity) -> bool {
+        self.current.iter().any(|o| o.other(a) == Some(b))
+    }
+
+    /// Every entity currently touching `entity`
+    pub fn overlapping(&self, entity: Entity) -> impl Iterator<Item = Entity> + '_ {
+        self.current.iter().filter_map(move |o| o.other(entity))
+    }
+
+    /// Replaces the overlaps with the ones of a new step and reports what changed
+    pub fn update(&mut self, overlaps: Vec<Overlap>) -> OverlapTransitions {
+        let key = |o: &Overlap| (o.entity_a, o.entity_b);
+        let previous: HashSet<_> = self.current.iter().map(key).collect();
+        let next: HashSet<_> = overlaps.iter().map(key).collect();
+
+        let mut transitions = OverlapTransitions::default();
+        for overlap in &overlaps {
+            if previous.contains(&key(overlap)) {
+                transitions.stayed.push(*overlap);
+            } else {
+                transitions.began.push(*overlap);
+            }
+        }
+        transitions.ended = self
+            .current
+            .iter()
+            .filter(|o| !next.contains(&key(o)))
+            .copied()
+            .collect();
+
+        self.current = overlaps;
+        transitions
+    }
+}
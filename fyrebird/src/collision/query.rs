@@ -0,0 +1,388 @@
+use cgmath::InnerSpace;
+use specs::shred::ResourceId;
+use specs::{Entities, Entity, Read, SystemData, World};
+
+use crate::arith::ray::{Ray, Ray2D};
+use crate::arith::{EPSILON, Point2, Vec2, cross_vec2, perp_vec2, vec2};
+use crate::components::Transform2D;
+
+use super::broad_phase::CollisionWorld;
+use super::contact::ContactManifold;
+use super::obstacle::Obstacle;
+use super::shapes::{Aabb, Shape, WorldShape};
+use super::{ALL_LAYERS, Collider, distance, narrow_phase};
+
+/// Selects which colliders a scene query can return
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryFilter {
+    /// Only colliders on one of these layers are considered
+    pub mask: u32,
+    /// Layers of the shape asking, colliders whose mask rejects them are skipped.
+    /// Masks are ignored if `None`.
+    pub layer: Option<u32>,
+    /// Triggers are skipped unless set
+    pub include_triggers: bool,
+    /// Entity ignored by the query, usually the one asking
+    pub exclude: Option<Entity>,
+}
+
+impl Default for QueryFilter {
+    fn default() -> Self {
+        Self {
+            mask: ALL_LAYERS,
+            layer: None,
+            include_triggers: false,
+            exclude: None,
+        }
+    }
+}
+
+impl QueryFilter {
+    pub fn new(mask: u32) -> Self {
+        Self {
+            mask,
+            ..Default::default()
+        }
+    }
+
+    /// Considers the colliders `collider`, attached to `entity`, can collide with
+    pub fn for_collider(entity: Entity, collider: &Collider) -> Self {
+        Self::new(collider.mask)
+            .with_layer(collider.layer)
+            .excluding(entity)
+    }
+
+    pub fn with_layer(mut self, layer: u32) -> Self {
+        self.layer = Some(layer);
+        self
+    }
+
+    pub fn with_triggers(mut self) -> Self {
+        self.include_triggers = true;
+        self
+    }
+
+    pub fn excluding(mut self, entity: Entity) -> Self {
+        self.exclude = Some(entity);
+        self
+    }
+
+    pub fn accepts(&self, obstacle: &Obstacle) -> bool {
+        obstacle.layer & self.mask != 0
+            && self.layer.is_none_or(|layer| layer & obstacle.mask != 0)
+            && (self.include_triggers || !obstacle.is_trigger)
+            && self.exclude != Some(obstacle.entity)
+    }
+}
+
+/// Collider hit by a ray or shape cast
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QueryHit {
+    pub entity: Entity,
+    /// World position of the hit on the collider surface
+    pub point: Point2,
+    /// Unit surface normal at the hit, facing the query
+    pub normal: Vec2,
+    /// Distance travelled along the query direction before hitting
+    pub distance: f32,
+}
+
+/// Ray casts, shape casts and overlap tests against every collider in the world.
+/// Use it as system data, or get one from `Scene::query`.
+/// Colliders are found through the broad phase of the `CollisionWorld`, so they are seen where
+/// they were at the end of the last fixed step.
+pub struct PhysicsQuery<'a> {
+    entities: Entities<'a>,
+    collision_world: Read<'a, CollisionWorld>,
+}
+
+type QueryData<'a> = (Entities<'a>, Read<'a, CollisionWorld>);
+
+impl<'a> SystemData<'a> for PhysicsQuery<'a> {
+    fn setup(world: &mut World) {
+        QueryData::setup(world);
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        let (entities, collision_world) = QueryData::fetch(world);
+        Self {
+            entities,
+            collision_world,
+        }
+    }
+
+    fn reads() -> Vec<ResourceId> {
+        QueryData::reads()
+    }
+
+    fn writes() -> Vec<ResourceId> {
+        QueryData::writes()
+    }
+}
+
+impl PhysicsQuery<'_> {
+    /// Colliders accepted by the filter whose bounds overlap `area`
+    fn candidates(&self, area: &Aabb, filter: &QueryFilter) -> Vec<&Obstacle> {
+        let mut found = self.collision_world.obstacles(area);
+        found
+            .retain(|obstacle| self.entities.is_alive(obstacle.entity) && filter.accepts(obstacle));
+        found
+    }
+
+    /// Closest collider hit by the ray within `max_distance`.
+    /// Colliders containing the ray origin are ignored.
+    pub fn raycast(&self, ray: &Ray2D, max_distance: f32, filter: QueryFilter) -> Option<QueryHit> {
+        self.raycast_all(ray, max_distance, filter)
+            .into_iter()
+            .next()
+    }
+
+    /// Every collider hit by the ray within `max_distance`, closest first
+    pub fn raycast_all(
+        &self,
+        ray: &Ray2D,
+        max_distance: f32,
+        filter: QueryFilter,
+    ) -> Vec<QueryHit> {
+        let bounds = Aabb::from_points([ray.origin(), ray.at(max_distance)]);
+
+        let mut hits: Vec<_> = self
+            .candidates(&bounds, &filter)
+            .into_iter()
+            .filter_map(|obstacle| {
+                raycast_shape(&obstacle.shape, ray, max_distance).map(|(distance, normal)| {
+                    QueryHit {
+                        entity: obstacle.entity,
+                        point: ray.at(distance),
+                        normal,
+                        distance,
+                    }
+                })
+            })
+            .collect();
+
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        hits
+    }
+
+    /// Sweeps `shape`, placed at `transform`, along `direction` and returns the first collider
+    /// it touches within `max_distance`. Colliders already overlapping the shape are hit at 0.
+    /// A zero direction sweeps nothing and returns `None`, use `overlap_shape` instead.
+    pub fn shape_cast(
+        &self,
+        shape: &Shape,
+        transform: &Transform2D,
+        direction: Vec2,
+        max_distance: f32,
+        filter: QueryFilter,
+    ) -> Option<QueryHit> {
+        if direction.magnitude2() < EPSILON {
+            return None;
+        }
+        let moving = shape.to_world(transform, vec2(0.0, 0.0));
+        self.sweep(&moving, direction.normalize() * max_distance, filter)
+    }
+
+    /// Moves a shape already in world space by `translation` and returns the first collider
+    /// it touches. Colliders already overlapping the shape are hit at 0.
+    pub fn sweep(
+        &self,
+        moving: &WorldShape,
+        translation: Vec2,
+        filter: QueryFilter,
+    ) -> Option<QueryHit> {
+        let length = translation.magnitude();
+        let start = moving.aabb();
+        let swept = start.union(&Aabb::new(start.min + translation, start.max + translation));
+
+        self.candidates(&swept, &filter)
+            .into_iter()
+            .filter_map(|obstacle| {
+                distance::cast(moving, translation, &obstacle.shape).map(|hit| QueryHit {
+                    entity: obstacle.entity,
+                    point: hit.point,
+                    normal: hit.normal,
+                    distance: hit.fraction * length,
+                })
+            })
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+
+    /// Every collider containing the point
+    pub fn overlap_point(&self, point: Point2, filter: QueryFilter) -> Vec<Entity> {
+        self.candidates(&Aabb::new(point, point), &filter)
+            .into_iter()
+            .filter(|obstacle| obstacle.shape.contains_point(point))
+            .map(|obstacle| obstacle.entity)
+            .collect()
+    }
+
+    /// Every collider whose bounds overlap the area
+    pub fn overlap_aabb(&self, area: &Aabb, filter: QueryFilter) -> Vec<Entity> {
+        self.candidates(area, &filter)
+            .into_iter()
+            .map(|obstacle| obstacle.entity)
+            .collect()
+    }
+
+    /// Every collider touching `shape` placed at `transform`
+    pub fn overlap_shape(
+        &self,
+        shape: &Shape,
+        transform: &Transform2D,
+        filter: QueryFilter,
+    ) -> Vec<Entity> {
+        let query = shape.to_world(transform, vec2(0.0, 0.0));
+
+        self.overlap_manifolds(&query, filter)
+            .into_iter()
+            .map(|(entity, _)| entity)
+            .collect()
+    }
+
+    /// Every collider touching a shape already in world space, with the manifold of the contact.
+    /// The manifold normals point from the shape to the collider.
+    pub fn overlap_manifolds(
+        &self,
+        shape: &WorldShape,
+        filter: QueryFilter,
+    ) -> Vec<(Entity, ContactManifold)> {
+        self.candidates(&shape.aabb(), &filter)
+            .into_iter()
+            .filter_map(|obstacle| {
+                narrow_phase::collide(shape, &obstacle.shape).map(|m| (obstacle.entity, m))
+            })
+            .collect()
+    }
+}
+
+/// Distance along the ray and surface normal of the first intersection with `shape`.
+/// Rays starting inside the shape don't hit it.
+pub(crate) fn raycast_shape(
+    shape: &WorldShape,
+    ray: &Ray2D,
+    max_distance: f32,
+) -> Option<(f32, Vec2)> {
+    let origin = ray.origin();
+    let direction = ray.direction();
+
+    match shape {
+        WorldShape::Circle { center, radius } => {
+            raycast_circle(*center, *radius, origin, direction, max_distance)
+        }
+        WorldShape::Capsule { a, b, radius } if *radius <= EPSILON => {
+            raycast_segment(*a, *b, origin, direction, max_distance)
+        }
+        WorldShape::Capsule { a, b, radius } => {
+            if shape.contains_point(origin) {
+                return None;
+            }
+
+            let axis = b - a;
+            let side = if axis.magnitude2() > EPSILON {
+                perp_vec2(axis).normalize() * *radius
+            } else {
+                vec2(0.0, 0.0)
+            };
+
+            [
+                raycast_circle(*a, *radius, origin, direction, max_distance),
+                raycast_circle(*b, *radius, origin, direction, max_distance),
+                raycast_segment(a + side, b + side, origin, direction, max_distance),
+                raycast_segment(a - side, b - side, origin, direction, max_distance),
+            ]
+            .into_iter()
+            .flatten()
+            .min_by(|x, y| x.0.total_cmp(&y.0))
+        }
+        WorldShape::Polygon(polygon) => {
+            // Clip the ray against every edge plane (Cyrus-Beck)
+            let mut lower = 0.0;
+            let mut upper = max_distance;
+            let mut hit_edge = None;
+
+            for (i, (vertex, normal)) in polygon.vertices.iter().zip(&polygon.normals).enumerate() {
+                let numerator = normal.dot(vertex - origin);
+                let denominator = normal.dot(direction);
+
+                if denominator.abs() < EPSILON {
+                    if numerator < 0.0 {
+                        return None;
+                    }
+                } else if denominator < 0.0 && numerator < lower * denominator {
+                    lower = numerator / denominator;
+                    hit_edge = Some(i);
+                } else if denominator > 0.0 && numerator < upper * denominator {
+                    upper = numerator / denominator;
+                }
+
+                if upper < lower {
+                    return None;
+                }
+            }
+
+            hit_edge.map(|i| (lower, polygon.normals[i]))
+        }
+    }
+}
+
+fn raycast_circle(
+    center: Point2,
+    radius: f32,
+    origin: Point2,
+    direction: Vec2,
+    max_distance: f32,
+) -> Option<(f32, Vec2)> {
+    let m = origin - center;
+    let b = m.dot(direction);
+    let c = m.magnitude2() - radius * radius;
+
+    // Starting inside, or outside and pointing away
+    if c <= 0.0 || b > 0.0 {
+        return None;
+    }
+
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let t = -b - discriminant.sqrt();
+    if t > max_distance {
+        return None;
+    }
+
+    let normal = (origin + direction * t - center) / radius;
+    Some((t.max(0.0), normal))
+}
+
+fn raycast_segment(
+    a: Point2,
+    b: Point2,
+    origin: Point2,
+    direction: Vec2,
+    max_distance: f32,
+) -> Option<(f32, Vec2)> {
+    let edge = b - a;
+    let denominator = cross_vec2(direction, edge);
+    if denominator.abs() < EPSILON {
+        return None;
+    }
+
+    let d = a - origin;
+    let t = cross_vec2(d, edge) / denominator;
+    let s = cross_vec2(d, direction) / denominator;
+    if !(0.0..=max_distance).contains(&t) || !(0.0..=1.0).contains(&s) {
+        return None;
+    }
+
+    let normal = perp_vec2(edge).normalize();
+    Some((
+        t,
+        if normal.dot(direction) > 0.0 {
+            -normal
+        } else {
+            normal
+        },
+    ))
+}
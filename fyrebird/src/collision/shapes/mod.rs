@@ -1 +1,523 @@
+use std::f32::consts::PI;
 
+use cgmath::InnerSpace;
+
+use crate::arith::{EPSILON, Point2, Vec2, perp_vec2, point2, rad, rotate_vec2, vec2};
+use crate::components::Transform2D;
+
+/// Axis-aligned bounding box in world space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point2,
+    pub max: Point2,
+}
+
+impl Aabb {
+    pub fn new(min: Point2, max: Point2) -> Self {
+        Self { min, max }
+    }
+
+    /// Create a box from its center and half extents
+    pub fn from_center(center: Point2, half_extents: Vec2) -> Self {
+        Self {
+            min: center - half_extents,
+            max: center + half_extents,
+        }
+    }
+
+    /// Smallest box containing every point. Returns an inverted (empty) box for no points.
+    pub fn from_points(points: impl IntoIterator<Item = Point2>) -> Self {
+        let empty = Self::new(
+            point2(f32::INFINITY, f32::INFINITY),
+            point2(f32::NEG_INFINITY, f32::NEG_INFINITY),
+        );
+
+        points.into_iter().fold(empty, |aabb, p| Self {
+            min: point2(aabb.min.x.min(p.x), aabb.min.y.min(p.y)),
+            max: point2(aabb.max.x.max(p.x), aabb.max.y.max(p.y)),
+        })
+    }
+
+    pub fn center(&self) -> Point2 {
+        point2(
+            (self.min.x + self.max.x) * 0.5,
+            (self.min.y + self.max.y) * 0.5,
+        )
+    }
+
+    pub fn half_extents(&self) -> Vec2 {
+        (self.max - self.min) * 0.5
+    }
+
+    /// Returns true if both boxes intersect (touching counts as intersecting)
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+    }
+
+    pub fn contains_point(&self, p: Point2) -> bool {
+        p.x >= self.min.x && p.x <= self.max.x && p.y >= self.min.y && p.y <= self.max.y
+    }
+
+    /// Returns true if `other` lies completely inside this box
+    pub fn contains(&self, other: &Aabb) -> bool {
+        self.min.x <= other.min.x
+            && self.min.y <= other.min.y
+            && self.max.x >= other.max.x
+            && self.max.y >= other.max.y
+    }
+
+    /// Smallest box containing both boxes
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Self {
+            min: point2(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            max: point2(self.max.x.max(other.max.x), self.max.y.max(other.max.y)),
+        }
+    }
+
+    /// Grows the box by `margin` on every side
+    pub fn expanded(&self, margin: f32) -> Aabb {
+        Self {
+            min: self.min - vec2(margin, margin),
+            max: self.max + vec2(margin, margin),
+        }
+    }
+
+    pub fn perimeter(&self) -> f32 {
+        let size = self.max - self.min;
+        2.0 * (size.x + size.y)
+    }
+}
+
+/// Circle centered on the collider origin
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Circle {
+    pub radius: f32,
+}
+
+impl Circle {
+    pub fn new(radius: f32) -> Self {
+        Self { radius }
+    }
+}
+
+/// Box that always stays aligned to the world axes, ignoring the transform rotation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisAlignedBox {
+    pub half_extents: Vec2,
+}
+
+impl AxisAlignedBox {
+    pub fn new(half_extents: Vec2) -> Self {
+        Self { half_extents }
+    }
+}
+
+/// Box that rotates with the transform, with an extra local rotation in radians
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrientedBox {
+    pub half_extents: Vec2,
+    pub rotation: f32,
+}
+
+impl OrientedBox {
+    pub fn new(half_extents: Vec2, rotation: f32) -> Self {
+        Self {
+            half_extents,
+            rotation,
+        }
+    }
+}
+
+/// Capsule aligned to the local Y axis. `half_height` excludes the rounded caps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capsule {
+    pub half_height: f32,
+    pub radius: f32,
+}
+
+impl Capsule {
+    pub fn new(half_height: f32, radius: f32) -> Self {
+        Self {
+            half_height,
+            radius,
+        }
+    }
+}
+
+/// Convex polygon with counter-clockwise local vertices
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvexPolygon {
+    vertices: Vec<Point2>,
+}
+
+impl ConvexPolygon {
+    /// Builds the convex hull of `points`. Returns `None` if the points don't enclose any area.
+    pub fn new(points: &[Point2]) -> Option<Self> {
+        let vertices = convex_hull(points);
+
+        if vertices.len() < 3 {
+            return None;
+        }
+
+        Some(Self { vertices })
+    }
+
+    pub fn vertices(&self) -> &[Point2] {
+        &self.vertices
+    }
+}
+
+/// Line segment between two local points, with no thickness
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment {
+    pub a: Point2,
+    pub b: Point2,
+}
+
+impl Segment {
+    pub fn new(a: Point2, b: Point2) -> Self {
+        Self { a, b }
+    }
+}
+
+/// All shapes supported by the collision module, in collider local space
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Circle(Circle),
+    AxisAlignedBox(AxisAlignedBox),
+    OrientedBox(OrientedBox),
+    Capsule(Capsule),
+    ConvexPolygon(ConvexPolygon),
+    Segment(Segment),
+}
+
+impl From<Circle> for Shape {
+    fn from(shape: Circle) -> Self {
+        Shape::Circle(shape)
+    }
+}
+
+impl From<AxisAlignedBox> for Shape {
+    fn from(shape: AxisAlignedBox) -> Self {
+        Shape::AxisAlignedBox(shape)
+    }
+}
+
+impl From<OrientedBox> for Shape {
+    fn from(shape: OrientedBox) -> Self {
+        Shape::OrientedBox(shape)
+    }
+}
+
+impl From<Capsule> for Shape {
+    fn from(shape: Capsule) -> Self {
+        Shape::Capsule(shape)
+    }
+}
+
+impl From<ConvexPolygon> for Shape {
+    fn from(shape: ConvexPolygon) -> Self {
+        Shape::ConvexPolygon(shape)
+    }
+}
+
+impl From<Segment> for Shape {
+    fn from(shape: Segment) -> Self {
+        Shape::Segment(shape)
+    }
+}
+
+impl Shape {
+    /// Resolves the shape into world space. `offset` is applied in local space, before
+    /// the transform's scale, rotation and translation.
+    pub fn to_world(&self, transform: &Transform2D, offset: Vec2) -> WorldShape {
+        let local = |p: Point2| transform.transform_point(p + offset);
+        let radius_scale = transform.scale.x.abs().max(transform.scale.y.abs());
+
+        match self {
+            Shape::Circle(circle) => WorldShape::Circle {
+                center: local(point2(0.0, 0.0)),
+                radius: circle.radius * radius_scale,
+            },
+            Shape::AxisAlignedBox(aab) => {
+                let center = local(point2(0.0, 0.0));
+                let h = vec2(
+                    aab.half_extents.x * transform.scale.x.abs(),
+                    aab.half_extents.y * transform.scale.y.abs(),
+                );
+                WorldShape::Polygon(WorldPolygon::new(vec![
+                    center + vec2(-h.x, -h.y),
+                    center + vec2(h.x, -h.y),
+                    center + vec2(h.x, h.y),
+                    center + vec2(-h.x, h.y),
+                ]))
+            }
+            Shape::OrientedBox(obb) => {
+                let h = obb.half_extents;
+                let corner = |x: f32, y: f32| {
+                    let v = rotate_vec2(vec2(x, y), rad(obb.rotation));
+                    local(point2(v.x, v.y))
+                };
+                WorldShape::Polygon(WorldPolygon::new(vec![
+                    corner(-h.x, -h.y),
+                    corner(h.x, -h.y),
+                    corner(h.x, h.y),
+                    corner(-h.x, h.y),
+                ]))
+            }
+            Shape::Capsule(capsule) => WorldShape::Capsule {
+                a: local(point2(0.0, -capsule.half_height)),
+                b: local(point2(0.0, capsule.half_height)),
+                radius: capsule.radius * radius_scale,
+            },
+            Shape::ConvexPolygon(polygon) => WorldShape::Polygon(WorldPolygon::new(
+                polygon.vertices.iter().map(|&v| local(v)).collect(),
+            )),
+            Shape::Segment(segment) => WorldShape::Capsule {
+                a: local(segment.a),
+                b: local(segment.b),
+                radius: 0.0,
+            },
+        }
+    }
+}
+
+impl Shape {
+    /// Area in local space (the transform scale is not applied), 0 for segments
+    pub fn area(&self) -> f32 {
+        match self {
+            Shape::Circle(circle) => PI * circle.radius * circle.radius,
+            Shape::AxisAlignedBox(AxisAlignedBox { half_extents })
+            | Shape::OrientedBox(OrientedBox { half_extents, .. }) => {
+                4.0 * half_extents.x * half_extents.y
+            }
+            Shape::Capsule(capsule) => {
+                PI * capsule.radius * capsule.radius + 4.0 * capsule.radius * capsule.half_height
+            }
+            Shape::ConvexPolygon(polygon) => signed_area(&polygon.vertices).abs(),
+            Shape::Segment(_) => 0.0,
+        }
+    }
+
+    /// Moment of inertia about the collider origin for a uniformly dense shape of the given mass,
+    /// in local space (the transform scale is not applied)
+    pub fn inertia(&self, mass: f32) -> f32 {
+        match self {
+            Shape::Circle(circle) => 0.5 * mass * circle.radius * circle.radius,
+            Shape::AxisAlignedBox(AxisAlignedBox { half_extents })
+            | Shape::OrientedBox(OrientedBox { half_extents, .. }) => {
+                mass * half_extents.magnitude2() / 3.0
+            }
+            Shape::Capsule(capsule) => {
+                // Approximated by the box enclosing the capsule
+                let h = vec2(capsule.radius, capsule.half_height + capsule.radius);
+                mass * h.magnitude2() / 3.0
+            }
+            Shape::ConvexPolygon(polygon) => polygon_inertia(&polygon.vertices, mass),
+            Shape::Segment(segment) => {
+                let (a, b) = (
+                    vec2(segment.a.x, segment.a.y),
+                    vec2(segment.b.x, segment.b.y),
+                );
+                mass * (a.magnitude2() + a.dot(b) + b.magnitude2()) / 3.0
+            }
+        }
+    }
+}
+
+/// Shape resolved into world space. Boxes and polygons become `Polygon`,
+/// segments become zero radius capsules.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorldShape {
+    Circle { center: Point2, radius: f32 },
+    Capsule { a: Point2, b: Point2, radius: f32 },
+    Polygon(WorldPolygon),
+}
+
+impl WorldShape {
+    /// Area of the shape, 0 for segments
+    pub fn area(&self) -> f32 {
+        match self {
+            WorldShape::Circle { radius, .. } => PI * radius * radius,
+            WorldShape::Capsule { a, b, radius } => {
+                PI * radius * radius + 2.0 * radius * (b - a).magnitude()
+            }
+            WorldShape::Polygon(polygon) => signed_area(&polygon.vertices).abs(),
+        }
+    }
+
+    /// Moment of inertia about the world origin for a uniformly dense shape of the given mass
+    pub fn inertia(&self, mass: f32) -> f32 {
+        match self {
+            WorldShape::Circle { center, radius } => {
+                mass * (0.5 * radius * radius + vec2(center.x, center.y).magnitude2())
+            }
+            WorldShape::Capsule { a, b, radius } => {
+                // Approximated by the box enclosing the capsule
+                let h = vec2(*radius, (b - a).magnitude() * 0.5 + radius);
+                let center = (vec2(a.x, a.y) + vec2(b.x, b.y)) * 0.5;
+                mass * (h.magnitude2() / 3.0 + center.magnitude2())
+            }
+            WorldShape::Polygon(polygon) => polygon_inertia(&polygon.vertices, mass),
+        }
+    }
+
+    /// Same shape moved by `offset`
+    pub fn translated(&self, offset: Vec2) -> WorldShape {
+        match self {
+            WorldShape::Circle { center, radius } => WorldShape::Circle {
+                center: center + offset,
+                radius: *radius,
+            },
+            WorldShape::Capsule { a, b, radius } => WorldShape::Capsule {
+                a: a + offset,
+                b: b + offset,
+                radius: *radius,
+            },
+            WorldShape::Polygon(polygon) => WorldShape::Polygon(WorldPolygon {
+                vertices: polygon.vertices.iter().map(|v| v + offset).collect(),
+                normals: polygon.normals.clone(),
+            }),
+        }
+    }
+
+    /// Returns true if the point lies inside or on the boundary of the shape
+    pub fn contains_point(&self, p: Point2) -> bool {
+        match self {
+            WorldShape::Circle { center, radius } => (p - center).magnitude2() <= radius * radius,
+            WorldShape::Capsule { a, b, radius } => {
+                let ab = b - a;
+                let len2 = ab.magnitude2();
+                let t = if len2 > EPSILON {
+                    ((p - a).dot(ab) / len2).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                (p - (a + ab * t)).magnitude2() <= radius * radius
+            }
+            WorldShape::Polygon(polygon) => polygon
+                .vertices
+                .iter()
+                .zip(&polygon.normals)
+                .all(|(v, n)| n.dot(p - v) <= 0.0),
+        }
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        match self {
+            WorldShape::Circle { center, radius } => {
+                Aabb::from_center(*center, vec2(*radius, *radius))
+            }
+            WorldShape::Capsule { a, b, radius } => Aabb::from_points([*a, *b]).expanded(*radius),
+            WorldShape::Polygon(polygon) => Aabb::from_points(polygon.vertices.iter().copied()),
+        }
+    }
+}
+
+/// Counter-clockwise convex polygon in world space with outward edge normals.
+/// `normals[i]` belongs to the edge from `vertices[i]` to `vertices[i + 1]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldPolygon {
+    pub vertices: Vec<Point2>,
+    pub normals: Vec<Vec2>,
+}
+
+impl WorldPolygon {
+    /// Builds a polygon from convex vertices in either winding order
+    pub fn new(mut vertices: Vec<Point2>) -> Self {
+        if signed_area(&vertices) < 0.0 {
+            vertices.reverse();
+        }
+
+        let normals = (0..vertices.len())
+            .map(|i| {
+                let edge = vertices[(i + 1) % vertices.len()] - vertices[i];
+                let normal = perp_vec2(edge);
+                if normal.magnitude2() > EPSILON * EPSILON {
+                    normal.normalize()
+                } else {
+                    vec2(0.0, 0.0)
+                }
+            })
+            .collect();
+
+        Self { vertices, normals }
+    }
+
+    pub fn centroid(&self) -> Point2 {
+        let sum = self
+            .vertices
+            .iter()
+            .fold(vec2(0.0, 0.0), |acc, v| acc + vec2(v.x, v.y));
+        let c = sum / self.vertices.len() as f32;
+        point2(c.x, c.y)
+    }
+}
+
+fn signed_area(vertices: &[Point2]) -> f32 {
+    let n = vertices.len();
+    (0..n)
+        .map(|i| {
+            let a = vertices[i];
+            let b = vertices[(i + 1) % n];
+            a.x * b.y - b.x * a.y
+        })
+        .sum::<f32>()
+        * 0.5
+}
+
+/// Moment of inertia about the origin of a uniformly dense polygon, summed over the
+/// triangles fanning out of the origin
+fn polygon_inertia(vertices: &[Point2], mass: f32) -> f32 {
+    let n = vertices.len();
+    let (area, moment) = (0..n).fold((0.0, 0.0), |(area, moment), i| {
+        let a = vec2(vertices[i].x, vertices[i].y);
+        let b = vec2(vertices[(i + 1) % n].x, vertices[(i + 1) % n].y);
+        let cross = a.x * b.y - b.x * a.y;
+        (
+            area + cross * 0.5,
+            moment + cross * (a.magnitude2() + a.dot(b) + b.magnitude2()) / 12.0,
+        )
+    });
+
+    if area.abs() > EPSILON {
+        mass * moment / area
+    } else {
+        0.0
+    }
+}
+
+/// Counter-clockwise convex hull (Andrew's monotone chain), without collinear points
+fn convex_hull(points: &[Point2]) -> Vec<Point2> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    sorted.dedup();
+
+    if sorted.len() < 3 {
+        return sorted;
+    }
+
+    let cross =
+        |o: Point2, a: Point2, b: Point2| (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x);
+    let chain = |points: &mut dyn Iterator<Item = &Point2>| {
+        let mut chain: Vec<Point2> = Vec::new();
+        for &p in points {
+            while chain.len() >= 2
+                && cross(chain[chain.len() - 2], chain[chain.len() - 1], p) <= EPSILON
+            {
+                chain.pop();
+            }
+            chain.push(p);
+        }
+        // The last point of each chain is the first point of the other one
+        chain.pop();
+        chain
+    };
+
+    let mut hull = chain(&mut sorted.iter());
+    hull.extend(chain(&mut sorted.iter().rev()));
+    hull
+}
@@ -0,0 +1,686 @@
+use std::f32::consts::FRAC_PI_2;
+use std::sync::{Arc, Mutex};
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use specs::{Builder, Entity, RunNow, System, SystemData, World, WorldExt};
+
+use crate::arith::ray::Ray2D;
+use crate::arith::{point2, vec2};
+use crate::components::Transform2D;
+use crate::event::EventSystem;
+
+use super::broad_phase::{BroadPhase, CollisionWorld, DynamicAabbTree, SpatialHash};
+use super::contact::Contacts;
+use super::distance::distance;
+use super::narrow_phase::collide;
+use super::overlap::{OverlapBegin, OverlapEnd, OverlapStay, Overlaps};
+use super::query::{PhysicsQuery, QueryFilter};
+use super::shapes::{
+    Aabb, AxisAlignedBox, Capsule, Circle, ConvexPolygon, OrientedBox, Segment, WorldShape,
+};
+use super::{ALL_LAYERS, Collider, CollisionSystem};
+
+fn approx_eq(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-4
+}
+
+fn aabb_approx_eq(a: Aabb, b: Aabb) -> bool {
+    approx_eq(a.min.x, b.min.x)
+        && approx_eq(a.min.y, b.min.y)
+        && approx_eq(a.max.x, b.max.x)
+        && approx_eq(a.max.y, b.max.y)
+}
+
+fn transform(x: f32, y: f32, rotation: f32, sx: f32, sy: f32) -> Transform2D {
+    Transform2D {
+        position: point2(x, y),
+        rotation,
+        scale: vec2(sx, sy),
+    }
+}
+
+#[test]
+fn test_circle_aabb_with_offset_and_scale() {
+    let collider = Collider::new(Circle::new(1.0)).with_offset(vec2(1.0, 0.0));
+    let t = transform(2.0, 3.0, FRAC_PI_2, 2.0, 1.0);
+
+    // Offset is scaled to (2, 0) then rotated to (0, 2); radius uses the largest scale axis
+    let expected = Aabb::new(point2(0.0, 3.0), point2(4.0, 7.0));
+    let aabb = collider.world_aabb(&t);
+    assert!(aabb_approx_eq(aabb, expected), "Got {aabb:?}");
+}
+
+#[test]
+fn test_axis_aligned_box_ignores_rotation() {
+    let collider = Collider::new(AxisAlignedBox::new(vec2(1.0, 2.0)));
+    let t = transform(0.0, 0.0, 0.7, 2.0, 1.0);
+
+    let expected = Aabb::new(point2(-2.0, -2.0), point2(2.0, 2.0));
+    assert!(aabb_approx_eq(collider.world_aabb(&t), expected));
+}
+
+#[test]
+fn test_oriented_box_follows_rotation() {
+    let collider = Collider::new(OrientedBox::new(vec2(2.0, 1.0), 0.0));
+    let t = transform(0.0, 0.0, FRAC_PI_2, 1.0, 1.0);
+
+    let expected = Aabb::new(point2(-1.0, -2.0), point2(1.0, 2.0));
+    assert!(aabb_approx_eq(collider.world_aabb(&t), expected));
+}
+
+#[test]
+fn test_capsule_and_segment_aabb() {
+    let capsule = Collider::new(Capsule::new(1.0, 0.5));
+    let t = transform(0.0, 0.0, FRAC_PI_2, 1.0, 1.0);
+    let expected = Aabb::new(point2(-1.5, -0.5), point2(1.5, 0.5));
+    assert!(aabb_approx_eq(capsule.world_aabb(&t), expected));
+
+    let segment = Collider::new(Segment::new(point2(-1.0, 0.0), point2(1.0, 1.0)));
+    let t = transform(1.0, 1.0, 0.0, 1.0, 1.0);
+    let expected = Aabb::new(point2(0.0, 1.0), point2(2.0, 2.0));
+    assert!(aabb_approx_eq(segment.world_aabb(&t), expected));
+}
+
+#[test]
+fn test_convex_polygon_hull_and_winding() {
+    // Interior and duplicated points are dropped, clockwise input is accepted
+    let polygon = ConvexPolygon::new(&[
+        point2(0.0, 0.0),
+        point2(0.0, 1.0),
+        point2(0.25, 0.25),
+        point2(1.0, 1.0),
+        point2(1.0, 0.0),
+        point2(1.0, 0.0),
+    ])
+    .unwrap();
+    assert_eq!(polygon.vertices().len(), 4);
+    assert!(ConvexPolygon::new(&[point2(0.0, 0.0), point2(1.0, 1.0), point2(2.0, 2.0)]).is_none());
+
+    // Mirrored scale must still produce counter-clockwise vertices with outward normals
+    let collider = Collider::new(polygon);
+    let t = transform(0.0, 0.0, 0.0, -1.0, 1.0);
+    let WorldShape::Polygon(world) = collider.world_shape(&t) else {
+        panic!("Expected a polygon");
+    };
+    let centroid = world.centroid();
+    for (vertex, normal) in world.vertices.iter().zip(&world.normals) {
+        let to_center = centroid - vertex;
+        assert!(to_center.x * normal.x + to_center.y * normal.y < 0.0);
+    }
+}
+
+fn world_shape(collider: &Collider, x: f32, y: f32, rotation: f32) -> WorldShape {
+    collider.world_shape(&transform(x, y, rotation, 1.0, 1.0))
+}
+
+#[test]
+fn test_circle_circle_manifold() {
+    let circle = Collider::new(Circle::new(1.0));
+    let a = world_shape(&circle, 0.0, 0.0, 0.0);
+    let b = world_shape(&circle, 1.5, 0.0, 0.0);
+
+    let manifold = collide(&a, &b).unwrap();
+    assert!(approx_eq(manifold.normal.x, 1.0));
+    assert_eq!(manifold.points().len(), 1);
+    assert!(approx_eq(manifold.penetration(), 0.5));
+    assert!(approx_eq(manifold.points()[0].position.x, 0.75));
+
+    let far = world_shape(&circle, 2.5, 0.0, 0.0);
+    assert!(collide(&a, &far).is_none());
+}
+
+#[test]
+fn test_box_resting_on_box_has_two_points() {
+    let ground = Collider::new(AxisAlignedBox::new(vec2(5.0, 0.5)));
+    let crate_box = Collider::new(OrientedBox::new(vec2(0.5, 0.5), 0.0));
+    let a = world_shape(&ground, 0.0, 0.0, 0.0);
+    let b = world_shape(&crate_box, 1.0, 0.9, 0.0);
+
+    let manifold = collide(&a, &b).unwrap();
+    assert!(approx_eq(manifold.normal.y, 1.0));
+    assert_eq!(manifold.points().len(), 2);
+    for point in manifold.points() {
+        assert!(approx_eq(point.penetration, 0.1));
+    }
+
+    // Swapping the shapes flips the normal
+    let flipped = collide(&b, &a).unwrap();
+    assert!(approx_eq(flipped.normal.y, -1.0));
+}
+
+#[test]
+fn test_rotated_box_touches_with_a_corner() {
+    let crate_box = Collider::new(OrientedBox::new(vec2(0.5, 0.5), 0.0));
+    let ground = world_shape(
+        &Collider::new(AxisAlignedBox::new(vec2(5.0, 0.5))),
+        0.0,
+        0.0,
+        0.0,
+    );
+    let corner_down = world_shape(&crate_box, 0.0, 1.2, FRAC_PI_2 / 2.0);
+
+    let manifold = collide(&ground, &corner_down).unwrap();
+    assert_eq!(manifold.points().len(), 1);
+    assert!(approx_eq(manifold.penetration(), 0.5_f32.sqrt() - 0.7));
+}
+
+#[test]
+fn test_polygon_circle_vertex_region() {
+    let square = world_shape(
+        &Collider::new(AxisAlignedBox::new(vec2(1.0, 1.0))),
+        0.0,
+        0.0,
+        0.0,
+    );
+    let circle = Collider::new(Circle::new(0.5));
+
+    let touching = world_shape(&circle, 1.3, 1.3, 0.0);
+    let manifold = collide(&square, &touching).unwrap();
+    let diagonal = 0.5_f32.sqrt();
+    assert!(approx_eq(manifold.normal.x, diagonal) && approx_eq(manifold.normal.y, diagonal));
+
+    // Inside the face band the AABBs overlap, but the rounded corner doesn't reach
+    let apart = world_shape(&circle, 1.4, 1.4, 0.0);
+    assert!(collide(&square, &apart).is_none());
+    assert!(collide(&apart, &square).is_none());
+}
+
+#[test]
+fn test_capsules_and_segments() {
+    let capsule = Collider::new(Capsule::new(1.0, 0.5));
+    let lying = world_shape(&capsule, 0.0, 0.9, FRAC_PI_2);
+    let ground = world_shape(
+        &Collider::new(AxisAlignedBox::new(vec2(5.0, 0.5))),
+        0.0,
+        0.0,
+        0.0,
+    );
+
+    let manifold = collide(&ground, &lying).unwrap();
+    assert!(approx_eq(manifold.normal.y, 1.0));
+    assert_eq!(manifold.points().len(), 2);
+    assert!(approx_eq(manifold.penetration(), 0.1));
+
+    // Capsule lying on a segment, and a standing capsule touching a lying one
+    let segment = world_shape(
+        &Collider::new(Segment::new(point2(-5.0, 0.0), point2(5.0, 0.0))),
+        0.0,
+        0.5,
+        0.0,
+    );
+    let manifold = collide(&segment, &lying).unwrap();
+    assert_eq!(manifold.points().len(), 2);
+    assert!(approx_eq(manifold.penetration(), 0.1));
+
+    let standing = world_shape(&capsule, 0.0, 2.8, 0.0);
+    let manifold = collide(&lying, &standing).unwrap();
+    assert_eq!(manifold.points().len(), 1);
+    assert!(approx_eq(manifold.penetration(), 0.1));
+}
+
+#[test]
+fn test_collision_system_fills_contacts() {
+    let mut world = World::new();
+    System::setup(&mut CollisionSystem, &mut world);
+
+    let a = world
+        .create_entity()
+        .with(Transform2D::default())
+        .with(Collider::new(Circle::new(1.0)))
+        .build();
+    let b = world
+        .create_entity()
+        .with(transform(1.0, 0.0, 0.0, 1.0, 1.0))
+        .with(Collider::new(AxisAlignedBox::new(vec2(0.5, 0.5))))
+        .build();
+    world
+        .create_entity()
+        .with(transform(10.0, 0.0, 0.0, 1.0, 1.0))
+        .with(Collider::default())
+        .build();
+
+    CollisionSystem.run_now(&world);
+
+    let contacts = world.read_resource::<Contacts>();
+    assert_eq!(contacts.len(), 1);
+    let manifold = contacts.between(a, b).unwrap();
+    assert!(approx_eq(manifold.normal.x, 1.0));
+    assert!(approx_eq(contacts.between(b, a).unwrap().normal.x, -1.0));
+}
+
+fn random_aabbs(world: &mut World, count: usize) -> Vec<(Entity, Aabb)> {
+    let mut rng = SmallRng::seed_from_u64(7);
+    (0..count)
+        .map(|_| {
+            let center = point2(rng.random_range(-50.0..50.0), rng.random_range(-50.0..50.0));
+            let half = vec2(rng.random_range(0.1..3.0), rng.random_range(0.1..3.0));
+            (
+                world.create_entity().build(),
+                Aabb::from_center(center, half),
+            )
+        })
+        .collect()
+}
+
+fn brute_force_pairs(aabbs: &[(Entity, Aabb)], margin: f32) -> Vec<(Entity, Entity)> {
+    let mut pairs = Vec::new();
+    for (i, (a, aabb_a)) in aabbs.iter().enumerate() {
+        for (b, aabb_b) in &aabbs[i + 1..] {
+            if aabb_a.expanded(margin).overlaps(&aabb_b.expanded(margin)) {
+                pairs.push(if a < b { (*a, *b) } else { (*b, *a) });
+            }
+        }
+    }
+    pairs.sort();
+    pairs
+}
+
+fn sorted_pairs(broad_phase: &dyn BroadPhase) -> Vec<(Entity, Entity)> {
+    let mut pairs = Vec::new();
+    broad_phase.collect_pairs(&mut pairs);
+    pairs.sort();
+    pairs
+}
+
+#[test]
+fn test_broad_phases_match_brute_force() {
+    let mut world = World::new();
+    let mut aabbs = random_aabbs(&mut world, 300);
+
+    let mut tree = DynamicAabbTree::new(0.0);
+    let mut hash = SpatialHash::new(4.0);
+    for &(entity, aabb) in &aabbs {
+        tree.insert(entity, aabb);
+        hash.insert(entity, aabb);
+    }
+
+    let expected = brute_force_pairs(&aabbs, 0.0);
+    assert!(!expected.is_empty());
+    assert_eq!(sorted_pairs(&tree), expected);
+    assert_eq!(sorted_pairs(&hash), expected);
+    // The balanced tree stays far shallower than the number of leaves
+    assert!(tree.height() < 20, "Tree height {}", tree.height());
+
+    // Move half of the boxes and delete a few
+    for (entity, aabb) in aabbs.iter_mut().step_by(2) {
+        *aabb = Aabb::from_center(aabb.center() + vec2(7.0, -3.0), aabb.half_extents());
+        tree.update(*entity, *aabb);
+        hash.update(*entity, *aabb);
+    }
+    for (entity, _) in aabbs.drain(..30) {
+        tree.remove(entity);
+        hash.remove(entity);
+    }
+
+    let expected = brute_force_pairs(&aabbs, 0.0);
+    assert_eq!(tree.len(), aabbs.len());
+    assert_eq!(sorted_pairs(&tree), expected);
+    assert_eq!(sorted_pairs(&hash), expected);
+
+    let area = Aabb::new(point2(-10.0, -10.0), point2(10.0, 10.0));
+    let mut found = Vec::new();
+    tree.query(&area, &mut |entity| found.push(entity));
+    found.sort();
+    let mut expected: Vec<_> = aabbs
+        .iter()
+        .filter(|(_, aabb)| aabb.overlaps(&area))
+        .map(|(entity, _)| *entity)
+        .collect();
+    expected.sort();
+    assert_eq!(found, expected);
+}
+
+#[test]
+fn test_small_moves_stay_inside_fat_bounds() {
+    let mut world = World::new();
+    let entity = world.create_entity().build();
+    let mut tree = DynamicAabbTree::new(0.5);
+    let aabb = Aabb::from_center(point2(0.0, 0.0), vec2(1.0, 1.0));
+    tree.insert(entity, aabb);
+
+    let nudged = Aabb::from_center(point2(0.2, 0.0), vec2(1.0, 1.0));
+    assert!(!tree.update(entity, nudged));
+    let moved = Aabb::from_center(point2(2.0, 0.0), vec2(1.0, 1.0));
+    assert!(tree.update(entity, moved));
+    assert!(tree.fat_aabb(entity).unwrap().contains(&moved));
+
+    let mut hash = SpatialHash::new(4.0);
+    hash.insert(entity, aabb);
+    assert!(!hash.update(entity, nudged));
+    assert!(hash.update(entity, Aabb::from_center(point2(6.0, 0.0), vec2(1.0, 1.0))));
+}
+
+#[test]
+fn test_collision_world_tracks_entities_across_frames() {
+    let mut world = World::new();
+    world.insert(CollisionWorld::new(SpatialHash::new(2.0)));
+    System::setup(&mut CollisionSystem, &mut world);
+
+    let a = world
+        .create_entity()
+        .with(Transform2D::default())
+        .with(Collider::default())
+        .build();
+    let b = world
+        .create_entity()
+        .with(transform(0.8, 0.0, 0.0, 1.0, 1.0))
+        .with(Collider::default())
+        .build();
+
+    CollisionSystem.run_now(&world);
+    assert_eq!(
+        world.read_resource::<CollisionWorld>().candidate_pairs(),
+        &[(a, b)]
+    );
+    assert_eq!(world.read_resource::<Contacts>().len(), 1);
+
+    // Obstacles are found by their tight bounds
+    let right = Aabb::from_center(point2(1.2, 0.0), vec2(0.1, 0.1));
+    let both = Aabb::from_center(point2(0.4, 0.0), vec2(0.1, 0.1));
+    {
+        let collision_world = world.read_resource::<CollisionWorld>();
+        let found = |area| -> Vec<_> {
+            collision_world
+                .obstacles(area)
+                .iter()
+                .map(|o| o.entity)
+                .collect()
+        };
+        assert_eq!(found(&right), vec![b]);
+        assert_eq!(found(&both), vec![a, b]);
+        assert!(approx_eq(
+            collision_world.obstacle(b).unwrap().aabb.max.x,
+            1.3
+        ));
+    }
+
+    world.delete_entity(b).unwrap();
+    world.maintain();
+    CollisionSystem.run_now(&world);
+
+    let collision_world = world.read_resource::<CollisionWorld>();
+    assert!(collision_world.candidate_pairs().is_empty());
+    assert_eq!(collision_world.broad_phase().len(), 1);
+    assert!(collision_world.obstacle(b).is_none());
+    assert!(collision_world.obstacles(&right).is_empty());
+    assert!(world.read_resource::<Contacts>().is_empty());
+}
+
+#[test]
+fn test_layers_and_masks_filter_pairs() {
+    let player = Collider::default().with_layers(0b01, 0b10);
+    let enemy = Collider::default().with_layers(0b10, 0b01);
+    let ghost = Collider::default().with_layers(0b10, 0b00);
+
+    assert!(player.can_collide_with(&enemy));
+    assert!(!player.can_collide_with(&ghost));
+    assert!(!ghost.can_collide_with(&player));
+
+    let mut world = World::new();
+    System::setup(&mut CollisionSystem, &mut world);
+    for collider in [player, ghost] {
+        world
+            .create_entity()
+            .with(Transform2D::default())
+            .with(collider)
+            .build();
+    }
+
+    CollisionSystem.run_now(&world);
+    assert!(world.read_resource::<Contacts>().is_empty());
+    assert!(world.read_resource::<Overlaps>().is_empty());
+}
+
+#[test]
+fn test_trigger_overlap_events() {
+    let mut world = World::new();
+    System::setup(&mut CollisionSystem, &mut world);
+
+    let log = Arc::new(Mutex::new(Vec::new()));
+    {
+        let events = world.read_resource::<EventSystem>();
+        let begin_log = log.clone();
+        events
+            .subscribe(move |e: &OverlapBegin| {
+                assert!(e.is_trigger);
+                begin_log.lock().unwrap().push("begin");
+            })
+            .detach();
+        let stay_log = log.clone();
+        events
+            .subscribe(move |_: &OverlapStay| stay_log.lock().unwrap().push("stay"))
+            .detach();
+        let end_log = log.clone();
+        events
+            .subscribe(move |_: &OverlapEnd| end_log.lock().unwrap().push("end"))
+            .detach();
+    }
+
+    let zone = world
+        .create_entity()
+        .with(Transform2D::default())
+        .with(Collider::new(AxisAlignedBox::new(vec2(2.0, 2.0))).trigger())
+        .build();
+    let pickup = world
+        .create_entity()
+        .with(transform(1.0, 0.0, 0.0, 1.0, 1.0))
+        .with(Collider::default())
+        .build();
+
+    CollisionSystem.run_now(&world);
+    CollisionSystem.run_now(&world);
+    {
+        // Triggers never produce contacts but still show up as overlaps
+        assert!(world.read_resource::<Contacts>().is_empty());
+        let overlaps = world.read_resource::<Overlaps>();
+        assert!(overlaps.is_overlapping(zone, pickup));
+        assert_eq!(overlaps.overlapping(pickup).collect::<Vec<_>>(), vec![zone]);
+    }
+
+    world.delete_entity(pickup).unwrap();
+    world.maintain();
+    CollisionSystem.run_now(&world);
+    CollisionSystem.run_now(&world);
+
+    assert_eq!(*log.lock().unwrap(), vec!["begin", "stay", "end"]);
+}
+
+#[test]
+fn test_gjk_distance() {
+    let square = world_shape(
+        &Collider::new(AxisAlignedBox::new(vec2(1.0, 1.0))),
+        0.0,
+        0.0,
+        0.0,
+    );
+    let circle = world_shape(&Collider::new(Circle::new(0.5)), 3.0, 3.0, 0.0);
+    let gap = distance(&square, &circle);
+    assert!(approx_eq(gap.distance, 8.0_f32.sqrt() - 0.5));
+    assert!(approx_eq(gap.point_a.x, 1.0) && approx_eq(gap.point_a.y, 1.0));
+
+    let rotated = world_shape(
+        &Collider::new(OrientedBox::new(vec2(1.0, 1.0), 0.0)),
+        4.0,
+        0.0,
+        0.3,
+    );
+    let gap = distance(&square, &rotated);
+    let expected = collide(&square, &rotated.translated(vec2(-gap.distance, 0.0)));
+    assert!(gap.distance > 0.0 && gap.distance < 2.0);
+    assert!(approx_eq(gap.normal.x, 1.0));
+    assert!(expected.is_some());
+
+    let overlapping = world_shape(&Collider::new(Capsule::new(1.0, 0.5)), 0.5, 0.0, 0.0);
+    assert_eq!(distance(&square, &overlapping).distance, 0.0);
+}
+
+fn query_world() -> (World, Entity, Entity, Entity) {
+    let mut world = World::new();
+    PhysicsQuery::setup(&mut world);
+    System::setup(&mut CollisionSystem, &mut world);
+
+    let wall = world
+        .create_entity()
+        .with(transform(5.0, 0.0, 0.0, 1.0, 1.0))
+        .with(Collider::new(AxisAlignedBox::new(vec2(0.5, 5.0))).with_layers(0b01, ALL_LAYERS))
+        .build();
+    let ball = world
+        .create_entity()
+        .with(transform(2.0, 0.0, 0.0, 1.0, 1.0))
+        .with(Collider::new(Circle::new(0.5)).with_layers(0b10, ALL_LAYERS))
+        .build();
+    let zone = world
+        .create_entity()
+        .with(transform(8.0, 0.0, 0.0, 1.0, 1.0))
+        .with(Collider::new(Capsule::new(1.0, 0.5)).trigger())
+        .build();
+    CollisionSystem.run_now(&world);
+
+    (world, wall, ball, zone)
+}
+
+#[test]
+fn test_raycasts() {
+    let (world, wall, ball, zone) = query_world();
+    let query = world.system_data::<PhysicsQuery>();
+    let ray = Ray2D::new(point2(0.0, 0.0), vec2(1.0, 0.0));
+
+    let hit = query.raycast(&ray, 100.0, QueryFilter::default()).unwrap();
+    assert_eq!(hit.entity, ball);
+    assert!(approx_eq(hit.distance, 1.5));
+    assert!(approx_eq(hit.normal.x, -1.0));
+
+    let hits = query.raycast_all(&ray, 100.0, QueryFilter::default().with_triggers());
+    let entities: Vec<_> = hits.iter().map(|h| h.entity).collect();
+    assert_eq!(entities, vec![ball, wall, zone]);
+    assert!(approx_eq(hits[1].point.x, 4.5));
+    assert!(approx_eq(hits[2].distance, 7.5));
+
+    // Layer filter skips the ball, max distance stops before the wall
+    let hit = query.raycast(&ray, 100.0, QueryFilter::new(0b01)).unwrap();
+    assert_eq!(hit.entity, wall);
+    assert!(query.raycast(&ray, 4.0, QueryFilter::new(0b01)).is_none());
+
+    // Rays starting inside a collider ignore it
+    let inside = Ray2D::new(point2(2.0, 0.0), vec2(1.0, 0.0));
+    assert_eq!(
+        query
+            .raycast(&inside, 100.0, QueryFilter::default())
+            .unwrap()
+            .entity,
+        wall
+    );
+
+    // A capsule hit on its flat side
+    let down = Ray2D::new(point2(8.0, 5.0), vec2(0.0, -1.0));
+    let hit = query
+        .raycast(&down, 100.0, QueryFilter::default().with_triggers())
+        .unwrap();
+    assert_eq!(hit.entity, zone);
+    assert!(approx_eq(hit.distance, 3.5));
+    let side = Ray2D::new(point2(6.0, 0.5), vec2(1.0, 0.0));
+    let hit = query
+        .raycast(
+            &side,
+            100.0,
+            QueryFilter::default().with_triggers().excluding(wall),
+        )
+        .unwrap();
+    assert!(approx_eq(hit.distance, 1.5));
+    assert!(approx_eq(hit.normal.x, -1.0));
+}
+
+#[test]
+fn test_shape_cast_and_overlaps() {
+    let (world, wall, ball, zone) = query_world();
+    let query = world.system_data::<PhysicsQuery>();
+
+    let probe = Circle::new(0.25).into();
+    let start = transform(0.0, 3.0, 0.0, 1.0, 1.0);
+    let hit = query
+        .shape_cast(&probe, &start, vec2(1.0, 0.0), 10.0, QueryFilter::default())
+        .unwrap();
+    assert_eq!(hit.entity, wall);
+    assert!((hit.distance - 4.25).abs() < 0.01, "Got {}", hit.distance);
+    assert!(approx_eq(hit.normal.x, -1.0));
+
+    // Sliding along the wall never hits it, starting inside the ball hits at 0
+    let sliding = transform(4.25, 3.0, 0.0, 1.0, 1.0);
+    let filter = QueryFilter::new(0b01);
+    assert!(
+        query
+            .shape_cast(&probe, &sliding, vec2(0.0, 1.0), 10.0, filter)
+            .is_none()
+    );
+    let inside = transform(2.0, 0.0, 0.0, 1.0, 1.0);
+    let hit = query
+        .shape_cast(
+            &probe,
+            &inside,
+            vec2(0.0, 1.0),
+            10.0,
+            QueryFilter::default(),
+        )
+        .unwrap();
+    assert_eq!((hit.entity, hit.distance), (ball, 0.0));
+
+    // A zero direction has nowhere to sweep, even from inside a collider
+    assert!(
+        query
+            .shape_cast(
+                &probe,
+                &inside,
+                vec2(0.0, 0.0),
+                10.0,
+                QueryFilter::default()
+            )
+            .is_none()
+    );
+
+    let filter = QueryFilter::default().with_triggers();
+    assert_eq!(query.overlap_point(point2(2.2, 0.1), filter), vec![ball]);
+    assert_eq!(query.overlap_point(point2(8.0, 1.4), filter), vec![zone]);
+    assert!(
+        query
+            .overlap_point(point2(8.0, 1.4), QueryFilter::default())
+            .is_empty()
+    );
+
+    let area = Aabb::new(point2(1.0, -1.0), point2(4.6, 1.0));
+    let mut found = query.overlap_aabb(&area, filter);
+    found.sort();
+    assert_eq!(found, vec![wall, ball]);
+
+    let shape = AxisAlignedBox::new(vec2(1.0, 0.2)).into();
+    let at = transform(6.9, 1.4, 0.0, 1.0, 1.0);
+    assert_eq!(query.overlap_shape(&shape, &at, filter), vec![zone]);
+}
+
+#[test]
+fn test_queries_see_the_last_collision_step() {
+    let (mut world, wall, ball, _) = query_world();
+    let ray = Ray2D::new(point2(0.0, 0.0), vec2(1.0, 0.0));
+    let first_hit = |world: &World| {
+        world
+            .system_data::<PhysicsQuery>()
+            .raycast(&ray, 100.0, QueryFilter::default())
+            .map(|hit| hit.entity)
+    };
+
+    // Colliders added since the last step aren't in the broad phase yet
+    let post = world
+        .create_entity()
+        .with(transform(1.0, 0.0, 0.0, 1.0, 1.0))
+        .with(Collider::new(Circle::new(0.25)))
+        .build();
+    assert_eq!(first_hit(&world), Some(ball));
+    CollisionSystem.run_now(&world);
+    assert_eq!(first_hit(&world), Some(post));
+
+    // Deleted entities are skipped right away
+    world.delete_entity(post).unwrap();
+    world.delete_entity(ball).unwrap();
+    world.maintain();
+    assert_eq!(first_hit(&world), Some(wall));
+}
@@ -1,12 +1,13 @@
-use crate::arith::{Point2, Vec2, point2, vec2};
+use crate::arith::{Point2, Vec2, point2, rad, rotate_vec2, vec2};
 
-use specs::{Component, VecStorage};
+use specs::{Component, Entities, Join, ReadStorage, System, VecStorage, WriteStorage};
 use specs_derive::Component;
 
 #[derive(Debug, Component, Clone, Copy)]
 #[storage(VecStorage)]
 pub struct Transform2D {
     pub position: Point2,
+    /// Rotation in radians
     pub rotation: f32,
     pub scale: Vec2,
 }
@@ -16,7 +17,54 @@ impl Default for Transform2D {
         Self {
             position: point2(0.0, 0.0),
             rotation: 0.0,
-            scale: vec2(0.0, 0.0),
+            scale: vec2(1.0, 1.0),
+        }
+    }
+}
+
+impl Transform2D {
+    /// Transforms a local space point into world space (scale, then rotation, then translation)
+    pub fn transform_point(&self, local: Point2) -> Point2 {
+        self.position + self.transform_vector(vec2(local.x, local.y))
+    }
+
+    /// Transforms a local space vector into world space, ignoring translation
+    pub fn transform_vector(&self, local: Vec2) -> Vec2 {
+        let scaled = vec2(local.x * self.scale.x, local.y * self.scale.y);
+        rotate_vec2(scaled, rad(self.rotation))
+    }
+
+    /// Blends from `previous` at `alpha` 0 to this transform at 1
+    pub fn interpolate(&self, previous: &Transform2D, alpha: f32) -> Transform2D {
+        Transform2D {
+            position: previous.position + (self.position - previous.position) * alpha,
+            rotation: previous.rotation + (self.rotation - previous.rotation) * alpha,
+            scale: previous.scale + (self.scale - previous.scale) * alpha,
+        }
+    }
+}
+
+/// `Transform2D` of the entity before the last fixed update, kept by the
+/// `TransformHistorySystem` for rendering interpolation
+#[derive(Debug, Component, Clone, Copy, Default)]
+#[storage(VecStorage)]
+pub struct PreviousTransform2D(pub Transform2D);
+
+/// Records every `Transform2D` into its `PreviousTransform2D`, run first in the fixed update
+pub struct TransformHistorySystem;
+
+impl<'a> System<'a> for TransformHistorySystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Transform2D>,
+        WriteStorage<'a, PreviousTransform2D>,
+    );
+
+    fn run(&mut self, (entities, transforms, mut previous): Self::SystemData) {
+        for (entity, transform) in (&entities, &transforms).join() {
+            previous
+                .insert(entity, PreviousTransform2D(*transform))
+                .expect("joined entities are alive");
         }
     }
 }
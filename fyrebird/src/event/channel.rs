@@ -0,0 +1,263 @@
+use std::any::TypeId;
+use std::marker::PhantomData;
+
+use specs::shred::ResourceId;
+use specs::{Read, SystemData, World, WorldExt, Write};
+
+use super::Event;
+
+/// Double buffered queue of events of one type, read by systems through `EventReader`s and
+/// written through `EventWriter`s. Each frame `update` drops the events of the frame before
+/// last, so an event can be read until the end of the frame after the one it was sent in and
+/// every reader running once a frame sees it exactly once.
+#[derive(Debug)]
+pub struct Events<E> {
+    /// Events sent last frame
+    previous: Vec<E>,
+    /// Id of the first event of `previous`
+    previous_start: usize,
+    /// Events sent this frame
+    current: Vec<E>,
+    /// Id of the first event of `current`
+    current_start: usize,
+}
+
+impl<E> Default for Events<E> {
+    fn default() -> Self {
+        Self {
+            previous: Vec::new(),
+            previous_start: 0,
+            current: Vec::new(),
+            current_start: 0,
+        }
+    }
+}
+
+impl<E: Event> Events<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn send(&mut self, event: E) {
+        self.current.push(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = E>) {
+        self.current.extend(events);
+    }
+
+    /// Drops the events of last frame, the events of this frame become last frame's
+    pub fn update(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+        self.previous_start = self.current_start;
+        self.current_start = self.previous_start + self.previous.len();
+    }
+
+    /// Number of events held, from this frame and the last
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops every event held, readers skip to the next one sent
+    pub fn clear(&mut self) {
+        self.current_start = self.event_count();
+        self.previous_start = self.current_start;
+        self.previous.clear();
+        self.current.clear();
+    }
+
+    /// Number of events ever sent
+    pub fn event_count(&self) -> usize {
+        self.current_start + self.current.len()
+    }
+
+    /// Cursor skipping the events sent so far
+    pub fn latest_cursor(&self) -> EventCursor<E> {
+        EventCursor {
+            next: self.event_count(),
+            missed: 0,
+            event: PhantomData,
+        }
+    }
+}
+
+/// Position of a reader in an `Events` queue, kept by the system reading them.
+/// A new cursor starts at the oldest event still held.
+#[derive(Debug)]
+pub struct EventCursor<E> {
+    /// Id of the next event to read
+    next: usize,
+    missed: usize,
+    event: PhantomData<fn() -> E>,
+}
+
+impl<E> Default for EventCursor<E> {
+    fn default() -> Self {
+        Self {
+            next: 0,
+            missed: 0,
+            event: PhantomData,
+        }
+    }
+}
+
+impl<E> Clone for EventCursor<E> {
+    fn clone(&self) -> Self {
+        Self {
+            next: self.next,
+            missed: self.missed,
+            event: PhantomData,
+        }
+    }
+}
+
+impl<E: Event> EventCursor<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Events sent since the last read, oldest first
+    pub fn read<'e>(&mut self, events: &'e Events<E>) -> impl Iterator<Item = &'e E> + 'e {
+        if self.next < events.previous_start {
+            self.missed += events.previous_start - self.next;
+        }
+        let next = self.next.max(events.previous_start);
+        self.next = events.event_count();
+
+        let previous = (next - events.previous_start).min(events.previous.len());
+        let current = next
+            .saturating_sub(events.current_start)
+            .min(events.current.len());
+        events.previous[previous..]
+            .iter()
+            .chain(&events.current[current..])
+    }
+
+    /// Number of events left to read
+    pub fn len(&self, events: &Events<E>) -> usize {
+        events
+            .event_count()
+            .saturating_sub(self.next.max(events.previous_start))
+    }
+
+    pub fn is_empty(&self, events: &Events<E>) -> bool {
+        self.len(events) == 0
+    }
+
+    /// Number of events dropped before this cursor read them, the reader didn't run for
+    /// two frames
+    pub fn missed(&self) -> usize {
+        self.missed
+    }
+}
+
+/// Updates the `Events` of one event type
+type EventsUpdate = fn(&World);
+
+/// Events updated by the `GameLoop` at the start of every frame.
+/// `EventReader` and `EventWriter` register their event type when their system is set up.
+#[derive(Debug, Default)]
+pub struct EventRegistry {
+    updates: Vec<(TypeId, EventsUpdate)>,
+}
+
+impl EventRegistry {
+    /// Inserts the `Events` of `E` in the world and has them updated every frame
+    pub fn register<E: Event>(world: &mut World) {
+        world.entry::<Events<E>>().or_insert_with(Events::default);
+        let mut registry = world
+            .entry::<EventRegistry>()
+            .or_insert_with(EventRegistry::default);
+        let type_id = TypeId::of::<E>();
+        if registry.updates.iter().all(|(id, _)| *id != type_id) {
+            registry.updates.push((type_id, |world| {
+                world.write_resource::<Events<E>>().update();
+            }));
+        }
+    }
+
+    /// Updates every registered event queue of the world
+    pub fn update(world: &World) {
+        let Some(registry) = world.try_fetch::<EventRegistry>() else {
+            return;
+        };
+        for (_, update) in &registry.updates {
+            update(world);
+        }
+    }
+}
+
+/// System data reading the events of `E`, with a cursor kept by the system
+pub struct EventReader<'a, E: Event> {
+    events: Read<'a, Events<E>>,
+}
+
+impl<'a, E: Event> SystemData<'a> for EventReader<'a, E> {
+    fn setup(world: &mut World) {
+        EventRegistry::register::<E>(world);
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        Self {
+            events: Read::fetch(world),
+        }
+    }
+
+    fn reads() -> Vec<ResourceId> {
+        Read::<Events<E>>::reads()
+    }
+
+    fn writes() -> Vec<ResourceId> {
+        Read::<Events<E>>::writes()
+    }
+}
+
+impl<E: Event> EventReader<'_, E> {
+    /// Events sent since `cursor` last read, oldest first
+    pub fn read<'s>(&'s self, cursor: &mut EventCursor<E>) -> impl Iterator<Item = &'s E> + 's {
+        cursor.read(&self.events)
+    }
+
+    pub fn events(&self) -> &Events<E> {
+        &self.events
+    }
+}
+
+/// System data sending events of `E`
+pub struct EventWriter<'a, E: Event> {
+    events: Write<'a, Events<E>>,
+}
+
+impl<'a, E: Event> SystemData<'a> for EventWriter<'a, E> {
+    fn setup(world: &mut World) {
+        EventRegistry::register::<E>(world);
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        Self {
+            events: Write::fetch(world),
+        }
+    }
+
+    fn reads() -> Vec<ResourceId> {
+        Write::<Events<E>>::reads()
+    }
+
+    fn writes() -> Vec<ResourceId> {
+        Write::<Events<E>>::writes()
+    }
+}
+
+impl<E: Event> EventWriter<'_, E> {
+    pub fn send(&mut self, event: E) {
+        self.events.send(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = E>) {
+        self.events.send_batch(events);
+    }
+}
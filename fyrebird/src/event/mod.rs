@@ -1,21 +1,71 @@
 use std::{
-    any::{Any, TypeId},
-    collections::HashMap,
-    sync::Mutex,
+    any::{Any, TypeId, type_name},
+    cell::Cell,
+    sync::atomic::{AtomicUsize, Ordering},
+    sync::{Arc, Mutex},
 };
 
+use log::error;
+use subscription::{Listeners, Propagation, SubscribeOptions, Subscription};
+
 use specs::Component;
 use specs::{VecStorage, WriteStorage};
 use specs_derive::Component;
 
+pub mod channel;
+pub mod subscription;
+
+#[cfg(test)]
+mod tests;
+
 pub trait Event: Any + Send + Sync {}
 
 impl<T: Any + Send + Sync> Event for T {}
 
-pub type EventCallback = Box<dyn Fn(&dyn Any) + Send + Sync>;
+pub type EventCallback = Arc<dyn Fn(&dyn Any) -> Propagation + Send + Sync>;
+
+/// Event waiting in the deferred queue
+type DeferredEvent = Box<dyn FnOnce(&EventSystem) + Send>;
+
+/// Nested dispatches allowed by default before a dispatch is dropped as a runaway loop
+pub const DEFAULT_MAX_DISPATCH_DEPTH: usize = 32;
+
+thread_local! {
+    /// Dispatches running on this thread, the callbacks of one dispatching the next
+    static DISPATCH_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Leaves a dispatch level when dropped, even if a callback panics
+struct DepthGuard;
+
+impl DepthGuard {
+    fn enter() -> (Self, usize) {
+        let depth = DISPATCH_DEPTH.with(|depth| {
+            depth.set(depth.get() + 1);
+            depth.get()
+        });
+        (DepthGuard, depth)
+    }
+}
 
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        DISPATCH_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+/// Resource sending events to the callbacks subscribed to their type, highest priority first.
+/// A listener stays subscribed while its `Subscription` lives, and can consume an event to
+/// keep it from the listeners after it.
+/// Callbacks may dispatch, defer and subscribe themselves, the listeners aren't locked while
+/// they run. Subscriptions made during a dispatch apply from the next one, while listeners
+/// unsubscribed during a dispatch don't get its event anymore. Events can also be
+/// deferred, the `GameLoop` flushes them at the end of every frame.
 pub struct EventSystem {
-    listeners: Mutex<HashMap<TypeId, Vec<EventCallback>>>,
+    listeners: Arc<Mutex<Listeners>>,
+    deferred: Mutex<Vec<DeferredEvent>>,
+    max_depth: usize,
+    runaway: AtomicUsize,
 }
 
 impl EventSystem {
@@ -23,48 +73,144 @@ impl EventSystem {
         Self::default()
     }
 
-    pub fn subscribe<E: Event>(&self, callback: impl Fn(&E) + Send + Sync + 'static) {
+    /// Allows `max_depth` dispatches nested in callbacks
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Calls `callback` with every event of type `E` until the subscription is dropped
+    pub fn subscribe<E: Event>(
+        &self,
+        callback: impl Fn(&E) + Send + Sync + 'static,
+    ) -> Subscription {
+        self.subscribe_with(SubscribeOptions::default(), move |event: &E| {
+            callback(event);
+            Propagation::Continue
+        })
+    }
+
+    /// Calls `callback` with events of type `E` as set by `options`.
+    /// The callback can consume an event to stop its propagation.
+    pub fn subscribe_with<E: Event>(
+        &self,
+        options: SubscribeOptions,
+        callback: impl Fn(&E) -> Propagation + Send + Sync + 'static,
+    ) -> Subscription {
         let type_id = TypeId::of::<E>();
 
-        let callback = Box::new(move |event: &dyn Any| {
-            if let Some(e) = event.downcast_ref::<E>() {
-                callback(e);
-            }
+        let callback = Arc::new(move |event: &dyn Any| match event.downcast_ref::<E>() {
+            Some(e) => callback(e),
+            None => Propagation::Continue,
         });
 
-        let mut listeners = self.listeners.lock().unwrap();
-        listeners.entry(type_id).or_default().push(callback);
+        let id = self
+            .listeners
+            .lock()
+            .unwrap()
+            .insert(type_id, options, callback);
+        Subscription::new(&self.listeners, type_id, id)
     }
 
-    pub fn dispatch<E: Event>(&self, event: E) {
-        let type_id = TypeId::of::<E>();
+    /// Calls `callback` with the next event of type `E` only
+    pub fn subscribe_once<E: Event>(
+        &self,
+        callback: impl Fn(&E) + Send + Sync + 'static,
+    ) -> Subscription {
+        self.subscribe_with(SubscribeOptions::new().once(), move |event: &E| {
+            callback(event);
+            Propagation::Continue
+        })
+    }
 
-        let listeners = self.listeners.lock().unwrap();
+    /// Sends `event` to its callbacks right away, returns true if one of them consumed it.
+    /// A dispatch nested deeper than the max depth is dropped and reported, as callbacks are
+    /// most likely sending events to each other in a loop.
+    pub fn dispatch<E: Event>(&self, event: E) -> bool {
+        let (_guard, depth) = DepthGuard::enter();
+        if depth > self.max_depth {
+            self.runaway.fetch_add(1, Ordering::Relaxed);
+            error!(
+                "Dropped {} dispatched {depth} levels deep, events are likely sent in a loop",
+                type_name::<E>()
+            );
+            return false;
+        }
 
-        if let Some(callbacks) = listeners.get(&type_id) {
-            for callback in callbacks {
-                callback(&event);
+        let type_id = TypeId::of::<E>();
+        // Callbacks run on a snapshot so they can use the event system themselves, listeners
+        // unsubscribed meanwhile are skipped by `claim`
+        let listeners = match self.listeners.lock().unwrap().get(type_id) {
+            Some(listeners) => listeners.clone(),
+            None => return false,
+        };
+
+        for listener in listeners {
+            if !listener.claim() {
+                continue;
+            }
+            if listener.options.once {
+                self.listeners.lock().unwrap().remove(type_id, listener.id);
+            }
+            if (listener.callback)(&event) == Propagation::Consumed {
+                return true;
             }
         }
+        false
+    }
+
+    /// Queues `event` until the next `flush`
+    pub fn defer<E: Event>(&self, event: E) {
+        self.deferred
+            .lock()
+            .unwrap()
+            .push(Box::new(move |events: &EventSystem| {
+                events.dispatch(event);
+            }));
+    }
+
+    /// Dispatches the deferred events in the order they were queued, events deferred by
+    /// their callbacks wait for the next flush. Returns the number of events dispatched.
+    pub fn flush(&self) -> usize {
+        let deferred = std::mem::take(&mut *self.deferred.lock().unwrap());
+        let count = deferred.len();
+        for dispatch in deferred {
+            dispatch(self);
+        }
+        count
+    }
+
+    /// Number of events waiting for the next flush
+    pub fn deferred_len(&self) -> usize {
+        self.deferred.lock().unwrap().len()
+    }
+
+    /// Number of dispatches dropped for going past the max depth
+    pub fn runaway_dispatches(&self) -> usize {
+        self.runaway.load(Ordering::Relaxed)
     }
 
+    /// Unsubscribes every listener of `E`
     pub fn clear<E: Event>(&self) {
         let type_id = TypeId::of::<E>();
 
         let mut listeners = self.listeners.lock().unwrap();
-        listeners.remove(&type_id);
+        listeners.clear(type_id);
     }
 
     pub fn clear_all(&self) {
         let mut listeners = self.listeners.lock().unwrap();
-        listeners.clear();
+        listeners.clear_all();
     }
 }
 
 impl Default for EventSystem {
     fn default() -> Self {
         Self {
-            listeners: Mutex::new(HashMap::new()),
+            listeners: Arc::new(Mutex::new(Listeners::default())),
+            deferred: Mutex::new(Vec::new()),
+            max_depth: DEFAULT_MAX_DISPATCH_DEPTH,
+            runaway: AtomicUsize::new(0),
         }
     }
 }
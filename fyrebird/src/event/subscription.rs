@@ -0,0 +1,186 @@
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+use super::EventCallback;
+
+/// Tells the `EventSystem` whether to keep sending an event to the next listeners
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Propagation {
+    #[default]
+    Continue,
+    /// The event was handled, listeners of lower priority don't get it
+    Consumed,
+}
+
+/// How a listener is called, see `EventSystem::subscribe_with`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SubscribeOptions {
+    /// Listeners of higher priority get events first, ties go in subscription order
+    pub priority: i32,
+    /// The listener unsubscribes after its first event
+    pub once: bool,
+}
+
+impl SubscribeOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn once(mut self) -> Self {
+        self.once = true;
+        self
+    }
+}
+
+pub(super) struct Listener {
+    pub id: u64,
+    pub options: SubscribeOptions,
+    /// Set once a once-only listener got its event, as nested dispatches may still hold it
+    pub fired: AtomicBool,
+    /// Set once the listener is unsubscribed, as dispatches running meanwhile may still hold it
+    pub removed: AtomicBool,
+    pub callback: EventCallback,
+}
+
+impl Listener {
+    /// Returns false if the listener must not be called anymore
+    pub fn claim(&self) -> bool {
+        if self.removed.load(Ordering::Relaxed) {
+            return false;
+        }
+        !self.options.once || !self.fired.swap(true, Ordering::Relaxed)
+    }
+
+    fn mark_removed(&self) {
+        self.removed.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Listeners of every event type, sorted by priority
+#[derive(Default)]
+pub(super) struct Listeners {
+    by_type: HashMap<TypeId, Vec<Arc<Listener>>>,
+    next_id: u64,
+}
+
+impl Listeners {
+    pub fn insert(
+        &mut self,
+        type_id: TypeId,
+        options: SubscribeOptions,
+        callback: EventCallback,
+    ) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let listeners = self.by_type.entry(type_id).or_default();
+        let index = listeners.partition_point(|l| l.options.priority >= options.priority);
+        listeners.insert(
+            index,
+            Arc::new(Listener {
+                id,
+                options,
+                fired: AtomicBool::new(false),
+                removed: AtomicBool::new(false),
+                callback,
+            }),
+        );
+        id
+    }
+
+    pub fn get(&self, type_id: TypeId) -> Option<&Vec<Arc<Listener>>> {
+        self.by_type.get(&type_id)
+    }
+
+    pub fn contains(&self, type_id: TypeId, id: u64) -> bool {
+        self.by_type
+            .get(&type_id)
+            .is_some_and(|listeners| listeners.iter().any(|l| l.id == id))
+    }
+
+    pub fn remove(&mut self, type_id: TypeId, id: u64) -> bool {
+        let Some(listeners) = self.by_type.get_mut(&type_id) else {
+            return false;
+        };
+        let Some(index) = listeners.iter().position(|l| l.id == id) else {
+            return false;
+        };
+        listeners.remove(index).mark_removed();
+        true
+    }
+
+    pub fn clear(&mut self, type_id: TypeId) {
+        for listener in self.by_type.remove(&type_id).into_iter().flatten() {
+            listener.mark_removed();
+        }
+    }
+
+    pub fn clear_all(&mut self) {
+        for (_, listeners) in self.by_type.drain() {
+            listeners
+                .iter()
+                .for_each(|listener| listener.mark_removed());
+        }
+    }
+}
+
+/// Handle to a listener of the `EventSystem`, unsubscribing it when dropped.
+/// Call `detach` to keep the listener for as long as the event system lives.
+#[must_use = "dropping a Subscription unsubscribes its listener, call `detach` to keep it"]
+pub struct Subscription {
+    listeners: Option<Weak<Mutex<Listeners>>>,
+    type_id: TypeId,
+    id: u64,
+}
+
+impl Subscription {
+    pub(super) fn new(listeners: &Arc<Mutex<Listeners>>, type_id: TypeId, id: u64) -> Self {
+        Self {
+            listeners: Some(Arc::downgrade(listeners)),
+            type_id,
+            id,
+        }
+    }
+
+    /// Returns true while the listener is subscribed
+    pub fn is_active(&self) -> bool {
+        self.listeners
+            .as_ref()
+            .and_then(Weak::upgrade)
+            .is_some_and(|listeners| listeners.lock().unwrap().contains(self.type_id, self.id))
+    }
+
+    /// Removes the listener now, returns false if it was already gone
+    pub fn unsubscribe(mut self) -> bool {
+        self.remove()
+    }
+
+    /// Keeps the listener subscribed after the handle is dropped
+    pub fn detach(mut self) {
+        self.listeners = None;
+    }
+
+    fn remove(&mut self) -> bool {
+        match self
+            .listeners
+            .take()
+            .and_then(|listeners| listeners.upgrade())
+        {
+            Some(listeners) => listeners.lock().unwrap().remove(self.type_id, self.id),
+            None => false,
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
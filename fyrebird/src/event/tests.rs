@@ -0,0 +1,381 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use specs::{DispatcherBuilder, System, World, WorldExt, Write};
+
+use crate::game::game_loop::GameLoop;
+use crate::time::Time;
+
+use super::EventSystem;
+use super::channel::{EventCursor, EventReader, EventRegistry, EventWriter, Events};
+use super::subscription::{Propagation, SubscribeOptions};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Ping(u32);
+
+#[test]
+fn test_events_live_two_frames() {
+    let mut events = Events::new();
+    let mut every_frame = EventCursor::new();
+    let mut every_other_frame = EventCursor::new();
+
+    events.send(Ping(0));
+    events.send(Ping(1));
+    assert_eq!(
+        every_frame.read(&events).copied().collect::<Vec<_>>(),
+        vec![Ping(0), Ping(1)]
+    );
+
+    events.update();
+    events.send(Ping(2));
+    assert_eq!(events.len(), 3);
+    assert_eq!(every_other_frame.len(&events), 3);
+    assert_eq!(every_frame.read(&events).count(), 1);
+    assert_eq!(every_other_frame.read(&events).count(), 3);
+
+    // Ping(2) is still held the frame after it was sent
+    events.update();
+    events.send(Ping(3));
+    assert_eq!(
+        every_frame.read(&events).copied().collect::<Vec<_>>(),
+        vec![Ping(3)]
+    );
+    events.update();
+    events.update();
+    events.send(Ping(4));
+    assert_eq!(every_frame.len(&events), 1);
+    assert_eq!(every_frame.read(&events).count(), 1);
+    assert_eq!(every_frame.missed(), 0);
+
+    // Ping(3) was dropped before this cursor got to it
+    assert_eq!(
+        every_other_frame.read(&events).copied().collect::<Vec<_>>(),
+        vec![Ping(4)]
+    );
+    assert_eq!(every_other_frame.missed(), 1);
+
+    let mut late = events.latest_cursor();
+    events.send(Ping(5));
+    assert_eq!(
+        late.read(&events).copied().collect::<Vec<_>>(),
+        vec![Ping(5)]
+    );
+
+    events.clear();
+    assert!(events.is_empty());
+    assert!(late.is_empty(&events));
+    assert_eq!(late.read(&events).count(), 0);
+}
+
+#[test]
+fn test_cursors_ahead_of_the_queue_read_nothing() {
+    let mut events = Events::new();
+    events.send_batch([Ping(0), Ping(1), Ping(2)]);
+    let mut cursor = EventCursor::new();
+    assert_eq!(cursor.read(&events).count(), 3);
+
+    // The queue was created again, the cursor is past all of its events
+    let mut events = Events::new();
+    events.send(Ping(3));
+    assert_eq!(cursor.len(&events), 0);
+    assert_eq!(cursor.read(&events).count(), 0);
+}
+
+struct PingSystem {
+    frame: u32,
+}
+
+impl<'a> System<'a> for PingSystem {
+    type SystemData = EventWriter<'a, Ping>;
+
+    fn run(&mut self, mut pings: Self::SystemData) {
+        pings.send_batch([Ping(self.frame), Ping(self.frame + 100)]);
+        self.frame += 1;
+    }
+}
+
+#[derive(Default)]
+struct Received(Vec<Ping>);
+
+#[derive(Default)]
+struct ListenSystem {
+    cursor: EventCursor<Ping>,
+}
+
+impl<'a> System<'a> for ListenSystem {
+    type SystemData = (EventReader<'a, Ping>, Write<'a, Received>);
+
+    fn run(&mut self, (pings, mut received): Self::SystemData) {
+        received.0.extend(pings.read(&mut self.cursor));
+    }
+}
+
+#[test]
+fn test_systems_send_and_read_events() {
+    let mut world = World::new();
+    // The listener runs before the sender and gets its events the next frame
+    let update = DispatcherBuilder::new()
+        .with(ListenSystem::default(), "listen", &[])
+        .with(PingSystem { frame: 0 }, "ping", &["listen"])
+        .build();
+    let fixed = DispatcherBuilder::new().build();
+    let mut game_loop = GameLoop::new(update, fixed);
+    game_loop.setup(&mut world);
+    assert!(world.has_value::<EventRegistry>());
+
+    let mut time = Time::new();
+    for _ in 0..3 {
+        time.advance(0.01);
+        game_loop.run_frame(&mut time, &mut world);
+    }
+
+    assert_eq!(
+        world.read_resource::<Received>().0,
+        vec![Ping(0), Ping(100), Ping(1), Ping(101)]
+    );
+    // Only the last two frames are held
+    let events = world.read_resource::<Events<Ping>>();
+    assert_eq!(events.len(), 4);
+    assert_eq!(events.event_count(), 6);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Pong(u32);
+
+#[test]
+fn test_callbacks_use_the_event_system() {
+    let events = Arc::new(EventSystem::new());
+    let received = Arc::new(Mutex::new(Vec::new()));
+
+    // Pings are answered with a pong, and the first one subscribes a late listener
+    let system = events.clone();
+    let log = received.clone();
+    let _pings = events.subscribe(move |ping: &Ping| {
+        log.lock().unwrap().push(format!("ping {}", ping.0));
+        if ping.0 == 0 {
+            let log = log.clone();
+            system
+                .subscribe(move |ping: &Ping| log.lock().unwrap().push(format!("late {}", ping.0)))
+                .detach();
+        }
+        system.dispatch(Pong(ping.0));
+    });
+    let log = received.clone();
+    let _pongs =
+        events.subscribe(move |pong: &Pong| log.lock().unwrap().push(format!("pong {}", pong.0)));
+
+    events.dispatch(Ping(0));
+    events.dispatch(Ping(1));
+    assert_eq!(
+        *received.lock().unwrap(),
+        vec!["ping 0", "pong 0", "ping 1", "pong 1", "late 1"]
+    );
+}
+
+#[test]
+fn test_deferred_events_wait_for_flush() {
+    let events = Arc::new(EventSystem::new());
+    let received = Arc::new(Mutex::new(Vec::new()));
+
+    let system = events.clone();
+    let log = received.clone();
+    let _pings = events.subscribe(move |ping: &Ping| {
+        log.lock().unwrap().push(*ping);
+        if ping.0 < 2 {
+            system.defer(Ping(ping.0 + 1));
+        }
+    });
+
+    events.defer(Ping(0));
+    assert!(received.lock().unwrap().is_empty());
+    assert_eq!(events.deferred_len(), 1);
+
+    // Events deferred by callbacks wait for the next flush
+    assert_eq!(events.flush(), 1);
+    assert_eq!(*received.lock().unwrap(), vec![Ping(0)]);
+    assert_eq!(events.flush(), 1);
+    assert_eq!(events.flush(), 1);
+    assert_eq!(events.flush(), 0);
+    assert_eq!(*received.lock().unwrap(), vec![Ping(0), Ping(1), Ping(2)]);
+}
+
+#[test]
+fn test_runaway_dispatch_is_stopped() {
+    let events = Arc::new(EventSystem::new().with_max_depth(8));
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    // Pings and pongs bounce off each other forever
+    let system = events.clone();
+    let count = calls.clone();
+    let _pings = events.subscribe(move |ping: &Ping| {
+        count.fetch_add(1, Ordering::Relaxed);
+        system.dispatch(Pong(ping.0 + 1));
+    });
+    let system = events.clone();
+    let _pongs = events.subscribe(move |pong: &Pong| {
+        system.dispatch(Ping(pong.0 + 1));
+    });
+
+    events.dispatch(Ping(0));
+    assert_eq!(calls.load(Ordering::Relaxed), 4);
+    assert_eq!(events.runaway_dispatches(), 1);
+
+    // The depth is back to zero once the loop unwound
+    events.dispatch(Ping(0));
+    assert_eq!(calls.load(Ordering::Relaxed), 8);
+    assert_eq!(events.runaway_dispatches(), 2);
+}
+
+#[test]
+fn test_game_loop_flushes_deferred_events() {
+    let mut world = World::new();
+    let update = DispatcherBuilder::new().build();
+    let fixed = DispatcherBuilder::new().build();
+    let mut game_loop = GameLoop::new(update, fixed);
+    game_loop.setup(&mut world);
+    world.insert(EventSystem::new());
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let count = calls.clone();
+    let events = world.read_resource::<EventSystem>();
+    events
+        .subscribe(move |_: &Ping| {
+            count.fetch_add(1, Ordering::Relaxed);
+        })
+        .detach();
+    events.defer(Ping(0));
+    drop(events);
+
+    let mut time = Time::new();
+    time.advance(0.01);
+    game_loop.run_frame(&mut time, &mut world);
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+    assert_eq!(world.read_resource::<EventSystem>().deferred_len(), 0);
+}
+
+#[test]
+fn test_subscriptions_unsubscribe_on_drop() {
+    let events = EventSystem::new();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let subscribe = |events: &EventSystem| {
+        let count = calls.clone();
+        events.subscribe(move |_: &Ping| {
+            count.fetch_add(1, Ordering::Relaxed);
+        })
+    };
+
+    let first = subscribe(&events);
+    let second = subscribe(&events);
+    events.dispatch(Ping(0));
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+
+    drop(first);
+    events.dispatch(Ping(1));
+    assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+    assert!(second.is_active());
+    assert!(second.unsubscribe());
+    events.dispatch(Ping(2));
+    assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+    // Detached listeners stay until cleared
+    subscribe(&events).detach();
+    events.dispatch(Ping(3));
+    assert_eq!(calls.load(Ordering::Relaxed), 4);
+    events.clear::<Ping>();
+    events.dispatch(Ping(4));
+    assert_eq!(calls.load(Ordering::Relaxed), 4);
+}
+
+#[test]
+fn test_listeners_unsubscribed_during_dispatch_are_skipped() {
+    let events = Arc::new(EventSystem::new());
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    // The first listener drops the subscription of the second one
+    let second = Arc::new(Mutex::new(None));
+    let held = second.clone();
+    let _first = events.subscribe(move |_: &Ping| {
+        held.lock().unwrap().take();
+    });
+    let count = calls.clone();
+    *second.lock().unwrap() = Some(events.subscribe(move |_: &Ping| {
+        count.fetch_add(1, Ordering::Relaxed);
+    }));
+    events.dispatch(Ping(0));
+    assert_eq!(calls.load(Ordering::Relaxed), 0);
+
+    // Same when the event system is cleared by a listener
+    let system = events.clone();
+    let count = calls.clone();
+    events
+        .subscribe(move |_: &Pong| {
+            count.fetch_add(1, Ordering::Relaxed);
+            system.clear_all();
+        })
+        .detach();
+    let count = calls.clone();
+    events
+        .subscribe(move |_: &Pong| {
+            count.fetch_add(1, Ordering::Relaxed);
+        })
+        .detach();
+    events.dispatch(Pong(0));
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn test_listener_priorities_and_consumption() {
+    let events = EventSystem::new();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let listen = |name: &'static str, options: SubscribeOptions, consume_odd: bool| {
+        let log = received.clone();
+        events.subscribe_with(options, move |ping: &Ping| {
+            log.lock().unwrap().push(name);
+            if consume_odd && ping.0 % 2 == 1 {
+                Propagation::Consumed
+            } else {
+                Propagation::Continue
+            }
+        })
+    };
+
+    let _low = listen("low", SubscribeOptions::new().with_priority(-1), false);
+    let _first = listen("first", SubscribeOptions::new(), false);
+    let _second = listen("second", SubscribeOptions::new(), false);
+    let _high = listen("high", SubscribeOptions::new().with_priority(10), true);
+    let once = listen(
+        "once",
+        SubscribeOptions::new().with_priority(20).once(),
+        false,
+    );
+
+    assert!(!events.dispatch(Ping(0)));
+    assert_eq!(
+        *received.lock().unwrap(),
+        vec!["once", "high", "first", "second", "low"]
+    );
+    assert!(!once.is_active());
+
+    // Consumed by the high priority listener
+    received.lock().unwrap().clear();
+    assert!(events.dispatch(Ping(1)));
+    assert_eq!(*received.lock().unwrap(), vec!["high"]);
+}
+
+#[test]
+fn test_once_listeners_fire_once_when_nested() {
+    let events = Arc::new(EventSystem::new());
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    let system = events.clone();
+    let count = calls.clone();
+    let _once = events.subscribe_once(move |ping: &Ping| {
+        count.fetch_add(1, Ordering::Relaxed);
+        system.dispatch(Ping(ping.0 + 1));
+    });
+
+    events.dispatch(Ping(0));
+    events.dispatch(Ping(0));
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+}
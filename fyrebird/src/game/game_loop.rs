@@ -0,0 +1,82 @@
+use specs::{Dispatcher, World, WorldExt};
+
+use crate::event::EventSystem;
+use crate::event::channel::EventRegistry;
+use crate::time::scheduler::Scheduler;
+use crate::time::stats::FrameStats;
+use crate::time::{Interpolation, Time};
+
+/// Runs the systems of a world once per frame and on the fixed timestep.
+/// Each frame starts by updating the registered `Events`, then the update dispatcher runs
+/// once with the frame delta time, followed by the tasks of the `Scheduler`, then the fixed
+/// dispatcher catches up with the time accumulated in `Time`, at most `max_fixed_steps`
+/// times. The `Time` resource of the world mirrors the engine's one while they run and the
+/// changes systems make to it are kept. The `Interpolation` resource is left with the alpha
+/// to render the frame with, and the frame is recorded in `FrameStats`. The events deferred
+/// on the `EventSystem` are flushed at the end of the frame.
+pub struct GameLoop {
+    update: Dispatcher<'static, 'static>,
+    fixed: Dispatcher<'static, 'static>,
+}
+
+impl GameLoop {
+    pub fn new(update: Dispatcher<'static, 'static>, fixed: Dispatcher<'static, 'static>) -> Self {
+        Self { update, fixed }
+    }
+
+    /// Registers the components and resources both dispatchers need
+    pub fn setup(&mut self, world: &mut World) {
+        self.update.setup(world);
+        self.fixed.setup(world);
+        world.insert(Interpolation::default());
+        world.insert(Scheduler::default());
+        world.insert(FrameStats::default());
+    }
+
+    /// Measures the frame with the clock of `time` and runs it
+    pub fn update(&mut self, time: &mut Time, world: &mut World) -> u32 {
+        time.update();
+        self.run_frame(time, world)
+    }
+
+    /// Runs one frame of `time.delta_time`, already advanced by the caller.
+    /// Returns the number of fixed updates run.
+    pub fn run_frame(&mut self, time: &mut Time, world: &mut World) -> u32 {
+        EventRegistry::update(world);
+        world.insert(time.clone());
+        self.update.dispatch(world);
+        world.maintain();
+        Scheduler::run(world);
+        world.maintain();
+        // Systems may have paused or rescaled the time
+        time.clone_from(&world.read_resource::<Time>());
+
+        let mut steps = 0;
+        while time.should_fixed_update() {
+            if steps >= time.max_fixed_steps {
+                time.drop_fixed_backlog();
+                break;
+            }
+
+            time.consume_fixed_update();
+            world.insert(time.clone());
+            self.fixed.dispatch(world);
+            world.maintain();
+            time.clone_from(&world.read_resource::<Time>());
+            steps += 1;
+        }
+
+        if let Some(events) = world.try_fetch::<EventSystem>() {
+            events.flush();
+        }
+
+        world.insert(time.clone());
+        world.insert(Interpolation {
+            alpha: time.interpolation_alpha(),
+        });
+        if let Some(mut stats) = world.try_fetch_mut::<FrameStats>() {
+            stats.record_time(time, steps);
+        }
+        steps
+    }
+}
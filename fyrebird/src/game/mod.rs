@@ -1,51 +1,125 @@
-use anyhow::Result;
-use specs::{Dispatcher, DispatcherBuilder, WorldExt};
+pub mod game_loop;
+
+#[cfg(test)]
+mod tests;
+
+use anyhow::{Result, bail};
+use specs::DispatcherBuilder;
 use winit::{
     application::ApplicationHandler,
     dpi::{LogicalPosition, LogicalSize, Position},
-    event_loop::ActiveEventLoop,
+    event_loop::{ActiveEventLoop, ControlFlow},
     window::{Fullscreen, Window},
 };
 
 use crate::{
-    behavior::BehaviorSystem,
+    behavior::{BehaviorSystem, FixedBehaviorSystem},
     collision::CollisionSystem,
-    physics::Physics2DSystem,
+    components::TransformHistorySystem,
+    particles::ParticleSystem,
+    physics::{Physics2DSystem, character::CharacterControllerSystem, soft_body::SoftBodySystem},
     scene::Scene,
-    time::Time,
+    time::{
+        Time, TimeSystem,
+        clock::Clock,
+        pacing::{FrameLimiter, FramePace},
+        stats::FrameStats,
+    },
     vulkan::{self, VulkanModule},
     window::{BaseWindowAttr, GameWindow},
 };
+use game_loop::GameLoop;
 
-pub struct GameEngine<'a> {
+pub struct GameEngine {
     time: Time,
     scenes: Vec<Scene>,
-    active_scene: Option<&'a mut Scene>,
-    dispatcher: Dispatcher<'static, 'static>,
+    /// Index in `scenes` of the scene the game loop runs
+    active_scene: Option<usize>,
+    game_loop: GameLoop,
+    limiter: FrameLimiter,
     window: GameWindow,
     vulkan: Option<VulkanModule>,
     started: bool,
 }
 
-impl GameEngine<'_> {
-    fn update(&mut self) {
-        // Update systems
-        if let Some(scene) = &mut self.active_scene {
-            self.dispatcher.dispatch(&scene.world);
-            scene.world.maintain();
+impl GameEngine {
+    /// Runs one frame and waits for its deadline
+    fn update(&mut self) -> FramePace {
+        match self.active_scene.map(|index| &mut self.scenes[index]) {
+            Some(scene) => {
+                self.game_loop.update(&mut self.time, &mut scene.world);
+                let pace = self.limiter.wait(self.time.clock());
+                if pace.missed
+                    && let Some(mut stats) = scene.world.try_fetch_mut::<FrameStats>()
+                {
+                    stats.record_missed_deadline();
+                }
+                pace
+            }
+            None => {
+                // No fixed updates to catch up on once a scene is activated
+                self.time.update();
+                self.time.drop_fixed_backlog();
+                self.limiter.wait(self.time.clock())
+            }
+        }
+    }
+
+    /// Registers the resources and components the engine systems need in the scene
+    pub fn setup_scene(&mut self, scene: &mut Scene) {
+        self.game_loop.setup(&mut scene.world);
+    }
+
+    /// Sets up the scene and adds it to the engine, returns its index
+    pub fn add_scene(&mut self, mut scene: Scene) -> usize {
+        self.setup_scene(&mut scene);
+        self.scenes.push(scene);
+        self.scenes.len() - 1
+    }
+
+    /// Makes the scene at `index` the one run every frame
+    pub fn set_active_scene(&mut self, index: usize) -> Result<()> {
+        if index >= self.scenes.len() {
+            bail!(
+                "No scene at index {index}, the engine has {}",
+                self.scenes.len()
+            );
         }
+        self.active_scene = Some(index);
+        Ok(())
+    }
+
+    /// Adds the scene and makes it the one run every frame, returns its index
+    pub fn activate_scene(&mut self, scene: Scene) -> usize {
+        let index = self.add_scene(scene);
+        self.active_scene = Some(index);
+        index
+    }
+
+    pub fn active_scene(&self) -> Option<&Scene> {
+        self.active_scene.map(|index| &self.scenes[index])
+    }
+
+    pub fn active_scene_mut(&mut self) -> Option<&mut Scene> {
+        self.active_scene.map(|index| &mut self.scenes[index])
     }
 
     pub fn get_time(&self) -> &Time {
         &self.time
     }
 
+    pub fn get_frame_limiter(&mut self) -> &mut FrameLimiter {
+        &mut self.limiter
+    }
+
     pub fn builder() -> GameEngineBuilder {
         GameEngineBuilder {
             window_title: "Fyrebird_data".to_owned(),
             window_height: 720.0,
             window_width: 1280.0,
             window_aspect_ratio: 16.0 / 9.0,
+            time: Time::new(),
+            limiter: FrameLimiter::new(),
         }
     }
 
@@ -60,8 +134,10 @@ impl GameEngine<'_> {
     }
 }
 
-impl ApplicationHandler for GameEngine<'_> {
+impl ApplicationHandler for GameEngine {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        // Frames keep running without waiting for window events
+        event_loop.set_control_flow(ControlFlow::Poll);
         if !self.started {
             self.started = true;
             self.start(event_loop);
@@ -74,6 +150,15 @@ impl ApplicationHandler for GameEngine<'_> {
         window_id: winit::window::WindowId,
         event: winit::event::WindowEvent,
     ) {
+        if let winit::event::WindowEvent::Focused(focused) = event {
+            self.limiter.set_focused(focused);
+        }
+    }
+
+    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
+        // Runs one frame each time the event loop has drained its events. The event loop
+        // polls, pacing is left to the frame limiter which blocks until the deadline.
+        self.update();
     }
 }
 
@@ -82,16 +167,34 @@ pub struct GameEngineBuilder {
     window_height: f32,
     window_width: f32,
     window_aspect_ratio: f32,
+    time: Time,
+    limiter: FrameLimiter,
 }
 
 impl GameEngineBuilder {
     /// Build the game engine using the current values.
-    pub fn build(self) -> Result<GameEngine<'static>> {
-        let time = Time::new();
-        let dispatcher = DispatcherBuilder::new()
-            .with(BehaviorSystem, "behaviors", &[])
-            .with(CollisionSystem, "collisions", &["behaviors"])
+    pub fn build(self) -> Result<GameEngine> {
+        let time = self.time;
+        let update = DispatcherBuilder::new()
+            .with(TimeSystem, "timers", &[])
+            .with(BehaviorSystem, "behaviors", &["timers"])
+            .with(ParticleSystem, "particles", &["behaviors"])
+            .build();
+        let fixed = DispatcherBuilder::new()
+            .with(TransformHistorySystem, "transform_history", &[])
+            .with(
+                FixedBehaviorSystem,
+                "fixed_behaviors",
+                &["transform_history"],
+            )
+            .with(
+                CharacterControllerSystem,
+                "characters",
+                &["fixed_behaviors"],
+            )
+            .with(CollisionSystem, "collisions", &["characters"])
             .with(Physics2DSystem, "physics", &["collisions"])
+            .with(SoftBodySystem, "soft_bodies", &["physics"])
             .build();
 
         let base_attr = BaseWindowAttr {
@@ -105,13 +208,32 @@ impl GameEngineBuilder {
             time,
             scenes: Vec::new(),
             active_scene: None,
-            dispatcher,
+            game_loop: GameLoop::new(update, fixed),
+            limiter: self.limiter,
             window: GameWindow::new(base_attr),
             vulkan: None,
             started: false,
         })
     }
 
+    /// Sets the clock frames are measured with, the wall clock by default
+    pub fn clock(&mut self, clock: impl Clock + 'static) -> &mut GameEngineBuilder {
+        self.time.set_clock(clock);
+        self
+    }
+
+    /// Caps the frame rate, uncapped by default
+    pub fn target_fps(&mut self, fps: f32) -> &mut GameEngineBuilder {
+        self.limiter.target_fps = Some(fps);
+        self
+    }
+
+    /// Caps the frame rate while the window is unfocused, the target frame rate by default
+    pub fn background_fps(&mut self, fps: f32) -> &mut GameEngineBuilder {
+        self.limiter.background_fps = Some(fps);
+        self
+    }
+
     /// Sets game window title
     pub fn title(&mut self, name: String) -> &mut GameEngineBuilder {
         self.window_title = name;
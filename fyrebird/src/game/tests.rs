@@ -0,0 +1,280 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use specs::{Builder, DispatcherBuilder, Entity, System, World, WorldExt, Write};
+
+use crate::arith::{point2, vec2};
+use crate::behavior::{Behavior, BehaviorContainer, BehaviorSystem, FixedBehaviorSystem};
+use crate::components::{PreviousTransform2D, Transform2D, TransformHistorySystem};
+use crate::physics::Physics2DSystem;
+use crate::physics::rigid_body::RigidBody2D;
+use crate::time::clock::{Clock, FixedStepClock, ManualClock};
+use crate::time::stats::FrameStats;
+use crate::time::{Interpolation, Time};
+
+use crate::scene::Scene;
+
+use super::GameEngine;
+use super::game_loop::GameLoop;
+
+struct Counter {
+    updates: Arc<AtomicUsize>,
+    fixed_updates: Arc<AtomicUsize>,
+}
+
+impl Behavior for Counter {
+    fn update(&mut self, _entity: Entity, _world: &mut World, _dt: f32) {
+        self.updates.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn fixed_update(&mut self, _entity: Entity, _world: &mut World) {
+        self.fixed_updates.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn game_loop(world: &mut World) -> GameLoop {
+    let update = DispatcherBuilder::new()
+        .with(BehaviorSystem, "behaviors", &[])
+        .build();
+    let fixed = DispatcherBuilder::new()
+        .with(TransformHistorySystem, "transform_history", &[])
+        .with(
+            FixedBehaviorSystem,
+            "fixed_behaviors",
+            &["transform_history"],
+        )
+        .with(Physics2DSystem, "physics", &["fixed_behaviors"])
+        .build();
+    let mut game_loop = GameLoop::new(update, fixed);
+    game_loop.setup(world);
+    game_loop
+}
+
+fn frame(game_loop: &mut GameLoop, time: &mut Time, world: &mut World, dt: f32) -> u32 {
+    time.advance(dt);
+    game_loop.run_frame(time, world)
+}
+
+#[test]
+fn test_fixed_updates_catch_up_with_frames() {
+    let mut world = World::new();
+    let mut game_loop = game_loop(&mut world);
+    let mut time = Time::new();
+    time.fixed_timestep = 0.25;
+
+    let updates = Arc::new(AtomicUsize::new(0));
+    let fixed_updates = Arc::new(AtomicUsize::new(0));
+    world
+        .create_entity()
+        .with(BehaviorContainer {
+            behaviors: vec![Box::new(Counter {
+                updates: updates.clone(),
+                fixed_updates: fixed_updates.clone(),
+            })],
+        })
+        .build();
+
+    assert_eq!(frame(&mut game_loop, &mut time, &mut world, 0.1), 0);
+    assert_eq!(frame(&mut game_loop, &mut time, &mut world, 0.5), 2);
+    assert_eq!(updates.load(Ordering::Relaxed), 2);
+    assert_eq!(fixed_updates.load(Ordering::Relaxed), 2);
+
+    let alpha = world.read_resource::<Interpolation>().alpha;
+    assert!((alpha - 0.4).abs() < 1e-4);
+    assert_eq!(world.read_resource::<Time>().delta_time, 0.5);
+}
+
+#[test]
+fn test_fixed_updates_are_capped() {
+    let mut world = World::new();
+    let mut game_loop = game_loop(&mut world);
+    let mut time = Time::new();
+    time.fixed_timestep = 0.25;
+    time.max_fixed_steps = 3;
+
+    // A long hitch runs the capped number of updates and drops the rest
+    assert_eq!(frame(&mut game_loop, &mut time, &mut world, 10.1), 3);
+    assert!(time.fixed_time_accumulator < time.fixed_timestep);
+    assert!((time.interpolation_alpha() - 0.4).abs() < 1e-3);
+
+    assert_eq!(frame(&mut game_loop, &mut time, &mut world, 0.2), 1);
+
+    let stats = world.read_resource::<FrameStats>();
+    assert_eq!(stats.frame_count(), 2);
+    assert_eq!(stats.fixed_steps().collect::<Vec<_>>(), vec![3, 1]);
+    assert!((stats.max_frame_time() - 10.1).abs() < 1e-4);
+}
+
+#[test]
+fn test_transforms_interpolate_between_fixed_updates() {
+    let mut world = World::new();
+    let mut game_loop = game_loop(&mut world);
+    let mut time = Time::new();
+    time.fixed_timestep = 0.25;
+
+    let body = world
+        .create_entity()
+        .with(Transform2D::default())
+        .with(RigidBody2D::kinematic().with_velocity(vec2(4.0, 0.0), 0.0))
+        .build();
+
+    frame(&mut game_loop, &mut time, &mut world, 0.625);
+
+    let transforms = world.read_storage::<Transform2D>();
+    let previous = world.read_storage::<PreviousTransform2D>();
+    let current = transforms.get(body).unwrap();
+    let previous = previous.get(body).unwrap();
+    assert_eq!(previous.0.position, point2(1.0, 0.0));
+    assert_eq!(current.position, point2(2.0, 0.0));
+
+    let alpha = world.read_resource::<Interpolation>().alpha;
+    let rendered = current.interpolate(&previous.0, alpha);
+    assert!((rendered.position.x - 1.5).abs() < 1e-4);
+}
+
+struct PauseSystem;
+
+impl<'a> System<'a> for PauseSystem {
+    type SystemData = Write<'a, Time>;
+
+    fn run(&mut self, mut time: Self::SystemData) {
+        time.pause();
+    }
+}
+
+#[test]
+fn test_paused_time_stops_fixed_updates() {
+    let mut world = World::new();
+    let update = DispatcherBuilder::new()
+        .with(PauseSystem, "pause", &[])
+        .build();
+    let fixed = DispatcherBuilder::new()
+        .with(Physics2DSystem, "physics", &[])
+        .build();
+    let mut game_loop = GameLoop::new(update, fixed);
+    game_loop.setup(&mut world);
+    let mut time = Time::new();
+    time.fixed_timestep = 0.25;
+
+    let body = world
+        .create_entity()
+        .with(Transform2D::default())
+        .with(RigidBody2D::kinematic().with_velocity(vec2(4.0, 0.0), 0.0))
+        .build();
+
+    // Paused by a system during the first frame, the pause sticks for the next one
+    assert_eq!(frame(&mut game_loop, &mut time, &mut world, 0.5), 2);
+    assert!(time.paused);
+    assert_eq!(frame(&mut game_loop, &mut time, &mut world, 0.5), 0);
+
+    let transforms = world.read_storage::<Transform2D>();
+    assert_eq!(transforms.get(body).unwrap().position, point2(2.0, 0.0));
+    assert_eq!(world.read_resource::<Time>().unscaled_delta_time, 0.5);
+}
+
+#[test]
+fn test_fixed_step_clock_runs_headless() {
+    let mut world = World::new();
+    let mut game_loop = game_loop(&mut world);
+    let mut time = Time::with_clock(FixedStepClock::from_hz(30.0));
+    time.fixed_timestep = 1.0 / 60.0;
+
+    let body = world
+        .create_entity()
+        .with(Transform2D::default())
+        .with(RigidBody2D::kinematic().with_velocity(vec2(6.0, 0.0), 0.0))
+        .build();
+
+    let steps: u32 = (0..30)
+        .map(|_| game_loop.update(&mut time, &mut world))
+        .sum();
+
+    // A second at 30 frames per second is 60 fixed updates, give or take rounding
+    assert!((59..=60).contains(&steps));
+    let x = world
+        .read_storage::<Transform2D>()
+        .get(body)
+        .unwrap()
+        .position
+        .x;
+    assert!((x - steps as f32 * 0.1).abs() < 1e-3);
+}
+
+#[test]
+fn test_engine_runs_the_active_scene() {
+    let mut builder = GameEngine::builder();
+    builder.clock(FixedStepClock::from_hz(30.0));
+    let mut engine = builder.build().unwrap();
+    assert!(engine.set_active_scene(0).is_err());
+
+    // Frames without a scene only measure time
+    engine.update();
+    assert!(engine.active_scene().is_none());
+
+    let index = engine.activate_scene(Scene {
+        world: World::new(),
+    });
+    let updates = Arc::new(AtomicUsize::new(0));
+    let fixed_updates = Arc::new(AtomicUsize::new(0));
+    let scene = engine.active_scene_mut().unwrap();
+    scene
+        .world
+        .create_entity()
+        .with(BehaviorContainer {
+            behaviors: vec![Box::new(Counter {
+                updates: updates.clone(),
+                fixed_updates: fixed_updates.clone(),
+            })],
+        })
+        .build();
+
+    for _ in 0..30 {
+        engine.update();
+    }
+
+    assert_eq!(updates.load(Ordering::Relaxed), 30);
+    let fixed = fixed_updates.load(Ordering::Relaxed);
+    assert!((59..=60).contains(&fixed), "Got {fixed}");
+    let world = &engine.active_scene().unwrap().world;
+    assert_eq!(world.read_resource::<FrameStats>().frame_count(), 30);
+
+    engine.set_active_scene(index).unwrap();
+}
+
+#[test]
+fn test_capped_frames_meet_their_deadlines() {
+    let clock = ManualClock::new();
+    let mut builder = GameEngine::builder();
+    builder.clock(clock.clone()).target_fps(50.0);
+    let mut engine = builder.build().unwrap();
+    engine.activate_scene(Scene {
+        world: World::new(),
+    });
+
+    // Frames taking less than their budget wait for the rest of it
+    for _ in 0..10 {
+        clock.advance_ms(5);
+        let pace = engine.update();
+        assert!(!pace.missed);
+    }
+    // The first frame starts the cadence, the others are a budget apart
+    assert_eq!(clock.now(), Duration::from_millis(5 + 9 * 20));
+    assert!((engine.get_time().unscaled_delta_time - 0.02).abs() < 1e-6);
+    assert_eq!(engine.get_frame_limiter().missed_deadlines(), 0);
+    let world = &engine.active_scene().unwrap().world;
+    assert_eq!(world.read_resource::<FrameStats>().missed_deadlines(), 0);
+}
+
+#[test]
+fn test_frames_run_on_worlds_without_setup() {
+    let mut world = World::new();
+    let mut game_loop = GameLoop::new(
+        DispatcherBuilder::new().build(),
+        DispatcherBuilder::new().build(),
+    );
+    let mut time = Time::new();
+
+    assert_eq!(frame(&mut game_loop, &mut time, &mut world, 0.06), 3);
+    assert!(world.has_value::<Interpolation>());
+}
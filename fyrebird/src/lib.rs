@@ -4,6 +4,7 @@ pub mod collision;
 pub mod components;
 pub mod event;
 pub mod game;
+pub mod particles;
 pub mod physics;
 pub mod scene;
 pub mod time;
@@ -0,0 +1,117 @@
+use crate::arith::Vec2;
+
+/// Linear RGBA color, channels from 0 to 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const WHITE: Color = Color::rgba(1.0, 1.0, 1.0, 1.0);
+    pub const TRANSPARENT: Color = Color::rgba(1.0, 1.0, 1.0, 0.0);
+
+    pub const fn rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Self::rgba(r, g, b, 1.0)
+    }
+
+    /// Same color with another alpha
+    pub const fn with_alpha(self, a: f32) -> Self {
+        Self::rgba(self.r, self.g, self.b, a)
+    }
+}
+
+impl Default for Color {
+    fn default() -> Self {
+        Self::WHITE
+    }
+}
+
+/// Values a `Curve` can blend between
+pub trait Lerp: Copy {
+    fn lerp(self, other: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Lerp for Vec2 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Lerp for Color {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Color::rgba(
+            self.r.lerp(other.r, t),
+            self.g.lerp(other.g, t),
+            self.b.lerp(other.b, t),
+            self.a.lerp(other.a, t),
+        )
+    }
+}
+
+/// Value changing over the life of a particle, sampled with the normalized age from 0 to 1.
+/// Keys are blended linearly, the first and last ones hold before and after them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve<T> {
+    /// Keys sorted by time
+    keys: Vec<(f32, T)>,
+}
+
+impl<T: Lerp> Curve<T> {
+    pub fn constant(value: T) -> Self {
+        Self {
+            keys: vec![(0.0, value)],
+        }
+    }
+
+    /// Goes from `start` at birth to `end` at death
+    pub fn linear(start: T, end: T) -> Self {
+        Self {
+            keys: vec![(0.0, start), (1.0, end)],
+        }
+    }
+
+    /// Adds a key at `time`, replacing any key already there
+    pub fn with_key(mut self, time: f32, value: T) -> Self {
+        let time = time.clamp(0.0, 1.0);
+        self.keys.retain(|(t, _)| *t != time);
+        let index = self.keys.partition_point(|(t, _)| *t < time);
+        self.keys.insert(index, (time, value));
+        self
+    }
+
+    pub fn keys(&self) -> &[(f32, T)] {
+        &self.keys
+    }
+
+    pub fn sample(&self, time: f32) -> T {
+        let index = self.keys.partition_point(|(t, _)| *t <= time);
+        match index {
+            0 => self.keys[0].1,
+            i if i == self.keys.len() => self.keys[i - 1].1,
+            i => {
+                let (t0, v0) = self.keys[i - 1];
+                let (t1, v1) = self.keys[i];
+                v0.lerp(v1, (time - t0) / (t1 - t0))
+            }
+        }
+    }
+}
+
+impl<T: Lerp + Default> Default for Curve<T> {
+    fn default() -> Self {
+        Self::constant(T::default())
+    }
+}
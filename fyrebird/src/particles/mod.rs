@@ -0,0 +1,500 @@
+pub mod curve;
+pub mod pool;
+
+#[cfg(test)]
+mod tests;
+
+use std::f32::consts::TAU;
+
+use cgmath::InnerSpace;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use specs::{
+    Component, Entities, Entity, Join, Read, ReadStorage, System, VecStorage, WriteStorage,
+};
+use specs_derive::Component;
+
+use crate::arith::ray::Ray2D;
+use crate::arith::{EPSILON, Interval, Point2, Vec2, point2, rad, rotate_vec2, vec2};
+use crate::collision::broad_phase::CollisionWorld;
+use crate::collision::shapes::Aabb;
+use crate::collision::{ALL_LAYERS, query};
+use crate::components::Transform2D;
+use crate::physics::Gravity;
+use crate::time::Time;
+
+use curve::{Color, Curve};
+use pool::{ParticlePool, Spawn};
+
+/// Distance particles are kept away from the surfaces they bounce off
+const COLLISION_SKIN: f32 = 0.001;
+
+/// Region new particles appear in, in the local space of the emitter's `Transform2D`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum EmissionShape {
+    #[default]
+    Point,
+    /// Anywhere inside the circle
+    Circle {
+        radius: f32,
+    },
+    /// On the edge of the circle
+    Ring {
+        radius: f32,
+    },
+    Rectangle {
+        half_extents: Vec2,
+    },
+    /// Segment along the local x axis
+    Line {
+        half_length: f32,
+    },
+}
+
+impl EmissionShape {
+    fn sample(&self, rng: &mut SmallRng) -> Point2 {
+        match *self {
+            EmissionShape::Point => point2(0.0, 0.0),
+            EmissionShape::Circle { radius } => {
+                let offset = vec2(radius * rng.random::<f32>().sqrt(), 0.0);
+                let v = rotate_vec2(offset, rad(rng.random::<f32>() * TAU));
+                point2(v.x, v.y)
+            }
+            EmissionShape::Ring { radius } => {
+                let v = rotate_vec2(vec2(radius, 0.0), rad(rng.random::<f32>() * TAU));
+                point2(v.x, v.y)
+            }
+            EmissionShape::Rectangle { half_extents } => point2(
+                half_extents.x * rng.random_range(-1.0..=1.0),
+                half_extents.y * rng.random_range(-1.0..=1.0),
+            ),
+            EmissionShape::Line { half_length } => {
+                point2(half_length * rng.random_range(-1.0..=1.0), 0.0)
+            }
+        }
+    }
+}
+
+/// Particles emitted all at once, `time` seconds after the emitter started
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Burst {
+    pub time: f32,
+    pub count: usize,
+    /// Repeats the burst every `interval` seconds when set
+    pub interval: Option<f32>,
+}
+
+impl Burst {
+    pub fn new(time: f32, count: usize) -> Self {
+        Self {
+            time,
+            count,
+            interval: None,
+        }
+    }
+
+    pub fn repeating(time: f32, count: usize, interval: f32) -> Self {
+        Self {
+            time,
+            count,
+            interval: Some(interval),
+        }
+    }
+
+    /// Times the burst goes off within `[from, to)`
+    fn occurrences(&self, from: f32, to: f32) -> usize {
+        match self.interval {
+            Some(interval) if interval > EPSILON => {
+                let first = ((from - self.time) / interval).ceil().max(0.0);
+                let end = ((to - self.time) / interval).ceil().max(0.0);
+                (end - first).max(0.0) as usize
+            }
+            _ => usize::from(from <= self.time && self.time < to),
+        }
+    }
+}
+
+/// How particles bounce off solid colliders
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParticleCollision {
+    /// Layers of the colliders particles hit
+    pub mask: u32,
+    /// Fraction of the velocity into the surface kept after a hit, bounced back
+    pub bounce: f32,
+    /// Fraction of the velocity along the surface lost on each hit
+    pub friction: f32,
+}
+
+impl ParticleCollision {
+    pub fn new(bounce: f32, friction: f32) -> Self {
+        Self {
+            mask: ALL_LAYERS,
+            bounce,
+            friction,
+        }
+    }
+
+    pub fn with_mask(mut self, mask: u32) -> Self {
+        self.mask = mask;
+        self
+    }
+}
+
+/// Spawns and simulates particles for visual effects. Particles are simulated in world space
+/// by the `ParticleSystem` and stored in the emitter's `ParticlePool`, ready to be drawn.
+/// Emitters need a `Transform2D`, the particles they spawn don't follow it afterwards.
+#[derive(Debug, Component, Clone)]
+#[storage(VecStorage)]
+pub struct ParticleEmitter {
+    /// Spawns particles continuously and from bursts while set
+    pub emitting: bool,
+    /// Particles spawned per second
+    pub rate: f32,
+    pub bursts: Vec<Burst>,
+    pub shape: EmissionShape,
+    /// Local direction particles are launched in
+    pub direction: Vec2,
+    /// Random deviation from the direction on either side, in radians
+    pub spread: f32,
+    /// Launch speed in units per second
+    pub speed: Interval<f32>,
+    /// Seconds a particle lives
+    pub lifetime: Interval<f32>,
+    /// Spin in radians per second
+    pub angular_velocity: Interval<f32>,
+    /// Multiplier of the velocity over the lifetime
+    pub velocity_scale: Curve<f32>,
+    pub size: Curve<f32>,
+    pub color: Curve<Color>,
+    /// Multiplier applied to the `Gravity` resource
+    pub gravity_scale: f32,
+    /// Air resistance, each frame multiplies the particle velocities by `1 / (1 + dt * drag)`
+    pub drag: f32,
+    pub collision: Option<ParticleCollision>,
+    /// Live particles never exceed this count, spawns past it are dropped
+    pub max_particles: usize,
+    particles: ParticlePool,
+    /// Seconds since the emitter started
+    time: f32,
+    /// Fraction of a particle carried over by the continuous emission
+    accumulator: f32,
+    /// Particles requested with `emit`
+    pending: usize,
+    rng: SmallRng,
+}
+
+impl Default for ParticleEmitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ParticleEmitter {
+    /// Create an emitter spawning 10 white particles per second, moving up at 1 unit per
+    /// second for 1 second
+    pub fn new() -> Self {
+        Self {
+            emitting: true,
+            rate: 10.0,
+            bursts: Vec::new(),
+            shape: EmissionShape::Point,
+            direction: vec2(0.0, 1.0),
+            spread: 0.0,
+            speed: Interval::new(1.0, 1.0),
+            lifetime: Interval::new(1.0, 1.0),
+            angular_velocity: Interval::new(0.0, 0.0),
+            velocity_scale: Curve::constant(1.0),
+            size: Curve::constant(1.0),
+            color: Curve::constant(Color::WHITE),
+            gravity_scale: 0.0,
+            drag: 0.0,
+            collision: None,
+            max_particles: 1000,
+            particles: ParticlePool::default(),
+            time: 0.0,
+            accumulator: 0.0,
+            pending: 0,
+            rng: SmallRng::from_rng(&mut rand::rng()),
+        }
+    }
+
+    pub fn with_rate(mut self, rate: f32) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    pub fn with_burst(mut self, burst: Burst) -> Self {
+        self.bursts.push(burst);
+        self
+    }
+
+    pub fn with_shape(mut self, shape: EmissionShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Launch direction and the random deviation from it on either side, in radians
+    pub fn with_direction(mut self, direction: Vec2, spread: f32) -> Self {
+        self.direction = direction;
+        self.spread = spread;
+        self
+    }
+
+    pub fn with_speed(mut self, min: f32, max: f32) -> Self {
+        self.speed = Interval::new(min, max);
+        self
+    }
+
+    pub fn with_lifetime(mut self, min: f32, max: f32) -> Self {
+        self.lifetime = Interval::new(min, max);
+        self
+    }
+
+    pub fn with_angular_velocity(mut self, min: f32, max: f32) -> Self {
+        self.angular_velocity = Interval::new(min, max);
+        self
+    }
+
+    pub fn with_velocity_scale(mut self, curve: Curve<f32>) -> Self {
+        self.velocity_scale = curve;
+        self
+    }
+
+    pub fn with_size(mut self, curve: Curve<f32>) -> Self {
+        self.size = curve;
+        self
+    }
+
+    pub fn with_color(mut self, curve: Curve<Color>) -> Self {
+        self.color = curve;
+        self
+    }
+
+    pub fn with_gravity_scale(mut self, scale: f32) -> Self {
+        self.gravity_scale = scale;
+        self
+    }
+
+    pub fn with_drag(mut self, drag: f32) -> Self {
+        self.drag = drag;
+        self
+    }
+
+    pub fn with_collision(mut self, collision: ParticleCollision) -> Self {
+        self.collision = Some(collision);
+        self
+    }
+
+    pub fn with_max_particles(mut self, max: usize) -> Self {
+        self.max_particles = max;
+        self
+    }
+
+    /// Makes the random spawns repeatable
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = SmallRng::seed_from_u64(seed);
+        self
+    }
+
+    pub fn particles(&self) -> &ParticlePool {
+        &self.particles
+    }
+
+    /// Spawns `count` particles on the next update, even if the emitter is stopped
+    pub fn emit(&mut self, count: usize) {
+        self.pending += count;
+    }
+
+    /// Starts emitting again, bursts restart from the beginning
+    pub fn play(&mut self) {
+        self.emitting = true;
+        self.time = 0.0;
+        self.accumulator = 0.0;
+    }
+
+    /// Stops spawning, live particles finish their lifetime
+    pub fn stop(&mut self) {
+        self.emitting = false;
+    }
+
+    /// Removes every live particle
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    /// Seconds since the emitter started
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Returns true while the emitter spawns particles or some are still alive
+    pub fn is_active(&self) -> bool {
+        self.emitting || self.pending > 0 || !self.particles.is_empty()
+    }
+
+    /// Ages and moves the live particles, then spawns the new ones
+    pub(crate) fn update(
+        &mut self,
+        entity: Entity,
+        transform: &Transform2D,
+        gravity: Vec2,
+        collision_world: &CollisionWorld,
+        dt: f32,
+    ) {
+        self.particles.age(dt);
+        self.simulate(entity, gravity, collision_world, dt);
+
+        let mut count = std::mem::take(&mut self.pending);
+        if self.emitting {
+            self.accumulator += self.rate.max(0.0) * dt;
+            let continuous = self.accumulator.floor();
+            self.accumulator -= continuous;
+            count += continuous as usize;
+
+            let (from, to) = (self.time, self.time + dt);
+            count += self
+                .bursts
+                .iter()
+                .map(|burst| burst.count * burst.occurrences(from, to))
+                .sum::<usize>();
+            self.time = to;
+        }
+
+        let count = count.min(self.max_particles.saturating_sub(self.particles.len()));
+        for _ in 0..count {
+            let spawn = self.spawn(transform);
+            self.particles.push(spawn);
+        }
+    }
+
+    fn spawn(&mut self, transform: &Transform2D) -> Spawn {
+        let rng = &mut self.rng;
+        let position = transform.transform_point(self.shape.sample(rng));
+        let deviation = rng.random_range(-1.0..=1.0) * self.spread;
+        let direction = rotate_vec2(self.direction, rad(transform.rotation + deviation));
+        let speed = self.speed.lerp(rng.random());
+
+        Spawn {
+            position,
+            velocity: if direction.magnitude2() > EPSILON {
+                direction.normalize() * speed
+            } else {
+                vec2(0.0, 0.0)
+            },
+            rotation: transform.rotation,
+            angular_velocity: self.angular_velocity.lerp(rng.random()),
+            size: self.size.sample(0.0),
+            color: self.color.sample(0.0),
+            lifetime: self.lifetime.lerp(rng.random()).max(EPSILON),
+        }
+    }
+
+    /// Moves the particles, bouncing them off the colliders other than the emitter's own
+    fn simulate(
+        &mut self,
+        entity: Entity,
+        gravity: Vec2,
+        collision_world: &CollisionWorld,
+        dt: f32,
+    ) {
+        let acceleration = gravity * self.gravity_scale;
+        let keep = 1.0 / (1.0 + dt * self.drag);
+        let state = self.particles.state_mut();
+
+        for i in 0..state.positions.len() {
+            let t = (state.ages[i] / state.lifetimes[i]).min(1.0);
+            let velocity = &mut state.velocities[i];
+            *velocity = (*velocity + acceleration * dt) * keep;
+
+            let travel = *velocity * self.velocity_scale.sample(t) * dt;
+            let position = &mut state.positions[i];
+            match self.collision {
+                Some(collision) => collide(
+                    position,
+                    velocity,
+                    travel,
+                    &collision,
+                    collision_world,
+                    entity,
+                ),
+                None => *position += travel,
+            }
+
+            state.rotations[i] += state.angular_velocities[i] * dt;
+            state.sizes[i] = self.size.sample(t);
+            state.colors[i] = self.color.sample(t);
+        }
+    }
+}
+
+/// Moves a particle along `travel`, stopping at the first surface hit and bouncing off it
+fn collide(
+    position: &mut Point2,
+    velocity: &mut Vec2,
+    travel: Vec2,
+    collision: &ParticleCollision,
+    collision_world: &CollisionWorld,
+    emitter: Entity,
+) {
+    let length = travel.magnitude();
+    if length <= EPSILON {
+        return;
+    }
+
+    let ray = Ray2D::new(*position, travel);
+    let end = *position + travel;
+    let swept = Aabb::from_points([*position, end]);
+    let mut hit: Option<(f32, Vec2)> = None;
+    collision_world.query(&swept, |o| {
+        if o.entity == emitter || o.is_trigger || o.layer & collision.mask == 0 {
+            return;
+        }
+        if let Some((distance, normal)) = query::raycast_shape(&o.shape, &ray, length)
+            && hit.is_none_or(|(closest, _)| distance < closest)
+        {
+            hit = Some((distance, normal));
+        }
+    });
+
+    let Some((distance, normal)) = hit else {
+        *position = end;
+        return;
+    };
+
+    *position += ray.direction() * distance + normal * COLLISION_SKIN;
+    let into = velocity.dot(normal);
+    if into < 0.0 {
+        let along = (*velocity - normal * into) * (1.0 - collision.friction).max(0.0);
+        *velocity = along - normal * into * collision.bounce;
+    }
+}
+
+/// Updates every `ParticleEmitter` with the frame delta time.
+/// Particles collide with the colliders of the `CollisionWorld`, as of the last fixed step.
+pub struct ParticleSystem;
+
+impl<'a> System<'a> for ParticleSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        Read<'a, Gravity>,
+        Read<'a, CollisionWorld>,
+        ReadStorage<'a, Transform2D>,
+        WriteStorage<'a, ParticleEmitter>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, time, gravity, collision_world, transforms, mut emitters): Self::SystemData,
+    ) {
+        for (entity, transform, emitter) in (&entities, &transforms, &mut emitters).join() {
+            emitter.update(
+                entity,
+                transform,
+                gravity.0,
+                &collision_world,
+                time.delta_time,
+            );
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use crate::arith::{Point2, Vec2};
+
+use super::curve::Color;
+
+/// Live particles of an emitter stored as parallel arrays, one entry per particle at the same
+/// index in each. Dead particles are swapped out so the arrays stay packed and can be uploaded
+/// to a renderer as they are. Particle order is not preserved.
+#[derive(Debug, Clone, Default)]
+pub struct ParticlePool {
+    positions: Vec<Point2>,
+    velocities: Vec<Vec2>,
+    rotations: Vec<f32>,
+    angular_velocities: Vec<f32>,
+    sizes: Vec<f32>,
+    colors: Vec<Color>,
+    ages: Vec<f32>,
+    lifetimes: Vec<f32>,
+}
+
+/// Initial state of a new particle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Spawn {
+    pub position: Point2,
+    pub velocity: Vec2,
+    pub rotation: f32,
+    pub angular_velocity: f32,
+    pub size: f32,
+    pub color: Color,
+    pub lifetime: f32,
+}
+
+impl ParticlePool {
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// World positions
+    pub fn positions(&self) -> &[Point2] {
+        &self.positions
+    }
+
+    /// Velocities in units per second
+    pub fn velocities(&self) -> &[Vec2] {
+        &self.velocities
+    }
+
+    /// Rotations in radians
+    pub fn rotations(&self) -> &[f32] {
+        &self.rotations
+    }
+
+    pub fn sizes(&self) -> &[f32] {
+        &self.sizes
+    }
+
+    pub fn colors(&self) -> &[Color] {
+        &self.colors
+    }
+
+    /// Seconds since each particle was spawned
+    pub fn ages(&self) -> &[f32] {
+        &self.ages
+    }
+
+    /// Seconds each particle lives in total
+    pub fn lifetimes(&self) -> &[f32] {
+        &self.lifetimes
+    }
+
+    /// Age of a particle relative to its lifetime, from 0 to 1
+    pub fn normalized_age(&self, index: usize) -> f32 {
+        (self.ages[index] / self.lifetimes[index]).min(1.0)
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub(crate) fn push(&mut self, spawn: Spawn) {
+        self.positions.push(spawn.position);
+        self.velocities.push(spawn.velocity);
+        self.rotations.push(spawn.rotation);
+        self.angular_velocities.push(spawn.angular_velocity);
+        self.sizes.push(spawn.size);
+        self.colors.push(spawn.color);
+        self.ages.push(0.0);
+        self.lifetimes.push(spawn.lifetime);
+    }
+
+    /// Ages every particle by `dt` and removes the ones past their lifetime
+    pub(crate) fn age(&mut self, dt: f32) {
+        let mut i = 0;
+        while i < self.len() {
+            self.ages[i] += dt;
+            if self.ages[i] >= self.lifetimes[i] {
+                self.swap_remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    fn swap_remove(&mut self, index: usize) {
+        self.positions.swap_remove(index);
+        self.velocities.swap_remove(index);
+        self.rotations.swap_remove(index);
+        self.angular_velocities.swap_remove(index);
+        self.sizes.swap_remove(index);
+        self.colors.swap_remove(index);
+        self.ages.swap_remove(index);
+        self.lifetimes.swap_remove(index);
+    }
+
+    /// Mutable access to the simulated arrays at once
+    pub(crate) fn state_mut(&mut self) -> PoolState<'_> {
+        PoolState {
+            positions: &mut self.positions,
+            velocities: &mut self.velocities,
+            rotations: &mut self.rotations,
+            angular_velocities: &self.angular_velocities,
+            sizes: &mut self.sizes,
+            colors: &mut self.colors,
+            ages: &self.ages,
+            lifetimes: &self.lifetimes,
+        }
+    }
+}
+
+pub(crate) struct PoolState<'a> {
+    pub positions: &'a mut [Point2],
+    pub velocities: &'a mut [Vec2],
+    pub rotations: &'a mut [f32],
+    pub angular_velocities: &'a [f32],
+    pub sizes: &'a mut [f32],
+    pub colors: &'a mut [Color],
+    pub ages: &'a [f32],
+    pub lifetimes: &'a [f32],
+}
@@ -0,0 +1,295 @@
+use cgmath::{InnerSpace, MetricSpace};
+use specs::{Builder, Entity, RunNow, System, World, WorldExt};
+
+use crate::arith::{point2, vec2};
+use crate::collision::shapes::AxisAlignedBox;
+use crate::collision::{Collider, CollisionSystem};
+use crate::components::Transform2D;
+use crate::physics::Gravity;
+use crate::time::Time;
+
+use super::curve::{Color, Curve};
+use super::{Burst, EmissionShape, ParticleCollision, ParticleEmitter, ParticleSystem};
+
+const DT: f32 = 0.1;
+
+fn particle_world() -> World {
+    let mut world = World::new();
+    System::setup(&mut ParticleSystem, &mut world);
+    System::setup(&mut CollisionSystem, &mut world);
+    let mut time = Time::new();
+    time.delta_time = DT;
+    world.insert(time);
+    world.insert(Gravity(vec2(0.0, -10.0)));
+    world
+}
+
+fn add_emitter(world: &mut World, x: f32, y: f32, emitter: ParticleEmitter) -> Entity {
+    world
+        .create_entity()
+        .with(Transform2D {
+            position: point2(x, y),
+            ..Default::default()
+        })
+        .with(emitter.with_seed(7))
+        .build()
+}
+
+fn step(world: &mut World, frames: usize) {
+    for _ in 0..frames {
+        ParticleSystem.run_now(world);
+        world.maintain();
+    }
+}
+
+fn emitter(world: &World, entity: Entity) -> ParticleEmitter {
+    world
+        .read_storage::<ParticleEmitter>()
+        .get(entity)
+        .unwrap()
+        .clone()
+}
+
+#[test]
+fn test_curve_sampling() {
+    let curve = Curve::linear(0.0, 1.0).with_key(0.5, 4.0);
+    assert_eq!(curve.sample(-1.0), 0.0);
+    assert_eq!(curve.sample(0.25), 2.0);
+    assert_eq!(curve.sample(0.5), 4.0);
+    assert_eq!(curve.sample(0.75), 2.5);
+    assert_eq!(curve.sample(2.0), 1.0);
+    assert_eq!(Curve::constant(3.0).sample(0.7), 3.0);
+
+    let fade = Curve::linear(Color::WHITE, Color::TRANSPARENT);
+    assert_eq!(fade.sample(0.5), Color::rgba(1.0, 1.0, 1.0, 0.5));
+}
+
+#[test]
+fn test_emission_rate_and_bursts() {
+    let mut world = particle_world();
+    let steady = add_emitter(
+        &mut world,
+        0.0,
+        0.0,
+        ParticleEmitter::new()
+            .with_rate(25.0)
+            .with_lifetime(10.0, 10.0),
+    );
+    let bursting = add_emitter(
+        &mut world,
+        0.0,
+        0.0,
+        ParticleEmitter::new()
+            .with_rate(0.0)
+            .with_lifetime(10.0, 10.0)
+            .with_burst(Burst::new(0.0, 5))
+            .with_burst(Burst::repeating(0.25, 3, 0.5)),
+    );
+
+    step(&mut world, 10);
+
+    // 2.5 particles a frame, the fractions carry over
+    assert_eq!(emitter(&world, steady).particles().len(), 25);
+    // Repeats at 0.25, 0.75
+    assert_eq!(emitter(&world, bursting).particles().len(), 5 + 2 * 3);
+
+    world
+        .write_storage::<ParticleEmitter>()
+        .get_mut(bursting)
+        .unwrap()
+        .stop();
+    world
+        .write_storage::<ParticleEmitter>()
+        .get_mut(bursting)
+        .unwrap()
+        .emit(4);
+    step(&mut world, 10);
+    assert_eq!(emitter(&world, bursting).particles().len(), 5 + 2 * 3 + 4);
+}
+
+#[test]
+fn test_particles_die_and_pool_stays_packed() {
+    let mut world = particle_world();
+    let entity = add_emitter(
+        &mut world,
+        0.0,
+        0.0,
+        ParticleEmitter::new()
+            .with_rate(0.0)
+            .with_lifetime(0.25, 0.55)
+            .with_burst(Burst::new(0.0, 100))
+            .with_max_particles(60),
+    );
+
+    step(&mut world, 1);
+    assert_eq!(emitter(&world, entity).particles().len(), 60);
+
+    step(&mut world, 3);
+    let particles = emitter(&world, entity);
+    let pool = particles.particles();
+    assert!(!pool.is_empty() && pool.len() < 60);
+    assert_eq!(pool.positions().len(), pool.len());
+    assert_eq!(pool.colors().len(), pool.len());
+    assert!(pool.ages().iter().zip(pool.lifetimes()).all(|(a, l)| a < l));
+
+    step(&mut world, 3);
+    let particles = emitter(&world, entity);
+    assert!(particles.particles().is_empty());
+}
+
+#[test]
+fn test_spawn_shapes_follow_the_transform() {
+    let mut world = particle_world();
+    let circle = add_emitter(
+        &mut world,
+        10.0,
+        5.0,
+        ParticleEmitter::new()
+            .with_rate(0.0)
+            .with_speed(0.0, 0.0)
+            .with_shape(EmissionShape::Circle { radius: 2.0 })
+            .with_burst(Burst::new(0.0, 200)),
+    );
+    let ring = add_emitter(
+        &mut world,
+        -10.0,
+        0.0,
+        ParticleEmitter::new()
+            .with_rate(0.0)
+            .with_speed(0.0, 0.0)
+            .with_shape(EmissionShape::Ring { radius: 3.0 })
+            .with_burst(Burst::new(0.0, 200)),
+    );
+    let rectangle = add_emitter(
+        &mut world,
+        0.0,
+        0.0,
+        ParticleEmitter::new()
+            .with_rate(0.0)
+            .with_speed(0.0, 0.0)
+            .with_shape(EmissionShape::Rectangle {
+                half_extents: vec2(4.0, 1.0),
+            })
+            .with_burst(Burst::new(0.0, 200)),
+    );
+    world
+        .write_storage::<Transform2D>()
+        .get_mut(rectangle)
+        .unwrap()
+        .rotation = std::f32::consts::FRAC_PI_2;
+
+    step(&mut world, 1);
+
+    let circle = emitter(&world, circle);
+    let positions = circle.particles().positions();
+    assert!(
+        positions
+            .iter()
+            .all(|p| p.distance(point2(10.0, 5.0)) <= 2.0 + 1e-4)
+    );
+    assert!(
+        positions
+            .iter()
+            .any(|p| p.distance(point2(10.0, 5.0)) > 1.5)
+    );
+
+    let ring = emitter(&world, ring);
+    let positions = ring.particles().positions();
+    assert!(
+        positions
+            .iter()
+            .all(|p| (p.distance(point2(-10.0, 0.0)) - 3.0).abs() < 1e-4)
+    );
+
+    // Rotated a quarter turn, the rectangle stands upright
+    let rectangle = emitter(&world, rectangle);
+    let positions = rectangle.particles().positions();
+    assert!(
+        positions
+            .iter()
+            .all(|p| p.x.abs() <= 1.0 + 1e-4 && p.y.abs() <= 4.0 + 1e-4)
+    );
+    assert!(positions.iter().any(|p| p.y.abs() > 2.0));
+}
+
+#[test]
+fn test_particle_motion_and_curves() {
+    let mut world = particle_world();
+    let entity = add_emitter(
+        &mut world,
+        0.0,
+        0.0,
+        ParticleEmitter::new()
+            .with_rate(0.0)
+            .with_burst(Burst::new(0.0, 1))
+            .with_direction(vec2(1.0, 0.0), 0.0)
+            .with_speed(2.0, 2.0)
+            .with_lifetime(2.0, 2.0)
+            .with_angular_velocity(1.0, 1.0)
+            .with_gravity_scale(0.5)
+            .with_size(Curve::linear(1.0, 0.0))
+            .with_color(Curve::linear(Color::WHITE, Color::TRANSPARENT)),
+    );
+
+    step(&mut world, 1);
+    let particles = emitter(&world, entity);
+    let pool = particles.particles();
+    assert_eq!(pool.positions()[0], point2(0.0, 0.0));
+    assert_eq!(pool.velocities()[0], vec2(2.0, 0.0));
+    assert_eq!(pool.sizes()[0], 1.0);
+
+    step(&mut world, 10);
+    let particles = emitter(&world, entity);
+    let pool = particles.particles();
+    let velocity = pool.velocities()[0];
+    assert!((velocity - vec2(2.0, -5.0)).magnitude() < 1e-4);
+    assert!((pool.positions()[0].x - 2.0).abs() < 1e-4);
+    assert!(pool.positions()[0].y < -2.0);
+    assert!((pool.rotations()[0] - 1.0).abs() < 1e-4);
+    assert!((pool.sizes()[0] - 0.5).abs() < 1e-4);
+    assert!((pool.colors()[0].a - 0.5).abs() < 1e-4);
+}
+
+#[test]
+fn test_particles_bounce_off_colliders() {
+    let mut world = particle_world();
+    world
+        .create_entity()
+        .with(Transform2D {
+            position: point2(0.0, -1.5),
+            ..Default::default()
+        })
+        .with(Collider::new(AxisAlignedBox::new(vec2(10.0, 0.5))))
+        .build();
+    CollisionSystem.run_now(&world);
+
+    let emitter_with = |collision| {
+        let emitter = ParticleEmitter::new()
+            .with_rate(0.0)
+            .with_burst(Burst::new(0.0, 1))
+            .with_direction(vec2(0.0, -1.0), 0.0)
+            .with_speed(5.0, 5.0)
+            .with_lifetime(10.0, 10.0);
+        match collision {
+            Some(collision) => emitter.with_collision(collision),
+            None => emitter,
+        }
+    };
+    let bouncing = add_emitter(
+        &mut world,
+        0.0,
+        0.0,
+        emitter_with(Some(ParticleCollision::new(0.5, 0.0))),
+    );
+    let ghost = add_emitter(&mut world, 5.0, 0.0, emitter_with(None));
+
+    step(&mut world, 4);
+
+    let bouncing = emitter(&world, bouncing);
+    let pool = bouncing.particles();
+    assert!(pool.positions()[0].y >= -1.0);
+    assert!(pool.velocities()[0].y > 0.0);
+
+    let ghost = emitter(&world, ghost);
+    assert!(ghost.particles().positions()[0].y < -1.0);
+}
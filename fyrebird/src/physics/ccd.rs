@@ -0,0 +1,99 @@
+use std::collections::HashSet;
+
+use cgmath::InnerSpace;
+use specs::Entity;
+
+use crate::arith::{EPSILON, Vec2};
+use crate::collision::Collider;
+use crate::collision::broad_phase::CollisionWorld;
+use crate::collision::distance::{self, CastHit};
+use crate::collision::obstacle::Obstacle;
+use crate::collision::shapes::{Aabb, WorldShape};
+use crate::components::Transform2D;
+
+use super::integrate_position;
+use super::material::PhysicsMaterials;
+use super::rigid_body::RigidBody2D;
+use super::solver::SolverSettings;
+
+/// Times a fast body may hit static geometry within a single step
+const MAX_SUB_STEPS: usize = 4;
+
+/// Static colliders continuous bodies are swept against
+pub(crate) struct StaticObstacles<'a> {
+    pub collision_world: &'a CollisionWorld,
+    /// Entities of the bodies that aren't static, left to the contact solver
+    pub moving: &'a HashSet<Entity>,
+    pub materials: &'a PhysicsMaterials,
+}
+
+/// Second half of the step for bodies using continuous collision detection. The collider is
+/// swept along the velocity against the obstacles, the body is advanced to the time of impact,
+/// loses the velocity going into the surface and spends the rest of the step sliding or bouncing.
+/// Rotation is ignored by the sweep. Bodies too slow to pass through anything move as usual.
+pub(crate) fn integrate_swept(
+    body: &mut RigidBody2D,
+    transform: &mut Transform2D,
+    collider: &Collider,
+    obstacles: &StaticObstacles,
+    settings: &SolverSettings,
+    dt: f32,
+) {
+    let material = obstacles.materials.get(collider.material);
+    let mut remaining = dt;
+
+    for _ in 0..MAX_SUB_STEPS {
+        let moving = collider.world_shape(transform);
+        let translation = body.linear_velocity * remaining;
+
+        // Moving less than half its size, the discrete solver can't lose the body
+        let start = moving.aabb();
+        let half = start.half_extents();
+        if translation.magnitude() <= half.x.min(half.y) {
+            break;
+        }
+
+        let swept = start.union(&Aabb::new(start.min + translation, start.max + translation));
+        let Some((hit, obstacle)) = first_hit(&moving, translation, collider, &swept, obstacles)
+        else {
+            break;
+        };
+
+        let approach = body.linear_velocity.dot(hit.normal);
+        if approach >= -EPSILON {
+            break;
+        }
+
+        let time = hit.fraction * remaining;
+        integrate_position(body, transform, time);
+        remaining -= time;
+
+        let restitution = material.combined_restitution(obstacles.materials.get(obstacle.material));
+        let bounce = if approach < -settings.restitution_threshold {
+            restitution
+        } else {
+            0.0
+        };
+        body.linear_velocity -= hit.normal * approach * (1.0 + bounce);
+    }
+
+    integrate_position(body, transform, remaining);
+}
+
+/// Earliest obstacle touched by `moving` along `translation`
+fn first_hit<'o>(
+    moving: &WorldShape,
+    translation: Vec2,
+    collider: &Collider,
+    swept: &Aabb,
+    obstacles: &StaticObstacles<'o>,
+) -> Option<(CastHit, &'o Obstacle)> {
+    obstacles
+        .collision_world
+        .obstacles(swept)
+        .into_iter()
+        .filter(|o| !o.is_trigger && !obstacles.moving.contains(&o.entity))
+        .filter(|o| o.accepts(collider.layer, collider.mask))
+        .filter_map(|o| distance::cast(moving, translation, &o.shape).map(|hit| (hit, o)))
+        .min_by(|(a, _), (b, _)| a.fraction.total_cmp(&b.fraction))
+}
@@ -0,0 +1,405 @@
+use std::f32::consts::FRAC_PI_4;
+
+use cgmath::InnerSpace;
+use specs::{
+    Component, Entities, Entity, Join, Read, ReadStorage, System, VecStorage, WriteStorage,
+};
+use specs_derive::Component;
+
+use crate::arith::{EPSILON, Vec2, rad, rotate_vec2, vec2};
+use crate::collision::Collider;
+use crate::collision::query::{PhysicsQuery, QueryFilter, QueryHit};
+use crate::components::Transform2D;
+use crate::time::Time;
+
+/// Obstacles a character can slide along within a single step
+const MAX_SLIDES: usize = 4;
+/// Attempts at pushing a character out of overlapping obstacles
+const MAX_DEPENETRATIONS: usize = 4;
+
+/// Moves an entity with a `Collider` by sliding it along obstacles rather than simulating it.
+/// Set `velocity` before every step, the `CharacterControllerSystem` then moves the transform
+/// and removes the parts of the velocity blocked by obstacles.
+#[derive(Debug, Component, Clone)]
+#[storage(VecStorage)]
+pub struct CharacterController2D {
+    /// Velocity in units per second, gravity included
+    pub velocity: Vec2,
+    /// Unit vector pointing away from the floor. Zero for top-down movement,
+    /// where every obstacle is a wall.
+    pub up: Vec2,
+    /// Steepest slope in radians that still counts as floor
+    pub max_slope: f32,
+    /// Highest ledge climbed while walking on the floor
+    pub step_height: f32,
+    /// Distance the character is pulled down to stay on the floor when walking down slopes
+    pub snap_distance: f32,
+    /// Gap left between the collider and the obstacles it runs into
+    pub skin_width: f32,
+    /// Seconds `is_grounded` stays true after walking off a ledge
+    pub coyote_time: f32,
+    on_floor: bool,
+    on_ceiling: bool,
+    on_wall: bool,
+    floor: Option<(Entity, Vec2)>,
+    wall_normal: Option<Vec2>,
+    /// Seconds since the character last stood on the floor
+    air_time: f32,
+    /// Floor entity and its pose at the end of the last step, to carry the character along
+    platform: Option<(Entity, Transform2D)>,
+}
+
+impl Default for CharacterController2D {
+    fn default() -> Self {
+        Self {
+            velocity: vec2(0.0, 0.0),
+            up: vec2(0.0, 1.0),
+            max_slope: FRAC_PI_4,
+            step_height: 0.25,
+            snap_distance: 0.1,
+            skin_width: 0.01,
+            coyote_time: 0.1,
+            on_floor: false,
+            on_ceiling: false,
+            on_wall: false,
+            floor: None,
+            wall_normal: None,
+            air_time: f32::INFINITY,
+            platform: None,
+        }
+    }
+}
+
+impl CharacterController2D {
+    /// Platformer controller, with the floor below
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Controller without floor, for top-down movement
+    pub fn top_down() -> Self {
+        Self {
+            up: vec2(0.0, 0.0),
+            step_height: 0.0,
+            snap_distance: 0.0,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_up(mut self, up: Vec2) -> Self {
+        self.up = up;
+        self
+    }
+
+    /// Sets the steepest walkable slope, in radians
+    pub fn with_max_slope(mut self, max_slope: f32) -> Self {
+        self.max_slope = max_slope;
+        self
+    }
+
+    pub fn with_step_height(mut self, step_height: f32) -> Self {
+        self.step_height = step_height;
+        self
+    }
+
+    pub fn with_snap_distance(mut self, snap_distance: f32) -> Self {
+        self.snap_distance = snap_distance;
+        self
+    }
+
+    pub fn with_coyote_time(mut self, coyote_time: f32) -> Self {
+        self.coyote_time = coyote_time;
+        self
+    }
+
+    /// Standing on the floor at the end of the last step
+    pub fn is_on_floor(&self) -> bool {
+        self.on_floor
+    }
+
+    /// Bumped into a ceiling during the last step
+    pub fn is_on_ceiling(&self) -> bool {
+        self.on_ceiling
+    }
+
+    /// Bumped into a wall or a slope too steep to walk on during the last step
+    pub fn is_on_wall(&self) -> bool {
+        self.on_wall
+    }
+
+    /// Normal of the floor the character stands on
+    pub fn floor_normal(&self) -> Option<Vec2> {
+        self.floor.map(|(_, normal)| normal)
+    }
+
+    /// Entity the character stands on
+    pub fn floor_entity(&self) -> Option<Entity> {
+        self.floor.map(|(entity, _)| entity)
+    }
+
+    /// Normal of the last wall bumped into
+    pub fn wall_normal(&self) -> Option<Vec2> {
+        self.wall_normal
+    }
+
+    /// On the floor, or left it less than `coyote_time` ago without jumping.
+    /// Use it to decide whether the character may jump.
+    pub fn is_grounded(&self) -> bool {
+        self.on_floor || self.air_time <= self.coyote_time
+    }
+
+    /// Ends the coyote time, call it when the character jumps
+    pub fn leave_ground(&mut self) {
+        self.on_floor = false;
+        self.air_time = f32::INFINITY;
+    }
+
+    fn has_floor(&self) -> bool {
+        self.up.magnitude2() > EPSILON
+    }
+
+    fn is_floor(&self, normal: Vec2) -> bool {
+        self.has_floor() && normal.dot(self.up) >= self.max_slope.cos() - EPSILON
+    }
+
+    fn is_ceiling(&self, normal: Vec2) -> bool {
+        self.has_floor() && -normal.dot(self.up) >= self.max_slope.cos() - EPSILON
+    }
+
+    fn land(&mut self, hit: &QueryHit) {
+        self.on_floor = true;
+        self.floor = Some((hit.entity, hit.normal));
+
+        let falling = self.velocity.dot(self.up);
+        if falling < 0.0 {
+            self.velocity -= self.up * falling;
+        }
+    }
+
+    /// Records a wall or ceiling hit and stops the velocity going into `normal`
+    fn bump(&mut self, hit: &QueryHit, normal: Vec2) {
+        if self.is_ceiling(hit.normal) {
+            self.on_ceiling = true;
+        } else {
+            self.on_wall = true;
+            self.wall_normal = Some(hit.normal);
+        }
+
+        let into = self.velocity.dot(normal);
+        if into < 0.0 {
+            self.velocity -= normal * into;
+        }
+    }
+
+    /// Moves the character for one step
+    fn move_and_slide(&mut self, mover: &Mover, transform: &mut Transform2D, dt: f32) {
+        let was_on_floor = self.on_floor;
+        self.on_floor = false;
+        self.on_ceiling = false;
+        self.on_wall = false;
+        self.floor = None;
+        self.wall_normal = None;
+
+        mover.depenetrate(transform, self.skin_width);
+
+        let mut motion = self.velocity * dt;
+        for _ in 0..MAX_SLIDES {
+            let length = motion.magnitude();
+            if length < EPSILON {
+                break;
+            }
+
+            let Some(hit) = mover.cast(transform, motion) else {
+                transform.position += motion;
+                break;
+            };
+
+            let direction = motion / length;
+            let travel = (hit.distance - self.skin_width).max(0.0);
+            transform.position += direction * travel;
+            let mut rest = direction * (length - travel);
+
+            let mut normal = hit.normal;
+            if self.is_floor(hit.normal) {
+                // The part going into the floor is gravity, only keep walking along it
+                self.land(&hit);
+                rest -= self.up * rest.dot(self.up);
+            } else {
+                let walking = was_on_floor || self.on_floor;
+                if walking
+                    && !self.is_ceiling(hit.normal)
+                    && let Some(rest) = self.step_up(mover, transform, rest)
+                {
+                    motion = rest;
+                    continue;
+                }
+
+                // Steep slopes block walking characters like vertical walls, instead of lifting them
+                let flat = hit.normal - self.up * hit.normal.dot(self.up);
+                if walking && !self.is_ceiling(hit.normal) && flat.magnitude2() > EPSILON {
+                    normal = flat.normalize();
+                }
+                self.bump(&hit, normal);
+            }
+
+            let into = rest.dot(normal);
+            if into < 0.0 {
+                rest -= normal * into;
+            }
+            motion = rest;
+        }
+
+        // Look for the floor right below, further when it was just under the character
+        if self.has_floor() && !self.on_floor && self.velocity.dot(self.up) <= EPSILON {
+            let reach = if was_on_floor {
+                self.snap_distance.max(self.skin_width * 2.0)
+            } else {
+                self.skin_width * 2.0
+            };
+
+            if let Some(hit) = mover.cast(transform, -self.up * reach)
+                && self.is_floor(hit.normal)
+            {
+                transform.position -= self.up * (hit.distance - self.skin_width).max(0.0);
+                self.land(&hit);
+            }
+        }
+
+        if self.on_floor {
+            self.air_time = 0.0;
+        } else {
+            self.air_time += dt;
+        }
+    }
+
+    /// Climbs over a ledge blocking the `rest` of the motion: up by `step_height`, forward,
+    /// then down onto the floor. Returns the motion left, or `None` if there is no floor to land on.
+    fn step_up(&mut self, mover: &Mover, transform: &mut Transform2D, rest: Vec2) -> Option<Vec2> {
+        if !self.has_floor() || self.step_height <= 0.0 {
+            return None;
+        }
+
+        let forward = rest - self.up * rest.dot(self.up);
+        let length = forward.magnitude();
+        if length < EPSILON {
+            return None;
+        }
+
+        let mut probe = *transform;
+        let raise = mover.travel(&probe, self.up * self.step_height, self.skin_width);
+        probe.position += self.up * raise;
+
+        let advance = mover.travel(&probe, forward, self.skin_width);
+        if advance < EPSILON {
+            return None;
+        }
+        probe.position += forward / length * advance;
+
+        let hit = mover.cast(&probe, -self.up * (raise + self.skin_width))?;
+        if !self.is_floor(hit.normal) {
+            return None;
+        }
+        probe.position -= self.up * (hit.distance - self.skin_width).max(0.0);
+
+        *transform = probe;
+        self.land(&hit);
+        Some(forward / length * (length - advance))
+    }
+}
+
+/// Character collider and the obstacles it collides with during the step
+struct Mover<'a, 'q> {
+    collider: &'a Collider,
+    query: &'a PhysicsQuery<'q>,
+    filter: QueryFilter,
+}
+
+impl Mover<'_, '_> {
+    /// First obstacle hit when moving by `translation`
+    fn cast(&self, transform: &Transform2D, translation: Vec2) -> Option<QueryHit> {
+        self.query.sweep(
+            &self.collider.world_shape(transform),
+            translation,
+            self.filter,
+        )
+    }
+
+    /// Distance the character can move along `translation` while keeping `skin` away from obstacles
+    fn travel(&self, transform: &Transform2D, translation: Vec2, skin: f32) -> f32 {
+        match self.cast(transform, translation) {
+            Some(hit) => (hit.distance - skin).max(0.0),
+            None => translation.magnitude(),
+        }
+    }
+
+    /// Pushes the character out of the obstacles it overlaps
+    fn depenetrate(&self, transform: &mut Transform2D, skin: f32) {
+        for _ in 0..MAX_DEPENETRATIONS {
+            let shape = self.collider.world_shape(transform);
+            let deepest = self
+                .query
+                .overlap_manifolds(&shape, self.filter)
+                .into_iter()
+                .map(|(_, manifold)| manifold)
+                .filter(|manifold| manifold.penetration() > 0.0)
+                .max_by(|a, b| a.penetration().total_cmp(&b.penetration()));
+
+            let Some(manifold) = deepest else {
+                return;
+            };
+            transform.position -= manifold.normal * (manifold.penetration() + skin);
+        }
+    }
+}
+
+/// Moves every entity with a `CharacterController2D`, a `Collider` and a `Transform2D`.
+/// Characters stand on any solid collider and are carried along when it moves.
+/// They push nothing, dynamic bodies act as obstacles like everything else.
+/// Obstacles are found with the `PhysicsQuery`, as they were at the end of the last step:
+/// colliders added since are not seen yet.
+pub struct CharacterControllerSystem;
+
+impl<'a> System<'a> for CharacterControllerSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        PhysicsQuery<'a>,
+        ReadStorage<'a, Collider>,
+        WriteStorage<'a, Transform2D>,
+        WriteStorage<'a, CharacterController2D>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, time, query, colliders, mut transforms, mut controllers): Self::SystemData,
+    ) {
+        let dt = time.fixed_timestep;
+
+        for (entity, collider, controller) in (&entities, &colliders, &mut controllers).join() {
+            let platform = controller
+                .platform
+                .and_then(|(floor, before)| transforms.get(floor).map(|now| (before, *now)));
+            let Some(transform) = transforms.get_mut(entity) else {
+                continue;
+            };
+
+            // Follow the floor, as it may have moved or turned since the last step
+            if let Some((before, now)) = platform {
+                let offset = transform.position - before.position;
+                let turn = now.rotation - before.rotation;
+                transform.position = now.position + rotate_vec2(offset, rad(turn));
+            }
+
+            let mover = Mover {
+                collider,
+                query: &query,
+                filter: QueryFilter::for_collider(entity, collider),
+            };
+            controller.move_and_slide(&mover, transform, dt);
+
+            controller.platform = controller
+                .floor_entity()
+                .and_then(|floor| transforms.get(floor).map(|pose| (floor, *pose)));
+        }
+    }
+}
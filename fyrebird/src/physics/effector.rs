@@ -0,0 +1,286 @@
+use std::f32::consts::TAU;
+
+use cgmath::{EuclideanSpace, InnerSpace};
+use specs::{Component, VecStorage};
+use specs_derive::Component;
+
+use crate::arith::{EPSILON, Point2, Vec2, cross_vec2, point2, rad, rotate_vec2, vec2};
+use crate::collision::shapes::WorldShape;
+
+use super::rigid_body::RigidBody2D;
+
+/// Vertices used to approximate round shapes when computing submerged areas
+const ROUND_SEGMENTS: usize = 16;
+
+/// How the strength of a radial effector changes with the distance to its origin
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Falloff {
+    #[default]
+    Constant,
+    /// Fades out linearly, reaching 0 at `range`
+    Linear { range: f32 },
+    /// Divided by the squared distance past 1 unit, constant closer in
+    InverseSquare,
+}
+
+impl Falloff {
+    fn scale(self, distance: f32) -> f32 {
+        match self {
+            Falloff::Constant => 1.0,
+            Falloff::Linear { range } if range > EPSILON => (1.0 - distance / range).max(0.0),
+            Falloff::Linear { .. } => 0.0,
+            Falloff::InverseSquare => 1.0 / distance.max(1.0).powi(2),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EffectorKind {
+    /// Acceleration scaled by the `gravity_scale` of each body, replacing the `Gravity`
+    /// resource for them unless `replace_global` is unset
+    Gravity {
+        acceleration: Vec2,
+        replace_global: bool,
+    },
+    /// Acceleration towards the effector origin, away from it when `strength` is negative
+    Radial { strength: f32, falloff: Falloff },
+    /// Pulls the velocity of bodies towards the wind `velocity`. `turbulence` from 0 to 1
+    /// varies its strength and direction over time, `frequency` times per second on average.
+    Wind {
+        velocity: Vec2,
+        drag: f32,
+        turbulence: f32,
+        frequency: f32,
+    },
+    /// Fluid pushing bodies against gravity with the weight of the fluid they displace,
+    /// and slowing their submerged part down relative to the `flow` velocity
+    Buoyancy {
+        density: f32,
+        linear_drag: f32,
+        angular_drag: f32,
+        flow: Vec2,
+    },
+}
+
+/// Applies forces to the dynamic bodies overlapping the entity's `Collider`, usually a trigger.
+/// Processed by the `Physics2DSystem`. Sleeping bodies are woken up as soon as the effector
+/// pushes them, so the bodies it acts on stay awake.
+#[derive(Debug, Component, Clone)]
+#[storage(VecStorage)]
+pub struct AreaEffector2D {
+    pub kind: EffectorKind,
+    /// Simulated seconds since the effector was created, drives the turbulence
+    time: f32,
+}
+
+impl AreaEffector2D {
+    pub fn new(kind: EffectorKind) -> Self {
+        Self { kind, time: 0.0 }
+    }
+
+    /// Gravity zone replacing the global gravity
+    pub fn gravity(acceleration: Vec2) -> Self {
+        Self::new(EffectorKind::Gravity {
+            acceleration,
+            replace_global: true,
+        })
+    }
+
+    pub fn radial(strength: f32, falloff: Falloff) -> Self {
+        Self::new(EffectorKind::Radial { strength, falloff })
+    }
+
+    pub fn wind(velocity: Vec2, drag: f32) -> Self {
+        Self::new(EffectorKind::Wind {
+            velocity,
+            drag,
+            turbulence: 0.0,
+            frequency: 1.0,
+        })
+    }
+
+    /// Water-like fluid of the given density
+    pub fn buoyancy(density: f32, linear_drag: f32, angular_drag: f32) -> Self {
+        Self::new(EffectorKind::Buoyancy {
+            density,
+            linear_drag,
+            angular_drag,
+            flow: vec2(0.0, 0.0),
+        })
+    }
+
+    /// Makes the wind gusty
+    pub fn with_turbulence(mut self, amount: f32, frequency_hz: f32) -> Self {
+        if let EffectorKind::Wind {
+            turbulence,
+            frequency,
+            ..
+        } = &mut self.kind
+        {
+            *turbulence = amount;
+            *frequency = frequency_hz;
+        }
+        self
+    }
+
+    /// Sets the velocity of the fluid current
+    pub fn with_flow(mut self, velocity: Vec2) -> Self {
+        if let EffectorKind::Buoyancy { flow, .. } = &mut self.kind {
+            *flow = velocity;
+        }
+        self
+    }
+
+    /// Returns true if the effector replaces the global gravity of the bodies inside it
+    pub fn replaces_gravity(&self) -> bool {
+        matches!(
+            self.kind,
+            EffectorKind::Gravity {
+                replace_global: true,
+                ..
+            }
+        )
+    }
+
+    pub(crate) fn advance(&mut self, dt: f32) {
+        self.time += dt;
+    }
+
+    /// Applies the effect to a body centered at `center`. `area` and `origin` belong to the
+    /// effector, `shape` is the body's collider and `gravity` the global gravity.
+    pub(crate) fn apply(
+        &self,
+        area: &WorldShape,
+        origin: Point2,
+        body: &mut RigidBody2D,
+        center: Point2,
+        shape: &WorldShape,
+        gravity: Vec2,
+    ) {
+        match self.kind {
+            EffectorKind::Gravity { acceleration, .. } => {
+                body.apply_force(acceleration * body.gravity_scale * body.mass());
+            }
+            EffectorKind::Radial { strength, falloff } => {
+                let offset = origin - center;
+                let distance = offset.magnitude();
+                if distance > EPSILON {
+                    let acceleration = strength * falloff.scale(distance);
+                    body.apply_force(offset / distance * acceleration * body.mass());
+                }
+            }
+            EffectorKind::Wind {
+                velocity,
+                drag,
+                turbulence,
+                frequency,
+            } => {
+                let wind = gust(velocity, turbulence, frequency, self.time, center);
+                body.apply_force((wind - body.linear_velocity) * drag);
+            }
+            EffectorKind::Buoyancy {
+                density,
+                linear_drag,
+                angular_drag,
+                flow,
+            } => {
+                let submerged = clip(&outline(shape), &outline(area));
+                let Some((submerged_area, centroid)) = area_centroid(&submerged) else {
+                    return;
+                };
+
+                let buoyancy = -gravity * density * submerged_area;
+                let current = flow - body.velocity_at_point(centroid, center);
+                let drag = current * linear_drag * submerged_area;
+                body.apply_force_at_point(buoyancy + drag, centroid, center);
+                body.apply_torque(-body.angular_velocity * angular_drag * submerged_area);
+            }
+        }
+    }
+}
+
+/// Wind velocity varied by smooth pseudo random noise over time and space
+fn gust(velocity: Vec2, turbulence: f32, frequency: f32, time: f32, at: Point2) -> Vec2 {
+    if turbulence <= 0.0 {
+        return velocity;
+    }
+
+    let phase = time * frequency * TAU + at.x * 0.37 + at.y * 0.71;
+    let strength = 1.0 + turbulence * noise(phase);
+    let angle = turbulence * noise(phase * 0.61 + 11.3) * 0.5;
+    rotate_vec2(velocity, rad(angle)) * strength
+}
+
+/// Smooth noise in `[-1, 1]` from a sum of incommensurate sines
+fn noise(x: f32) -> f32 {
+    (x.sin() + 0.5 * (x * 2.31 + 1.7).sin() + 0.25 * (x * 5.13 + 4.2).sin()) / 1.75
+}
+
+/// Counter-clockwise convex outline of a shape, round parts approximated by segments
+fn outline(shape: &WorldShape) -> Vec<Point2> {
+    let arc = |center: Point2, radius: f32, from: f32, segments: usize| {
+        (0..=segments).map(move |i| {
+            let angle = from + i as f32 / segments as f32 * TAU / 2.0;
+            center + vec2(angle.cos(), angle.sin()) * radius
+        })
+    };
+
+    match shape {
+        WorldShape::Circle { center, radius } => (0..ROUND_SEGMENTS)
+            .map(|i| {
+                let angle = i as f32 / ROUND_SEGMENTS as f32 * TAU;
+                center + vec2(angle.cos(), angle.sin()) * *radius
+            })
+            .collect(),
+        WorldShape::Capsule { a, b, radius } => {
+            let axis = b - a;
+            let start = axis.y.atan2(axis.x) - TAU / 4.0;
+            let half = ROUND_SEGMENTS / 2;
+            arc(*b, *radius, start, half)
+                .chain(arc(*a, *radius, start + TAU / 2.0, half))
+                .collect()
+        }
+        WorldShape::Polygon(polygon) => polygon.vertices.clone(),
+    }
+}
+
+/// Part of the convex `subject` inside the counter-clockwise convex `clipper` (Sutherland-Hodgman)
+fn clip(subject: &[Point2], clipper: &[Point2]) -> Vec<Point2> {
+    let mut output = subject.to_vec();
+
+    for i in 0..clipper.len() {
+        let (a, b) = (clipper[i], clipper[(i + 1) % clipper.len()]);
+        let edge = b - a;
+        let inside = |p: Point2| cross_vec2(edge, p - a) >= 0.0;
+
+        let input = std::mem::take(&mut output);
+        for j in 0..input.len() {
+            let (p, q) = (input[j], input[(j + 1) % input.len()]);
+            if inside(p) {
+                output.push(p);
+            }
+            if inside(p) != inside(q) {
+                let (dp, dq) = (cross_vec2(edge, p - a), cross_vec2(edge, q - a));
+                output.push(p + (q - p) * (dp / (dp - dq)));
+            }
+        }
+    }
+
+    output
+}
+
+/// Area and centroid of a polygon, `None` if it has no area
+fn area_centroid(vertices: &[Point2]) -> Option<(f32, Point2)> {
+    let n = vertices.len();
+    let (area, moment) = (0..n).fold((0.0, vec2(0.0, 0.0)), |(area, moment), i| {
+        let (a, b) = (vertices[i].to_vec(), vertices[(i + 1) % n].to_vec());
+        let cross = cross_vec2(a, b);
+        (area + cross * 0.5, moment + (a + b) * cross / 6.0)
+    });
+
+    if area.abs() > EPSILON {
+        Some((area.abs(), point2(moment.x / area, moment.y / area)))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::{HashMap, HashSet};
+
+use specs::Entity;
+use specs::storage::GenericWriteStorage;
+
+use super::rigid_body::RigidBody2D;
+
+/// Resource tuning when resting bodies fall asleep
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SleepSettings {
+    pub enabled: bool,
+    /// Speed under which a body counts as resting, in units per second
+    pub linear_threshold: f32,
+    /// Angular speed under which a body counts as resting, in radians per second
+    pub angular_threshold: f32,
+    /// Seconds every body of an island must rest before the island falls asleep
+    pub time_to_sleep: f32,
+}
+
+impl Default for SleepSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            linear_threshold: 0.01,
+            angular_threshold: 0.035,
+            time_to_sleep: 0.5,
+        }
+    }
+}
+
+/// Resource with counters updated by every physics step
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhysicsStats {
+    /// Dynamic and kinematic bodies simulated during the last step
+    pub awake_bodies: usize,
+    pub sleeping_bodies: usize,
+    pub islands: usize,
+}
+
+/// Groups of dynamic bodies linked by contacts or joints. Static and kinematic bodies
+/// never link islands together, so a floor doesn't merge everything resting on it.
+#[derive(Debug, Default)]
+pub struct Islands {
+    islands: Vec<Vec<Entity>>,
+    index: HashMap<Entity, usize>,
+}
+
+impl Islands {
+    /// Builds the islands of `bodies`. Links with an entity outside of `bodies` are ignored.
+    pub fn build(
+        bodies: impl IntoIterator<Item = Entity>,
+        links: impl IntoIterator<Item = (Entity, Entity)>,
+    ) -> Self {
+        let bodies: Vec<_> = bodies.into_iter().collect();
+        let nodes: HashMap<_, _> = bodies.iter().enumerate().map(|(i, &e)| (e, i)).collect();
+        let mut sets = DisjointSets::new(bodies.len());
+
+        for (a, b) in links {
+            if let (Some(&a), Some(&b)) = (nodes.get(&a), nodes.get(&b)) {
+                sets.union(a, b);
+            }
+        }
+
+        let mut islands = Islands::default();
+        let mut roots = HashMap::new();
+        for (i, &entity) in bodies.iter().enumerate() {
+            let island = *roots.entry(sets.find(i)).or_insert_with(|| {
+                islands.islands.push(Vec::new());
+                islands.islands.len() - 1
+            });
+            islands.islands[island].push(entity);
+            islands.index.insert(entity, island);
+        }
+
+        islands
+    }
+
+    pub fn len(&self) -> usize {
+        self.islands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.islands.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &[Entity]> {
+        self.islands.iter().map(Vec::as_slice)
+    }
+
+    /// Every body in the same island as `entity`, itself included
+    pub fn island_of(&self, entity: Entity) -> Option<&[Entity]> {
+        self.index.get(&entity).map(|&i| self.islands[i].as_slice())
+    }
+}
+
+/// Union-find with path halving and union by size
+struct DisjointSets {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl DisjointSets {
+    fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+            size: vec![1; len],
+        }
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+    }
+}
+
+impl Islands {
+    /// Wakes every island holding an awake body, or touching one of `wakers`
+    pub(crate) fn wake<B>(
+        &self,
+        links: &[(Entity, Entity)],
+        wakers: &HashSet<Entity>,
+        bodies: &mut B,
+    ) where
+        B: GenericWriteStorage<Component = RigidBody2D>,
+    {
+        let touched: HashSet<_> = links
+            .iter()
+            .filter_map(|&(a, b)| {
+                if wakers.contains(&a) {
+                    Some(b)
+                } else if wakers.contains(&b) {
+                    Some(a)
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        for island in self.iter() {
+            let awake = island.iter().any(|&entity| {
+                touched.contains(&entity)
+                    || bodies
+                        .get_mut(entity)
+                        .is_some_and(|body| !body.is_sleeping())
+            });
+
+            if awake {
+                for &entity in island {
+                    if let Some(body) = bodies.get_mut(entity)
+                        && body.is_sleeping()
+                    {
+                        body.wake_up();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Puts to sleep every island whose bodies all rested for long enough
+    pub(crate) fn sleep<B>(&self, time_to_sleep: f32, bodies: &mut B)
+    where
+        B: GenericWriteStorage<Component = RigidBody2D>,
+    {
+        for island in self.iter() {
+            let resting = island.iter().all(|&entity| {
+                bodies
+                    .get_mut(entity)
+                    .is_none_or(|body| body.is_sleeping() || body.sleep_time() >= time_to_sleep)
+            });
+
+            if resting {
+                for &entity in island {
+                    if let Some(body) = bodies.get_mut(entity) {
+                        body.sleep();
+                    }
+                }
+            }
+        }
+    }
+}
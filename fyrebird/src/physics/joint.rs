@@ -0,0 +1,713 @@
+use cgmath::InnerSpace;
+use specs::storage::{GenericReadStorage, GenericWriteStorage};
+use specs::{Component, Entity, VecStorage};
+use specs_derive::Component;
+
+use crate::arith::{EPSILON, Point2, Vec2, cross_vec2, perp_vec2, rad, rotate_vec2, vec2};
+use crate::components::Transform2D;
+
+use super::rigid_body::RigidBody2D;
+use super::solver::{BodySet, SolverBody, SolverSettings};
+
+/// Motor driving a joint towards a target speed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JointMotor {
+    /// Radians per second for revolute joints, units per second for prismatic joints
+    pub speed: f32,
+    /// Largest torque (revolute) or force (prismatic) the motor can apply
+    pub max_force: f32,
+}
+
+/// Range of the relative angle (revolute) or translation (prismatic) allowed by a joint
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JointLimits {
+    pub lower: f32,
+    pub upper: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JointKind {
+    /// Keeps the anchors between `min_length` and `max_length` apart.
+    /// Equal lengths make a rigid rod, a minimum of 0 makes a rope.
+    Distance { min_length: f32, max_length: f32 },
+    /// Pins the anchors together, letting the bodies rotate around them
+    Revolute {
+        reference_angle: f32,
+        limits: Option<JointLimits>,
+        motor: Option<JointMotor>,
+    },
+    /// Lets body B slide along an axis fixed in body A, without relative rotation
+    Prismatic {
+        /// Unit axis in body A's local space
+        axis: Vec2,
+        reference_angle: f32,
+        limits: Option<JointLimits>,
+        motor: Option<JointMotor>,
+    },
+    /// Glues both bodies together
+    Weld { reference_angle: f32 },
+    /// Pulls the anchors towards `rest_length` with a force of `stiffness` per unit of
+    /// stretch, and `damping` per unit of stretching speed. Solved implicitly so stiff
+    /// springs stay stable.
+    Spring {
+        rest_length: f32,
+        stiffness: f32,
+        damping: f32,
+    },
+    /// Drags anchor B towards a world point with a bounded force, body A is ignored.
+    /// `stiffness` is the fraction of the distance to the target recovered per step.
+    MouseDrag {
+        target: Point2,
+        max_force: f32,
+        stiffness: f32,
+    },
+}
+
+/// Accumulated impulses of a joint, kept across steps for warm starting
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct JointImpulses {
+    point: Vec2,
+    perpendicular: f32,
+    angular: f32,
+    motor: f32,
+    limit: f32,
+    spring: f32,
+}
+
+impl Default for JointImpulses {
+    fn default() -> Self {
+        Self {
+            point: vec2(0.0, 0.0),
+            perpendicular: 0.0,
+            angular: 0.0,
+            motor: 0.0,
+            limit: 0.0,
+            spring: 0.0,
+        }
+    }
+}
+
+/// Constraint between two `RigidBody2D` entities, usually on an entity of its own.
+/// Anchors are offsets from each body's origin that rotate with the body.
+#[derive(Debug, Component, Clone)]
+#[storage(VecStorage)]
+pub struct Joint2D {
+    pub entity_a: Entity,
+    pub entity_b: Entity,
+    pub local_anchor_a: Vec2,
+    pub local_anchor_b: Vec2,
+    pub kind: JointKind,
+    /// Lets the colliders of both bodies collide with each other
+    pub collide_connected: bool,
+    impulses: JointImpulses,
+}
+
+impl Joint2D {
+    pub fn new(entity_a: Entity, entity_b: Entity, kind: JointKind) -> Self {
+        Self {
+            entity_a,
+            entity_b,
+            local_anchor_a: vec2(0.0, 0.0),
+            local_anchor_b: vec2(0.0, 0.0),
+            kind,
+            collide_connected: false,
+            impulses: JointImpulses::default(),
+        }
+    }
+
+    /// Rigid rod keeping the anchors `length` apart
+    pub fn distance(entity_a: Entity, entity_b: Entity, length: f32) -> Self {
+        Self::new(
+            entity_a,
+            entity_b,
+            JointKind::Distance {
+                min_length: length,
+                max_length: length,
+            },
+        )
+    }
+
+    /// Rope keeping the anchors at most `max_length` apart
+    pub fn rope(entity_a: Entity, entity_b: Entity, max_length: f32) -> Self {
+        Self::new(
+            entity_a,
+            entity_b,
+            JointKind::Distance {
+                min_length: 0.0,
+                max_length,
+            },
+        )
+    }
+
+    pub fn revolute(entity_a: Entity, entity_b: Entity) -> Self {
+        Self::new(
+            entity_a,
+            entity_b,
+            JointKind::Revolute {
+                reference_angle: 0.0,
+                limits: None,
+                motor: None,
+            },
+        )
+    }
+
+    /// Slider along `axis`, given in body A's local space
+    pub fn prismatic(entity_a: Entity, entity_b: Entity, axis: Vec2) -> Self {
+        Self::new(
+            entity_a,
+            entity_b,
+            JointKind::Prismatic {
+                axis: axis.normalize(),
+                reference_angle: 0.0,
+                limits: None,
+                motor: None,
+            },
+        )
+    }
+
+    pub fn weld(entity_a: Entity, entity_b: Entity) -> Self {
+        Self::new(
+            entity_a,
+            entity_b,
+            JointKind::Weld {
+                reference_angle: 0.0,
+            },
+        )
+    }
+
+    pub fn spring(
+        entity_a: Entity,
+        entity_b: Entity,
+        rest_length: f32,
+        stiffness: f32,
+        damping: f32,
+    ) -> Self {
+        Self::new(
+            entity_a,
+            entity_b,
+            JointKind::Spring {
+                rest_length,
+                stiffness,
+                damping,
+            },
+        )
+    }
+
+    /// Drags `entity` towards `target`. Use `set_target` to move the target afterwards.
+    pub fn mouse_drag(entity: Entity, target: Point2, max_force: f32) -> Self {
+        Self::new(
+            entity,
+            entity,
+            JointKind::MouseDrag {
+                target,
+                max_force,
+                stiffness: 0.2,
+            },
+        )
+    }
+
+    pub fn with_anchors(mut self, local_anchor_a: Vec2, local_anchor_b: Vec2) -> Self {
+        self.local_anchor_a = local_anchor_a;
+        self.local_anchor_b = local_anchor_b;
+        self
+    }
+
+    /// Sets the relative angle of body B to body A the joint holds, or measures limits from
+    pub fn with_reference_angle(mut self, angle: f32) -> Self {
+        if let JointKind::Revolute {
+            reference_angle, ..
+        }
+        | JointKind::Prismatic {
+            reference_angle, ..
+        }
+        | JointKind::Weld { reference_angle } = &mut self.kind
+        {
+            *reference_angle = angle;
+        }
+        self
+    }
+
+    /// Limits the angle of revolute joints or the translation of prismatic joints
+    pub fn with_limits(mut self, lower: f32, upper: f32) -> Self {
+        if let JointKind::Revolute { limits, .. } | JointKind::Prismatic { limits, .. } =
+            &mut self.kind
+        {
+            *limits = Some(JointLimits { lower, upper });
+        }
+        self
+    }
+
+    /// Drives revolute or prismatic joints at `speed`
+    pub fn with_motor(mut self, speed: f32, max_force: f32) -> Self {
+        if let JointKind::Revolute { motor, .. } | JointKind::Prismatic { motor, .. } =
+            &mut self.kind
+        {
+            *motor = Some(JointMotor { speed, max_force });
+        }
+        self
+    }
+
+    pub fn with_collide_connected(mut self) -> Self {
+        self.collide_connected = true;
+        self
+    }
+
+    /// Moves the target of a mouse drag joint
+    pub fn set_target(&mut self, point: Point2) {
+        if let JointKind::MouseDrag { target, .. } = &mut self.kind {
+            *target = point;
+        }
+    }
+}
+
+/// Linear and angular terms of a one dimensional constraint. Body A receives the opposite
+/// of the linear term and the opposite of its angular term.
+#[derive(Debug, Clone, Copy)]
+struct Jacobian {
+    linear: Vec2,
+    angular_a: f32,
+    angular_b: f32,
+}
+
+impl Jacobian {
+    fn angular() -> Self {
+        Self {
+            linear: vec2(0.0, 0.0),
+            angular_a: 1.0,
+            angular_b: 1.0,
+        }
+    }
+
+    fn velocity(&self, a: &SolverBody, b: &SolverBody) -> f32 {
+        self.linear.dot(b.linear_velocity - a.linear_velocity) + self.angular_b * b.angular_velocity
+            - self.angular_a * a.angular_velocity
+    }
+
+    fn inv_mass(&self, a: &SolverBody, b: &SolverBody) -> f32 {
+        (a.inv_mass + b.inv_mass) * self.linear.magnitude2()
+            + a.inv_inertia * self.angular_a * self.angular_a
+            + b.inv_inertia * self.angular_b * self.angular_b
+    }
+
+    fn mass(&self, a: &SolverBody, b: &SolverBody) -> f32 {
+        let k = self.inv_mass(a, b);
+        if k > 0.0 { 1.0 / k } else { 0.0 }
+    }
+
+    fn apply(&self, set: &mut BodySet, a: usize, b: usize, impulse: f32) {
+        let body_a = set.get_mut(a);
+        body_a.linear_velocity -= self.linear * (body_a.inv_mass * impulse);
+        body_a.apply_angular_impulse(-self.angular_a * impulse);
+
+        let body_b = set.get_mut(b);
+        body_b.linear_velocity += self.linear * (body_b.inv_mass * impulse);
+        body_b.apply_angular_impulse(self.angular_b * impulse);
+    }
+}
+
+/// Which side of a limit is being enforced this step
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LimitState {
+    Inactive,
+    /// Error below the lower bound, the impulse can only push up
+    Lower(f32),
+    /// Error above the upper bound, the impulse can only push down
+    Upper(f32),
+    /// Both bounds are equal, the impulse is unbounded
+    Equal(f32),
+}
+
+impl LimitState {
+    fn new(value: f32, lower: f32, upper: f32, slop: f32) -> Self {
+        if upper - lower < 2.0 * slop {
+            LimitState::Equal(value - lower)
+        } else if value <= lower {
+            LimitState::Lower(value - lower)
+        } else if value >= upper {
+            LimitState::Upper(value - upper)
+        } else {
+            LimitState::Inactive
+        }
+    }
+
+    /// Accumulates `impulse` into `accumulated` within the bounds of the active side
+    fn clamp(&self, accumulated: f32, impulse: f32) -> f32 {
+        match self {
+            LimitState::Inactive => 0.0,
+            LimitState::Lower(_) => (accumulated + impulse).max(0.0),
+            LimitState::Upper(_) => (accumulated + impulse).min(0.0),
+            LimitState::Equal(_) => accumulated + impulse,
+        }
+    }
+
+    fn error(&self) -> f32 {
+        match self {
+            LimitState::Inactive => 0.0,
+            LimitState::Lower(e) | LimitState::Upper(e) | LimitState::Equal(e) => *e,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct JointConstraint {
+    joint: Entity,
+    kind: JointKind,
+    a: usize,
+    b: usize,
+    ra: Vec2,
+    rb: Vec2,
+    /// Position error of the point constraint, from anchor A to anchor B
+    point_error: Vec2,
+    /// Relative angle of B to A minus the reference angle
+    angle: f32,
+    /// Degree of freedom driven by motors and bounded by limits
+    drive: Option<Jacobian>,
+    /// Constraint perpendicular to the axis, for prismatic joints
+    perpendicular: Option<Jacobian>,
+    perpendicular_error: f32,
+    limit: LimitState,
+    /// Soft constraint terms of springs: compliance and position bias
+    spring_gamma: f32,
+    spring_bias: f32,
+    impulses: JointImpulses,
+}
+
+/// Solves the `Joint2D` constraints in the same iterations as the contacts
+#[derive(Debug, Default)]
+pub(crate) struct JointSolver {
+    constraints: Vec<JointConstraint>,
+    baumgarte: f32,
+    dt: f32,
+}
+
+impl JointSolver {
+    pub fn prepare<'j, T, B>(
+        &mut self,
+        joints: impl Iterator<Item = (Entity, &'j Joint2D)>,
+        settings: &SolverSettings,
+        transforms: &T,
+        bodies: &mut B,
+        set: &mut BodySet,
+        dt: f32,
+    ) where
+        T: GenericReadStorage<Component = Transform2D>,
+        B: GenericWriteStorage<Component = RigidBody2D>,
+    {
+        self.constraints.clear();
+        self.baumgarte = settings.baumgarte;
+        self.dt = dt;
+
+        for (entity, joint) in joints {
+            let b = match set.index(joint.entity_b, transforms, bodies) {
+                Some(b) => b,
+                None => continue,
+            };
+            let a = if let JointKind::MouseDrag { .. } = joint.kind {
+                set.ground()
+            } else {
+                match set.index(joint.entity_a, transforms, bodies) {
+                    Some(a) => a,
+                    None => continue,
+                }
+            };
+
+            let (body_a, body_b) = (*set.get(a), *set.get(b));
+            let mut ra = rotate_vec2(joint.local_anchor_a, rad(body_a.angle));
+            let rb = rotate_vec2(joint.local_anchor_b, rad(body_b.angle));
+            let mut anchor_a = body_a.center + ra;
+            let anchor_b = body_b.center + rb;
+            let angle = body_b.angle - body_a.angle;
+
+            let mut constraint = JointConstraint {
+                joint: entity,
+                kind: joint.kind,
+                a,
+                b,
+                ra,
+                rb,
+                point_error: anchor_b - anchor_a,
+                angle,
+                drive: None,
+                perpendicular: None,
+                perpendicular_error: 0.0,
+                limit: LimitState::Inactive,
+                spring_gamma: 0.0,
+                spring_bias: 0.0,
+                impulses: if settings.warm_starting {
+                    joint.impulses
+                } else {
+                    JointImpulses::default()
+                },
+            };
+
+            match joint.kind {
+                JointKind::Distance {
+                    min_length,
+                    max_length,
+                } => {
+                    let d = anchor_b - anchor_a;
+                    let length = d.magnitude();
+                    if length < EPSILON {
+                        continue;
+                    }
+                    let u = d / length;
+                    constraint.drive = Some(Jacobian {
+                        linear: u,
+                        angular_a: cross_vec2(ra, u),
+                        angular_b: cross_vec2(rb, u),
+                    });
+                    constraint.limit =
+                        LimitState::new(length, min_length, max_length, settings.linear_slop);
+                }
+                JointKind::Revolute {
+                    reference_angle,
+                    limits,
+                    ..
+                } => {
+                    constraint.angle -= reference_angle;
+                    constraint.drive = Some(Jacobian::angular());
+                    if let Some(limits) = limits {
+                        constraint.limit =
+                            LimitState::new(constraint.angle, limits.lower, limits.upper, 0.0);
+                    }
+                }
+                JointKind::Prismatic {
+                    axis,
+                    reference_angle,
+                    limits,
+                    ..
+                } => {
+                    constraint.angle -= reference_angle;
+                    let d = anchor_b - anchor_a;
+                    let axis = rotate_vec2(axis, rad(body_a.angle));
+                    let perp = perp_vec2(axis);
+
+                    constraint.drive = Some(Jacobian {
+                        linear: axis,
+                        angular_a: cross_vec2(d + ra, axis),
+                        angular_b: cross_vec2(rb, axis),
+                    });
+                    constraint.perpendicular = Some(Jacobian {
+                        linear: perp,
+                        angular_a: cross_vec2(d + ra, perp),
+                        angular_b: cross_vec2(rb, perp),
+                    });
+                    constraint.perpendicular_error = perp.dot(d);
+                    if let Some(limits) = limits {
+                        constraint.limit = LimitState::new(
+                            axis.dot(d),
+                            limits.lower,
+                            limits.upper,
+                            settings.linear_slop,
+                        );
+                    }
+                }
+                JointKind::Weld { reference_angle } => {
+                    constraint.angle -= reference_angle;
+                }
+                JointKind::Spring {
+                    rest_length,
+                    stiffness,
+                    damping,
+                } => {
+                    let d = anchor_b - anchor_a;
+                    let length = d.magnitude();
+                    let compliance = dt * (damping + dt * stiffness);
+                    if length < EPSILON || compliance <= 0.0 {
+                        continue;
+                    }
+                    let u = d / length;
+                    constraint.drive = Some(Jacobian {
+                        linear: u,
+                        angular_a: cross_vec2(ra, u),
+                        angular_b: cross_vec2(rb, u),
+                    });
+                    constraint.spring_gamma = 1.0 / compliance;
+                    constraint.spring_bias =
+                        (length - rest_length) * dt * stiffness * constraint.spring_gamma;
+                }
+                JointKind::MouseDrag { target, .. } => {
+                    ra = vec2(0.0, 0.0);
+                    anchor_a = target;
+                    constraint.ra = ra;
+                    constraint.point_error = anchor_b - anchor_a;
+                }
+            }
+
+            if constraint.limit == LimitState::Inactive {
+                constraint.impulses.limit = 0.0;
+            }
+            if !matches!(
+                joint.kind,
+                JointKind::Revolute { motor: Some(_), .. }
+                    | JointKind::Prismatic { motor: Some(_), .. }
+            ) {
+                constraint.impulses.motor = 0.0;
+            }
+            self.constraints.push(constraint);
+        }
+    }
+
+    /// Applies the impulses carried over from the previous step
+    pub fn warm_start(&self, set: &mut BodySet) {
+        for c in &self.constraints {
+            let impulses = c.impulses;
+            set.get_mut(c.a).apply_impulse(-impulses.point, c.ra);
+            set.get_mut(c.b).apply_impulse(impulses.point, c.rb);
+            Jacobian::angular().apply(set, c.a, c.b, impulses.angular);
+
+            if let Some(drive) = c.drive {
+                let impulse = impulses.motor + impulses.limit + impulses.spring;
+                drive.apply(set, c.a, c.b, impulse);
+            }
+            if let Some(perpendicular) = c.perpendicular {
+                perpendicular.apply(set, c.a, c.b, impulses.perpendicular);
+            }
+        }
+    }
+
+    /// One Gauss-Seidel pass over every joint
+    pub fn solve_velocities(&mut self, set: &mut BodySet) {
+        let bias = self.baumgarte / self.dt;
+        let dt = self.dt;
+
+        for c in &mut self.constraints {
+            match c.kind {
+                JointKind::Distance { .. } => solve_limit(c, set, bias),
+                JointKind::Revolute { motor, .. } => {
+                    if let Some(motor) = motor {
+                        solve_motor(c, motor, set, dt);
+                    }
+                    solve_limit(c, set, bias);
+                    solve_point(c, set, bias, None);
+                }
+                JointKind::Prismatic { motor, .. } => {
+                    if let Some(motor) = motor {
+                        solve_motor(c, motor, set, dt);
+                    }
+                    solve_limit(c, set, bias);
+                    solve_perpendicular(c, set, bias);
+                    solve_angle(c, set, bias);
+                }
+                JointKind::Weld { .. } => {
+                    solve_angle(c, set, bias);
+                    solve_point(c, set, bias, None);
+                }
+                JointKind::MouseDrag {
+                    max_force,
+                    stiffness,
+                    ..
+                } => solve_point(c, set, stiffness / dt, Some(max_force * dt)),
+                JointKind::Spring { .. } => solve_spring(c, set),
+            }
+        }
+    }
+
+    /// Keeps the accumulated impulses in the joints to warm start the next step
+    pub fn store_impulses<J>(&self, joints: &mut J)
+    where
+        J: GenericWriteStorage<Component = Joint2D>,
+    {
+        for c in &self.constraints {
+            if let Some(joint) = joints.get_mut(c.joint) {
+                joint.impulses = c.impulses;
+            }
+        }
+    }
+}
+
+fn solve_motor(c: &mut JointConstraint, motor: JointMotor, set: &mut BodySet, dt: f32) {
+    let Some(drive) = c.drive else { return };
+    let (a, b) = (c.a, c.b);
+
+    let cdot = drive.velocity(set.get(a), set.get(b)) - motor.speed;
+    let lambda = -drive.mass(set.get(a), set.get(b)) * cdot;
+    let max = motor.max_force * dt;
+    let accumulated = (c.impulses.motor + lambda).clamp(-max, max);
+    drive.apply(set, a, b, accumulated - c.impulses.motor);
+    c.impulses.motor = accumulated;
+}
+
+fn solve_limit(c: &mut JointConstraint, set: &mut BodySet, bias: f32) {
+    let Some(drive) = c.drive else { return };
+    if c.limit == LimitState::Inactive {
+        return;
+    }
+    let (a, b) = (c.a, c.b);
+
+    let cdot = drive.velocity(set.get(a), set.get(b));
+    let lambda = -drive.mass(set.get(a), set.get(b)) * (cdot + bias * c.limit.error());
+    let accumulated = c.limit.clamp(c.impulses.limit, lambda);
+    drive.apply(set, a, b, accumulated - c.impulses.limit);
+    c.impulses.limit = accumulated;
+}
+
+/// Soft constraint along the spring, equivalent to an implicit spring and damper force
+fn solve_spring(c: &mut JointConstraint, set: &mut BodySet) {
+    let Some(drive) = c.drive else { return };
+    let (a, b) = (c.a, c.b);
+
+    let cdot = drive.velocity(set.get(a), set.get(b));
+    let k = drive.inv_mass(set.get(a), set.get(b)) + c.spring_gamma;
+    let lambda = -(cdot + c.spring_bias + c.spring_gamma * c.impulses.spring) / k;
+    drive.apply(set, a, b, lambda);
+    c.impulses.spring += lambda;
+}
+
+/// Keeps the anchor of a prismatic joint on its axis
+fn solve_perpendicular(c: &mut JointConstraint, set: &mut BodySet, bias: f32) {
+    let Some(perpendicular) = c.perpendicular else {
+        return;
+    };
+    let (a, b) = (c.a, c.b);
+
+    let cdot = perpendicular.velocity(set.get(a), set.get(b));
+    let lambda =
+        -perpendicular.mass(set.get(a), set.get(b)) * (cdot + bias * c.perpendicular_error);
+    perpendicular.apply(set, a, b, lambda);
+    c.impulses.perpendicular += lambda;
+}
+
+/// Keeps the relative rotation at the reference angle
+fn solve_angle(c: &mut JointConstraint, set: &mut BodySet, bias: f32) {
+    let (a, b) = (c.a, c.b);
+    let jacobian = Jacobian::angular();
+    let cdot = jacobian.velocity(set.get(a), set.get(b));
+    let lambda = -jacobian.mass(set.get(a), set.get(b)) * (cdot + bias * c.angle);
+    jacobian.apply(set, a, b, lambda);
+    c.impulses.angular += lambda;
+}
+
+/// Makes both anchors coincide, solving both axes at once.
+/// `max_impulse` bounds the length of the accumulated impulse.
+fn solve_point(c: &mut JointConstraint, set: &mut BodySet, bias: f32, max_impulse: Option<f32>) {
+    let (a, b) = (c.a, c.b);
+    let (body_a, body_b) = (*set.get(a), *set.get(b));
+    let (ra, rb) = (c.ra, c.rb);
+
+    let (ma, mb) = (body_a.inv_mass, body_b.inv_mass);
+    let (ia, ib) = (body_a.inv_inertia, body_b.inv_inertia);
+    let k11 = ma + mb + ia * ra.y * ra.y + ib * rb.y * rb.y;
+    let k12 = -ia * ra.x * ra.y - ib * rb.x * rb.y;
+    let k22 = ma + mb + ia * ra.x * ra.x + ib * rb.x * rb.x;
+    let det = k11 * k22 - k12 * k12;
+    if det.abs() < EPSILON {
+        return;
+    }
+
+    let cdot = body_b.velocity_at(rb) - body_a.velocity_at(ra);
+    let rhs = -(cdot + c.point_error * bias);
+    let lambda = vec2(k22 * rhs.x - k12 * rhs.y, k11 * rhs.y - k12 * rhs.x) / det;
+
+    let mut accumulated = c.impulses.point + lambda;
+    if let Some(max) = max_impulse
+        && accumulated.magnitude() > max
+    {
+        accumulated = accumulated.normalize() * max;
+    }
+    let impulse = accumulated - c.impulses.point;
+    c.impulses.point = accumulated;
+
+    set.get_mut(a).apply_impulse(-impulse, ra);
+    set.get_mut(b).apply_impulse(impulse, rb);
+}
@@ -0,0 +1,112 @@
+/// How the values of two touching materials are merged into one.
+/// When the materials disagree, the rule coming last in this list wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum CombineRule {
+    #[default]
+    Average,
+    Min,
+    Multiply,
+    Max,
+}
+
+impl CombineRule {
+    pub fn combine(self, a: f32, b: f32) -> f32 {
+        match self {
+            CombineRule::Average => (a + b) * 0.5,
+            CombineRule::Min => a.min(b),
+            CombineRule::Multiply => a * b,
+            CombineRule::Max => a.max(b),
+        }
+    }
+}
+
+/// Surface and mass properties shared by colliders, registered in `PhysicsMaterials`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhysicsMaterial {
+    /// Coulomb friction coefficient
+    pub friction: f32,
+    /// Bounciness, 0 keeps nothing of the approach velocity and 1 all of it
+    pub restitution: f32,
+    /// Mass per unit of area
+    pub density: f32,
+    pub friction_combine: CombineRule,
+    pub restitution_combine: CombineRule,
+}
+
+impl Default for PhysicsMaterial {
+    fn default() -> Self {
+        Self {
+            friction: 0.5,
+            restitution: 0.0,
+            density: 1.0,
+            friction_combine: CombineRule::Average,
+            restitution_combine: CombineRule::Max,
+        }
+    }
+}
+
+impl PhysicsMaterial {
+    pub fn new(friction: f32, restitution: f32, density: f32) -> Self {
+        Self {
+            friction,
+            restitution,
+            density,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the rules used to merge friction and restitution with other materials
+    pub fn with_combine(mut self, friction: CombineRule, restitution: CombineRule) -> Self {
+        self.friction_combine = friction;
+        self.restitution_combine = restitution;
+        self
+    }
+
+    /// Friction of a contact between both materials
+    pub fn combined_friction(&self, other: &PhysicsMaterial) -> f32 {
+        let rule = self.friction_combine.max(other.friction_combine);
+        rule.combine(self.friction, other.friction)
+    }
+
+    /// Restitution of a contact between both materials
+    pub fn combined_restitution(&self, other: &PhysicsMaterial) -> f32 {
+        let rule = self.restitution_combine.max(other.restitution_combine);
+        rule.combine(self.restitution, other.restitution)
+    }
+}
+
+/// Identifies a material registered in `PhysicsMaterials`.
+/// The default handle refers to the default material.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct MaterialHandle(usize);
+
+/// Resource holding every physics material. Colliders refer to them by handle, so
+/// editing a material changes every collider using it.
+#[derive(Debug)]
+pub struct PhysicsMaterials {
+    materials: Vec<PhysicsMaterial>,
+}
+
+impl Default for PhysicsMaterials {
+    fn default() -> Self {
+        Self {
+            materials: vec![PhysicsMaterial::default()],
+        }
+    }
+}
+
+impl PhysicsMaterials {
+    pub fn add(&mut self, material: PhysicsMaterial) -> MaterialHandle {
+        self.materials.push(material);
+        MaterialHandle(self.materials.len() - 1)
+    }
+
+    /// Material behind the handle, the default material if the handle is unknown
+    pub fn get(&self, handle: MaterialHandle) -> &PhysicsMaterial {
+        self.materials.get(handle.0).unwrap_or(&self.materials[0])
+    }
+
+    pub fn get_mut(&mut self, handle: MaterialHandle) -> Option<&mut PhysicsMaterial> {
+        self.materials.get_mut(handle.0)
+    }
+}
@@ -1,10 +1,331 @@
-use specs::{Entities, System};
+use std::collections::HashSet;
 
+use cgmath::InnerSpace;
+use specs::join::MaybeJoin;
+use specs::{Entities, Join, Read, ReadStorage, System, Write, WriteStorage};
+
+use crate::arith::{Vec2, vec2};
+use crate::collision::Collider;
+use crate::collision::broad_phase::{CollisionWorld, ordered_pair};
+use crate::collision::contact::Contacts;
+use crate::collision::obstacle::Obstacle;
+use crate::collision::overlap::Overlaps;
+use crate::components::Transform2D;
+use crate::time::Time;
+
+mod ccd;
+pub mod character;
+pub mod effector;
+pub mod island;
+pub mod joint;
+pub mod material;
 pub mod rigid_body;
+pub mod soft_body;
+pub mod solver;
+
+#[cfg(test)]
+mod tests;
+
+use ccd::StaticObstacles;
+use effector::AreaEffector2D;
+use island::{Islands, PhysicsStats, SleepSettings};
+use joint::{Joint2D, JointSolver};
+use material::PhysicsMaterials;
+use rigid_body::RigidBody2D;
+use solver::{BodySet, ContactSolver, SolverSettings};
+
+/// Resource with the acceleration applied to every dynamic body, scaled by its `gravity_scale`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gravity(pub Vec2);
 
+impl Default for Gravity {
+    fn default() -> Self {
+        Self(vec2(0.0, -9.81))
+    }
+}
+
+/// Advances every rigid body by one fixed timestep and writes the result into its `Transform2D`.
+/// Velocities are integrated first, then the contacts found by the `CollisionSystem` and the
+/// `Joint2D` constraints are resolved together before the bodies are moved.
+/// Bodies linked by contacts and joints form islands, which fall asleep together once all
+/// their bodies rest and wake up together when one of them is disturbed.
+/// Masses not set by hand come from the scaled collider area and its `PhysicsMaterial` density.
+/// `AreaEffector2D`s push the dynamic bodies overlapping them, waking them up.
+/// Bodies with `ccd` set are swept against static colliders so they never pass through them.
+/// The obstacles of the moved bodies are refreshed in the `CollisionWorld` for the systems
+/// running after it.
 pub struct Physics2DSystem;
 
 impl<'a> System<'a> for Physics2DSystem {
-    type SystemData = (Entities<'a>,);
-    fn run(&mut self, data: Self::SystemData) {}
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        Read<'a, Gravity>,
+        Read<'a, SolverSettings>,
+        Read<'a, SleepSettings>,
+        Read<'a, PhysicsMaterials>,
+        Read<'a, Contacts>,
+        Read<'a, Overlaps>,
+        Write<'a, CollisionWorld>,
+        Write<'a, ContactSolver>,
+        Write<'a, Islands>,
+        Write<'a, PhysicsStats>,
+        ReadStorage<'a, Collider>,
+        WriteStorage<'a, Transform2D>,
+        WriteStorage<'a, RigidBody2D>,
+        WriteStorage<'a, Joint2D>,
+        WriteStorage<'a, AreaEffector2D>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            time,
+            gravity,
+            settings,
+            sleep,
+            materials,
+            contacts,
+            overlaps,
+            mut collision_world,
+            mut contact_solver,
+            mut islands,
+            mut stats,
+            colliders,
+            mut transforms,
+            mut bodies,
+            mut joints,
+            mut effectors,
+        ): Self::SystemData,
+    ) {
+        let dt = time.fixed_timestep;
+
+        // Moving a transform by hand wakes its body, and moving non-dynamic bodies
+        // wake whatever they touch
+        let mut wakers = HashSet::new();
+        for (entity, transform, body) in (&entities, &transforms, &mut bodies).join() {
+            if body.was_moved(transform.position, transform.rotation) || !sleep.enabled {
+                body.wake_up();
+                wakers.insert(entity);
+            }
+            let moving = body.linear_velocity.magnitude2() > 0.0 || body.angular_velocity != 0.0;
+            if body.is_kinematic() && moving {
+                wakers.insert(entity);
+            }
+        }
+
+        for (body, collider, transform) in (&mut bodies, &colliders, &transforms).join() {
+            let density = materials.get(collider.material).density;
+            let (mass, inertia) = collider.mass_properties(density, transform.scale);
+            body.set_collider_mass(mass, inertia);
+        }
+
+        for effector in (&mut effectors).join() {
+            effector.advance(dt);
+        }
+
+        let mut replaced_gravity = HashSet::new();
+        for overlap in overlaps.iter() {
+            let pairs = [
+                (overlap.entity_a, overlap.entity_b),
+                (overlap.entity_b, overlap.entity_a),
+            ];
+            for (zone, entity) in pairs {
+                let (Some(effector), Some(zone_transform), Some(zone_collider)) = (
+                    effectors.get(zone),
+                    transforms.get(zone),
+                    colliders.get(zone),
+                ) else {
+                    continue;
+                };
+                let (Some(transform), Some(collider)) =
+                    (transforms.get(entity), colliders.get(entity))
+                else {
+                    continue;
+                };
+                let Some(body) = bodies.get_mut(entity).filter(|body| body.is_dynamic()) else {
+                    continue;
+                };
+
+                let area = zone_collider.world_shape(zone_transform);
+                let shape = collider.world_shape(transform);
+                let apply = |body: &mut RigidBody2D| {
+                    effector.apply(
+                        &area,
+                        zone_transform.position,
+                        body,
+                        transform.position,
+                        &shape,
+                        gravity.0,
+                    );
+                };
+
+                // Sleeping bodies wake up once an effector pushes them, whether it was just
+                // added, changed or moved over them
+                if body.is_sleeping() {
+                    let mut pushed = body.clone();
+                    apply(&mut pushed);
+                    if pushed.force() == body.force() && pushed.torque() == body.torque() {
+                        continue;
+                    }
+                    body.wake_up();
+                }
+                apply(body);
+
+                // Cancels the global gravity once, however many zones replace it
+                if effector.replaces_gravity() && replaced_gravity.insert(entity) {
+                    body.apply_force(-gravity.0 * body.gravity_scale * body.mass());
+                }
+            }
+        }
+
+        // Islands wake up together, after the effectors woke the bodies they push
+        let links: Vec<_> = contacts
+            .iter()
+            .map(|c| (c.entity_a, c.entity_b))
+            .chain(joints.join().map(|j| (j.entity_a, j.entity_b)))
+            .collect();
+        *islands = Islands::build(
+            (&entities, &bodies)
+                .join()
+                .filter(|(_, body)| body.is_dynamic())
+                .map(|(entity, _)| entity),
+            links.iter().copied(),
+        );
+        islands.wake(&links, &wakers, &mut bodies);
+
+        for body in (&mut bodies).join() {
+            if !body.is_sleeping() {
+                integrate_velocity(body, gravity.0, dt);
+            }
+        }
+
+        // Bodies connected by a joint don't collide unless the joint allows it
+        let connected: HashSet<_> = joints
+            .join()
+            .filter(|joint| !joint.collide_connected)
+            .map(|joint| ordered_pair(joint.entity_a, joint.entity_b))
+            .collect();
+
+        let mut set = BodySet::default();
+        let mut joint_solver = JointSolver::default();
+        contact_solver.prepare(
+            &contacts,
+            &connected,
+            &settings,
+            &materials,
+            &transforms,
+            &colliders,
+            &mut bodies,
+            &mut set,
+            dt,
+        );
+        joint_solver.prepare(
+            (&entities, &joints).join(),
+            &settings,
+            &transforms,
+            &mut bodies,
+            &mut set,
+            dt,
+        );
+
+        if settings.warm_starting {
+            joint_solver.warm_start(&mut set);
+            contact_solver.warm_start(&mut set);
+        }
+
+        for _ in 0..settings.velocity_iterations {
+            joint_solver.solve_velocities(&mut set);
+            contact_solver.solve_velocities(&mut set);
+        }
+
+        joint_solver.store_impulses(&mut joints);
+        contact_solver.store_impulses();
+        set.write_back(&mut bodies);
+
+        let moving: HashSet<_> = if (&bodies).join().any(|body| body.ccd) {
+            (&entities, &bodies)
+                .join()
+                .filter(|(_, body)| !body.is_static())
+                .map(|(entity, _)| entity)
+                .collect()
+        } else {
+            HashSet::new()
+        };
+
+        for (entity, transform, body, collider) in (
+            &entities,
+            &mut transforms,
+            &mut bodies,
+            MaybeJoin(&colliders),
+        )
+            .join()
+        {
+            if !body.is_sleeping() {
+                match collider {
+                    Some(collider) if body.ccd && body.is_dynamic() && !collider.is_trigger => {
+                        let obstacles = StaticObstacles {
+                            collision_world: &collision_world,
+                            moving: &moving,
+                            materials: &materials,
+                        };
+                        ccd::integrate_swept(body, transform, collider, &obstacles, &settings, dt);
+                    }
+                    _ => integrate_position(body, transform, dt),
+                }
+                if let Some(collider) = collider
+                    && !body.is_static()
+                {
+                    collision_world.track(Obstacle::new(entity, collider, transform));
+                }
+                body.update_sleep_time(sleep.linear_threshold, sleep.angular_threshold, dt);
+            }
+        }
+
+        if sleep.enabled {
+            islands.sleep(sleep.time_to_sleep, &mut bodies);
+        }
+
+        *stats = PhysicsStats::default();
+        stats.islands = islands.len();
+        for (transform, body) in (&transforms, &mut bodies).join() {
+            body.record_pose(transform.position, transform.rotation);
+            if body.is_sleeping() {
+                stats.sleeping_bodies += 1;
+            } else if !body.is_static() {
+                stats.awake_bodies += 1;
+            }
+        }
+    }
+}
+
+/// First half of the semi-implicit Euler step: updates the velocity from gravity and the
+/// accumulated forces, then clears them
+pub fn integrate_velocity(body: &mut RigidBody2D, gravity: Vec2, dt: f32) {
+    if body.is_static() {
+        body.linear_velocity = vec2(0.0, 0.0);
+        body.angular_velocity = 0.0;
+    }
+
+    if body.is_dynamic() {
+        let acceleration = gravity * body.gravity_scale + body.force() * body.inv_mass();
+        body.linear_velocity += acceleration * dt;
+        body.angular_velocity += body.torque() * body.inv_inertia() * dt;
+
+        body.linear_velocity *= 1.0 / (1.0 + dt * body.linear_damping);
+        body.angular_velocity *= 1.0 / (1.0 + dt * body.angular_damping);
+    }
+
+    if body.fixed_rotation {
+        body.angular_velocity = 0.0;
+    }
+
+    body.clear_forces();
+}
+
+/// Second half of the semi-implicit Euler step: moves the transform with the new velocity
+pub fn integrate_position(body: &RigidBody2D, transform: &mut Transform2D, dt: f32) {
+    transform.position += body.linear_velocity * dt;
+    transform.rotation += body.angular_velocity * dt;
 }
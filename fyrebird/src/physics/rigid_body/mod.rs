@@ -1,6 +1,328 @@
+use cgmath::InnerSpace;
 use specs::{Component, VecStorage};
 use specs_derive::Component;
 
-#[derive(Component, Default)]
+use crate::arith::{Point2, Vec2, cross_vec2, vec2};
+
+/// How a body takes part in the simulation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BodyType {
+    /// Moved by forces, gravity and collisions
+    #[default]
+    Dynamic,
+    /// Moved only by its velocity, behaves as if it had infinite mass
+    Kinematic,
+    /// Never moves
+    Static,
+}
+
+/// Rigid body state of an entity, integrated into its `Transform2D` by the `Physics2DSystem`
+#[derive(Debug, Component, Clone)]
 #[storage(VecStorage)]
-pub struct RigidBody2D;
+pub struct RigidBody2D {
+    pub body_type: BodyType,
+    mass: f32,
+    inv_mass: f32,
+    inertia: f32,
+    inv_inertia: f32,
+    /// World space velocity in units per second
+    pub linear_velocity: Vec2,
+    /// Counter-clockwise velocity in radians per second
+    pub angular_velocity: f32,
+    /// Slows the body down, each step multiplies the linear velocity by
+    /// `1 / (1 + dt * linear_damping)`
+    pub linear_damping: f32,
+    /// Slows the rotation down, each step multiplies the angular velocity by
+    /// `1 / (1 + dt * angular_damping)`
+    pub angular_damping: f32,
+    /// Multiplier applied to the `Gravity` resource for this body
+    pub gravity_scale: f32,
+    /// Prevents any rotation when set
+    pub fixed_rotation: bool,
+    /// Lets the body fall asleep once it comes to rest
+    pub can_sleep: bool,
+    /// Sweeps the body against static geometry so it can't pass through it when moving fast
+    pub ccd: bool,
+    force: Vec2,
+    torque: f32,
+    /// Mass and inertia given by hand rather than computed from the collider
+    custom_mass: bool,
+    custom_inertia: bool,
+    sleeping: bool,
+    /// Time spent under the sleep velocity thresholds
+    sleep_time: f32,
+    /// Pose written by the last physics step, used to detect transform edits
+    last_pose: Option<(Point2, f32)>,
+}
+
+impl Default for RigidBody2D {
+    fn default() -> Self {
+        Self::new(BodyType::Dynamic)
+    }
+}
+
+impl RigidBody2D {
+    /// Create a body of the given type with unit mass and inertia. Unless set by hand, both
+    /// are replaced with the ones of the entity's `Collider` and material on the next step.
+    pub fn new(body_type: BodyType) -> Self {
+        Self {
+            body_type,
+            mass: 1.0,
+            inv_mass: 1.0,
+            inertia: 1.0,
+            inv_inertia: 1.0,
+            linear_velocity: vec2(0.0, 0.0),
+            angular_velocity: 0.0,
+            linear_damping: 0.0,
+            angular_damping: 0.0,
+            gravity_scale: 1.0,
+            fixed_rotation: false,
+            can_sleep: true,
+            ccd: false,
+            force: vec2(0.0, 0.0),
+            torque: 0.0,
+            custom_mass: false,
+            custom_inertia: false,
+            sleeping: false,
+            sleep_time: 0.0,
+            last_pose: None,
+        }
+    }
+
+    pub fn dynamic() -> Self {
+        Self::new(BodyType::Dynamic)
+    }
+
+    pub fn kinematic() -> Self {
+        Self::new(BodyType::Kinematic)
+    }
+
+    pub fn fixed() -> Self {
+        Self::new(BodyType::Static)
+    }
+
+    /// Sets the mass, a mass of 0 or less makes the body immovable by forces
+    pub fn with_mass(mut self, mass: f32) -> Self {
+        self.set_mass(mass);
+        self
+    }
+
+    /// Sets the moment of inertia, see `Shape::inertia` to compute it from a collider
+    pub fn with_inertia(mut self, inertia: f32) -> Self {
+        self.set_inertia(inertia);
+        self
+    }
+
+    pub fn with_velocity(mut self, linear: Vec2, angular: f32) -> Self {
+        self.linear_velocity = linear;
+        self.angular_velocity = angular;
+        self
+    }
+
+    pub fn with_damping(mut self, linear: f32, angular: f32) -> Self {
+        self.linear_damping = linear;
+        self.angular_damping = angular;
+        self
+    }
+
+    pub fn with_gravity_scale(mut self, scale: f32) -> Self {
+        self.gravity_scale = scale;
+        self
+    }
+
+    pub fn with_fixed_rotation(mut self) -> Self {
+        self.fixed_rotation = true;
+        self
+    }
+
+    /// Enables continuous collision detection, for bullets and other fast bodies
+    pub fn with_ccd(mut self) -> Self {
+        self.ccd = true;
+        self
+    }
+
+    /// Keeps the body awake forever
+    pub fn never_sleep(mut self) -> Self {
+        self.can_sleep = false;
+        self
+    }
+
+    pub fn set_mass(&mut self, mass: f32) {
+        self.custom_mass = true;
+        self.store_mass(mass);
+    }
+
+    pub fn set_inertia(&mut self, inertia: f32) {
+        self.custom_inertia = true;
+        self.store_inertia(inertia);
+    }
+
+    /// Uses the mass and inertia of the collider, for the parts not set by hand. A mass set by
+    /// hand scales the collider inertia. Shapes without area leave both unchanged.
+    pub(crate) fn set_collider_mass(&mut self, mass: f32, inertia: f32) {
+        if mass <= 0.0 {
+            return;
+        }
+        if !self.custom_mass {
+            self.store_mass(mass);
+        }
+        if !self.custom_inertia {
+            self.store_inertia(inertia * self.mass / mass);
+        }
+    }
+
+    fn store_mass(&mut self, mass: f32) {
+        self.mass = mass.max(0.0);
+        self.inv_mass = if mass > 0.0 { 1.0 / mass } else { 0.0 };
+    }
+
+    fn store_inertia(&mut self, inertia: f32) {
+        self.inertia = inertia.max(0.0);
+        self.inv_inertia = if inertia > 0.0 { 1.0 / inertia } else { 0.0 };
+    }
+
+    pub fn mass(&self) -> f32 {
+        self.mass
+    }
+
+    pub fn inertia(&self) -> f32 {
+        self.inertia
+    }
+
+    /// Inverse mass, 0 for bodies that forces and impulses can't move
+    pub fn inv_mass(&self) -> f32 {
+        if self.is_dynamic() {
+            self.inv_mass
+        } else {
+            0.0
+        }
+    }
+
+    /// Inverse inertia, 0 for bodies that torques and impulses can't rotate
+    pub fn inv_inertia(&self) -> f32 {
+        if self.is_dynamic() && !self.fixed_rotation {
+            self.inv_inertia
+        } else {
+            0.0
+        }
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.body_type == BodyType::Dynamic
+    }
+
+    pub fn is_kinematic(&self) -> bool {
+        self.body_type == BodyType::Kinematic
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.body_type == BodyType::Static
+    }
+
+    /// Force accumulated since the last step
+    pub fn force(&self) -> Vec2 {
+        self.force
+    }
+
+    /// Torque accumulated since the last step
+    pub fn torque(&self) -> f32 {
+        self.torque
+    }
+
+    /// Adds a force through the center of mass, applied on the next step. Wakes the body up.
+    pub fn apply_force(&mut self, force: Vec2) {
+        self.wake_up();
+        self.force += force;
+    }
+
+    /// Adds a force at a world point, `center` being the body's center of mass
+    pub fn apply_force_at_point(&mut self, force: Vec2, point: Point2, center: Point2) {
+        self.wake_up();
+        self.force += force;
+        self.torque += cross_vec2(point - center, force);
+    }
+
+    pub fn apply_torque(&mut self, torque: f32) {
+        self.wake_up();
+        self.torque += torque;
+    }
+
+    /// Changes the velocity immediately through the center of mass. Wakes the body up.
+    pub fn apply_impulse(&mut self, impulse: Vec2) {
+        self.wake_up();
+        self.linear_velocity += impulse * self.inv_mass();
+    }
+
+    /// Changes the linear and angular velocity immediately from an impulse at a world point
+    pub fn apply_impulse_at_point(&mut self, impulse: Vec2, point: Point2, center: Point2) {
+        self.wake_up();
+        self.linear_velocity += impulse * self.inv_mass();
+        self.angular_velocity += cross_vec2(point - center, impulse) * self.inv_inertia();
+    }
+
+    pub fn apply_angular_impulse(&mut self, impulse: f32) {
+        self.wake_up();
+        self.angular_velocity += impulse * self.inv_inertia();
+    }
+
+    /// Sleeping bodies are skipped by the simulation until something wakes them up
+    pub fn is_sleeping(&self) -> bool {
+        self.sleeping
+    }
+
+    /// Makes the body simulated again. Call it after setting a velocity directly.
+    pub fn wake_up(&mut self) {
+        self.sleeping = false;
+        self.sleep_time = 0.0;
+    }
+
+    /// Puts the body to sleep, stopping it
+    pub fn sleep(&mut self) {
+        self.sleeping = true;
+        self.linear_velocity = vec2(0.0, 0.0);
+        self.angular_velocity = 0.0;
+        self.clear_forces();
+    }
+
+    pub(crate) fn sleep_time(&self) -> f32 {
+        self.sleep_time
+    }
+
+    /// Accumulates the time spent at rest, or resets it when the body moves faster than the thresholds
+    pub(crate) fn update_sleep_time(
+        &mut self,
+        linear_threshold: f32,
+        angular_threshold: f32,
+        dt: f32,
+    ) {
+        let resting = self.linear_velocity.magnitude2() <= linear_threshold * linear_threshold
+            && self.angular_velocity.abs() <= angular_threshold;
+
+        if self.can_sleep && self.is_dynamic() && resting {
+            self.sleep_time += dt;
+        } else {
+            self.sleep_time = 0.0;
+        }
+    }
+
+    /// Returns true if the transform was changed since the last physics step
+    pub(crate) fn was_moved(&self, position: Point2, rotation: f32) -> bool {
+        self.last_pose
+            .is_some_and(|pose| pose != (position, rotation))
+    }
+
+    pub(crate) fn record_pose(&mut self, position: Point2, rotation: f32) {
+        self.last_pose = Some((position, rotation));
+    }
+
+    /// Velocity of a world point attached to the body
+    pub fn velocity_at_point(&self, point: Point2, center: Point2) -> Vec2 {
+        let r = point - center;
+        self.linear_velocity + vec2(-r.y, r.x) * self.angular_velocity
+    }
+
+    pub fn clear_forces(&mut self) {
+        self.force = vec2(0.0, 0.0);
+        self.torque = 0.0;
+    }
+}
@@ -0,0 +1,454 @@
+use cgmath::InnerSpace;
+use specs::{
+    Component, Entities, Entity, Join, Read, ReadStorage, System, VecStorage, WriteStorage,
+};
+use specs_derive::Component;
+
+use crate::arith::{EPSILON, Point2, Vec2, vec2};
+use crate::collision::broad_phase::CollisionWorld;
+use crate::collision::obstacle::Obstacle;
+use crate::collision::shapes::{Aabb, WorldShape};
+use crate::collision::{ALL_LAYERS, DEFAULT_LAYER, narrow_phase};
+use crate::components::Transform2D;
+use crate::time::Time;
+
+use super::Gravity;
+use super::material::{MaterialHandle, PhysicsMaterial, PhysicsMaterials};
+
+/// Point mass of a soft body, moved by Verlet integration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Particle {
+    /// World position
+    pub position: Point2,
+    /// Position at the previous step, the velocity is implied by the difference
+    previous: Point2,
+    inv_mass: f32,
+}
+
+impl Particle {
+    /// Create a particle at rest, a mass of 0 or less makes it immovable
+    pub fn new(position: Point2, mass: f32) -> Self {
+        Self {
+            position,
+            previous: position,
+            inv_mass: if mass > 0.0 { 1.0 / mass } else { 0.0 },
+        }
+    }
+
+    pub fn inv_mass(&self) -> f32 {
+        self.inv_mass
+    }
+
+    /// Average velocity over the last step of length `dt`
+    pub fn velocity(&self, dt: f32) -> Vec2 {
+        (self.position - self.previous) / dt
+    }
+
+    /// Moves the particle without giving it any velocity
+    pub fn teleport(&mut self, position: Point2) {
+        self.previous += position - self.position;
+        self.position = position;
+    }
+}
+
+/// Keeps two particles at `rest_length` from each other
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DistanceConstraint {
+    pub a: usize,
+    pub b: usize,
+    pub rest_length: f32,
+    /// Fraction of the error corrected per iteration, from 0 to 1
+    pub stiffness: f32,
+}
+
+/// What a pinned particle is attached to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Anchor {
+    /// Fixed world position
+    World(Point2),
+    /// Point in the local space of an entity's `Transform2D`, released if the entity is deleted
+    Entity { entity: Entity, local: Point2 },
+}
+
+/// Particle held in place by an anchor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pin {
+    pub particle: usize,
+    pub anchor: Anchor,
+}
+
+/// Position based soft body for ropes, chains and cloth: particles in world space linked by
+/// distance constraints. Simulated by the `SoftBodySystem` on the fixed timestep, the entity
+/// doesn't need a `Transform2D`.
+#[derive(Debug, Component, Clone)]
+#[storage(VecStorage)]
+pub struct SoftBody2D {
+    particles: Vec<Particle>,
+    constraints: Vec<DistanceConstraint>,
+    pins: Vec<Pin>,
+    /// Constraint relaxation passes per step, more makes the body stiffer
+    pub iterations: usize,
+    /// Slows the particles down, each step multiplies their velocity by `1 / (1 + dt * damping)`
+    pub damping: f32,
+    /// Multiplier applied to the `Gravity` resource
+    pub gravity_scale: f32,
+    /// Collision radius of every particle
+    pub radius: f32,
+    /// Layers the particles belong to
+    pub layer: u32,
+    /// Layers of the colliders the particles collide with
+    pub mask: u32,
+    /// Friction used against colliders, combined with their material
+    pub material: MaterialHandle,
+    /// Constraints stretched past this multiple of their rest length break
+    pub tear_ratio: Option<f32>,
+}
+
+impl Default for SoftBody2D {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SoftBody2D {
+    /// Create an empty soft body, see `add_particle` and `connect` to build it
+    pub fn new() -> Self {
+        Self {
+            particles: Vec::new(),
+            constraints: Vec::new(),
+            pins: Vec::new(),
+            iterations: 8,
+            damping: 0.1,
+            gravity_scale: 1.0,
+            radius: 0.05,
+            layer: DEFAULT_LAYER,
+            mask: ALL_LAYERS,
+            material: MaterialHandle::default(),
+            tear_ratio: None,
+        }
+    }
+
+    /// Chain of unit mass particles through the given points, linked by stiff constraints
+    pub fn chain(points: &[Point2]) -> Self {
+        let mut body = Self::new();
+        for &point in points {
+            body.add_particle(Particle::new(point, 1.0));
+        }
+        for i in 1..points.len() {
+            body.connect(i - 1, i, 1.0);
+        }
+        body
+    }
+
+    /// Straight rope from `start` to `end` made of `segments` links
+    pub fn rope(start: Point2, end: Point2, segments: usize) -> Self {
+        let segments = segments.max(1);
+        let points: Vec<_> = (0..=segments)
+            .map(|i| start + (end - start) * (i as f32 / segments as f32))
+            .collect();
+        Self::chain(&points)
+    }
+
+    /// Grid of `columns` by `rows` particles hanging down from `top_left`, `spacing` apart.
+    /// Particle `(column, row)` is at index `row * columns + column`. Structural links are
+    /// stiff and diagonal shear links softer.
+    pub fn cloth(top_left: Point2, columns: usize, rows: usize, spacing: f32) -> Self {
+        let mut body = Self::new();
+        for row in 0..rows {
+            for column in 0..columns {
+                let offset = vec2(column as f32, -(row as f32)) * spacing;
+                body.add_particle(Particle::new(top_left + offset, 1.0));
+            }
+        }
+
+        let index = |column: usize, row: usize| row * columns + column;
+        for row in 0..rows {
+            for column in 0..columns {
+                if column + 1 < columns {
+                    body.connect(index(column, row), index(column + 1, row), 1.0);
+                }
+                if row + 1 < rows {
+                    body.connect(index(column, row), index(column, row + 1), 1.0);
+                }
+                if column + 1 < columns && row + 1 < rows {
+                    body.connect(index(column, row), index(column + 1, row + 1), 0.5);
+                    body.connect(index(column + 1, row), index(column, row + 1), 0.5);
+                }
+            }
+        }
+        body
+    }
+
+    pub fn with_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    pub fn with_damping(mut self, damping: f32) -> Self {
+        self.damping = damping;
+        self
+    }
+
+    pub fn with_gravity_scale(mut self, scale: f32) -> Self {
+        self.gravity_scale = scale;
+        self
+    }
+
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    pub fn with_layers(mut self, layer: u32, mask: u32) -> Self {
+        self.layer = layer;
+        self.mask = mask;
+        self
+    }
+
+    pub fn with_material(mut self, material: MaterialHandle) -> Self {
+        self.material = material;
+        self
+    }
+
+    /// Lets constraints break once stretched past `ratio` times their rest length
+    pub fn with_tearing(mut self, ratio: f32) -> Self {
+        self.tear_ratio = Some(ratio);
+        self
+    }
+
+    /// Pins a particle where it currently is
+    pub fn pinned(mut self, particle: usize) -> Self {
+        if let Some(p) = self.particles.get(particle) {
+            let anchor = Anchor::World(p.position);
+            self.pin(particle, anchor);
+        }
+        self
+    }
+
+    /// Pins a particle to a point in the local space of an entity
+    pub fn pinned_to(mut self, particle: usize, entity: Entity, local: Point2) -> Self {
+        self.pin(particle, Anchor::Entity { entity, local });
+        self
+    }
+
+    /// Adds a particle and returns its index
+    pub fn add_particle(&mut self, particle: Particle) -> usize {
+        self.particles.push(particle);
+        self.particles.len() - 1
+    }
+
+    /// Links two particles at their current distance
+    pub fn connect(&mut self, a: usize, b: usize, stiffness: f32) {
+        let rest_length = (self.particles[b].position - self.particles[a].position).magnitude();
+        self.constraints.push(DistanceConstraint {
+            a,
+            b,
+            rest_length,
+            stiffness: stiffness.clamp(0.0, 1.0),
+        });
+    }
+
+    /// Attaches a particle to an anchor, replacing its previous pin
+    pub fn pin(&mut self, particle: usize, anchor: Anchor) {
+        self.unpin(particle);
+        self.pins.push(Pin { particle, anchor });
+    }
+
+    pub fn unpin(&mut self, particle: usize) {
+        self.pins.retain(|pin| pin.particle != particle);
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    pub fn particles_mut(&mut self) -> &mut [Particle] {
+        &mut self.particles
+    }
+
+    /// Constraints left, torn ones are removed
+    pub fn constraints(&self) -> &[DistanceConstraint] {
+        &self.constraints
+    }
+
+    pub fn pins(&self) -> &[Pin] {
+        &self.pins
+    }
+
+    /// Advances the body by `dt`. `anchor` resolves entity anchors into world positions,
+    /// pins it can't resolve are released.
+    pub(crate) fn step(
+        &mut self,
+        gravity: Vec2,
+        dt: f32,
+        anchor: impl Fn(Entity, Point2) -> Option<Point2>,
+        collision_world: &CollisionWorld,
+        materials: &PhysicsMaterials,
+    ) {
+        self.pins.retain(|pin| match pin.anchor {
+            Anchor::World(_) => true,
+            Anchor::Entity { entity, local } => anchor(entity, local).is_some(),
+        });
+        let targets: Vec<_> = self
+            .pins
+            .iter()
+            .filter(|pin| pin.particle < self.particles.len())
+            .map(|pin| {
+                let target = match pin.anchor {
+                    Anchor::World(position) => position,
+                    Anchor::Entity { entity, local } => anchor(entity, local).unwrap(),
+                };
+                (pin.particle, target)
+            })
+            .collect();
+
+        let mut weights: Vec<_> = self.particles.iter().map(|p| p.inv_mass).collect();
+        for &(particle, _) in &targets {
+            weights[particle] = 0.0;
+        }
+
+        let keep = 1.0 / (1.0 + dt * self.damping);
+        let acceleration = gravity * self.gravity_scale;
+        for (particle, &weight) in self.particles.iter_mut().zip(&weights) {
+            if weight > 0.0 {
+                let velocity = (particle.position - particle.previous) * keep;
+                particle.previous = particle.position;
+                particle.position += velocity + acceleration * dt * dt;
+            } else {
+                particle.previous = particle.position;
+            }
+        }
+
+        // Pinned particles move with their anchor, keeping the velocity for when released
+        for &(particle, target) in &targets {
+            self.particles[particle].position = target;
+        }
+
+        let bounds = self.bounds();
+        let nearby: Vec<_> = collision_world
+            .obstacles(&bounds)
+            .into_iter()
+            .filter(|o| !o.is_trigger && o.accepts(self.layer, self.mask))
+            .map(|o| (o, materials.get(o.material)))
+            .collect();
+        let material = materials.get(self.material);
+
+        for _ in 0..self.iterations {
+            for c in &self.constraints {
+                let (wa, wb) = (weights[c.a], weights[c.b]);
+                if wa + wb <= 0.0 {
+                    continue;
+                }
+                let delta = self.particles[c.b].position - self.particles[c.a].position;
+                let length = delta.magnitude();
+                if length <= EPSILON {
+                    continue;
+                }
+                let correction = delta * ((length - c.rest_length) / length * c.stiffness);
+                self.particles[c.a].position += correction * (wa / (wa + wb));
+                self.particles[c.b].position -= correction * (wb / (wa + wb));
+            }
+
+            for (particle, &weight) in self.particles.iter_mut().zip(&weights) {
+                if weight > 0.0 {
+                    for &(obstacle, surface) in &nearby {
+                        collide(particle, self.radius, obstacle, material, surface);
+                    }
+                }
+            }
+        }
+
+        if let Some(ratio) = self.tear_ratio {
+            let particles = &self.particles;
+            self.constraints.retain(|c| {
+                let length = (particles[c.b].position - particles[c.a].position).magnitude();
+                length <= c.rest_length * ratio
+            });
+        }
+    }
+
+    /// Bounds of every particle, padded by the distance they may travel this step
+    fn bounds(&self) -> Aabb {
+        let reach = self
+            .particles
+            .iter()
+            .map(|p| (p.position - p.previous).magnitude())
+            .fold(0.0, f32::max);
+        Aabb::from_points(self.particles.iter().map(|p| p.position)).expanded(self.radius + reach)
+    }
+}
+
+/// Pushes a particle out of an obstacle, friction removing some of its sliding motion
+fn collide(
+    particle: &mut Particle,
+    radius: f32,
+    obstacle: &Obstacle,
+    material: &PhysicsMaterial,
+    surface: &PhysicsMaterial,
+) {
+    let center = particle.position;
+    if !Aabb::from_center(center, vec2(radius, radius)).overlaps(&obstacle.aabb) {
+        return;
+    }
+
+    let shape = WorldShape::Circle { center, radius };
+    let Some(manifold) = narrow_phase::collide(&shape, &obstacle.shape) else {
+        return;
+    };
+    let penetration = manifold.penetration();
+    if penetration <= 0.0 {
+        return;
+    }
+
+    particle.position -= manifold.normal * penetration;
+
+    let travel = particle.position - particle.previous;
+    let tangent = travel - manifold.normal * travel.dot(manifold.normal);
+    let slide = tangent.magnitude();
+    let friction = material.combined_friction(surface);
+    if slide > EPSILON {
+        let stop = (friction * penetration / slide).min(1.0);
+        particle.previous += tangent * stop;
+    }
+}
+
+/// Simulates every `SoftBody2D` against the solid colliders of the `CollisionWorld`. Run it
+/// after the `Physics2DSystem` so pins follow the bodies they are attached to and the
+/// colliders are where the bodies ended the step.
+/// Particles are pushed out of colliders but don't push rigid bodies back.
+pub struct SoftBodySystem;
+
+impl<'a> System<'a> for SoftBodySystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        Read<'a, Gravity>,
+        Read<'a, PhysicsMaterials>,
+        Read<'a, CollisionWorld>,
+        ReadStorage<'a, Transform2D>,
+        WriteStorage<'a, SoftBody2D>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, time, gravity, materials, collision_world, transforms, mut soft_bodies): Self::SystemData,
+    ) {
+        let anchor = |entity: Entity, local: Point2| {
+            entities
+                .is_alive(entity)
+                .then(|| transforms.get(entity))
+                .flatten()
+                .map(|transform| transform.transform_point(local))
+        };
+
+        for soft_body in (&mut soft_bodies).join() {
+            soft_body.step(
+                gravity.0,
+                time.fixed_timestep,
+                anchor,
+                &collision_world,
+                &materials,
+            );
+        }
+    }
+}
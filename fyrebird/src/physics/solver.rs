@@ -0,0 +1,377 @@
+use std::collections::{HashMap, HashSet};
+
+use cgmath::InnerSpace;
+use specs::Entity;
+use specs::storage::{GenericReadStorage, GenericWriteStorage};
+
+use crate::arith::{Point2, Vec2, cross_vec2, perp_vec2, point2, vec2};
+use crate::collision::Collider;
+use crate::collision::broad_phase::ordered_pair;
+use crate::collision::contact::Contacts;
+use crate::components::Transform2D;
+
+use super::material::PhysicsMaterials;
+use super::rigid_body::RigidBody2D;
+
+/// Resource tuning the contact and joint solver of the `Physics2DSystem`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SolverSettings {
+    /// Passes over every constraint per step, more passes converge closer to the exact solution
+    pub velocity_iterations: usize,
+    /// Starts each step from the impulses found on the previous one
+    pub warm_starting: bool,
+    /// Fraction of the penetration or joint error removed per step (Baumgarte stabilization)
+    pub baumgarte: f32,
+    /// Penetration allowed without correction, avoids jitter of resting contacts
+    pub linear_slop: f32,
+    /// Approach speed under which contacts don't bounce
+    pub restitution_threshold: f32,
+}
+
+impl Default for SolverSettings {
+    fn default() -> Self {
+        Self {
+            velocity_iterations: 8,
+            warm_starting: true,
+            baumgarte: 0.2,
+            linear_slop: 0.005,
+            restitution_threshold: 1.0,
+        }
+    }
+}
+
+/// Velocity state of a body taking part in the solve
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SolverBody {
+    pub center: Point2,
+    pub angle: f32,
+    pub linear_velocity: Vec2,
+    pub angular_velocity: f32,
+    pub inv_mass: f32,
+    pub inv_inertia: f32,
+}
+
+impl SolverBody {
+    /// Body that never moves, used for joints anchored to the world
+    pub fn ground() -> Self {
+        Self {
+            center: point2(0.0, 0.0),
+            angle: 0.0,
+            linear_velocity: vec2(0.0, 0.0),
+            angular_velocity: 0.0,
+            inv_mass: 0.0,
+            inv_inertia: 0.0,
+        }
+    }
+
+    pub fn apply_impulse(&mut self, impulse: Vec2, r: Vec2) {
+        self.linear_velocity += impulse * self.inv_mass;
+        self.angular_velocity += cross_vec2(r, impulse) * self.inv_inertia;
+    }
+
+    pub fn apply_angular_impulse(&mut self, impulse: f32) {
+        self.angular_velocity += impulse * self.inv_inertia;
+    }
+
+    pub fn velocity_at(&self, r: Vec2) -> Vec2 {
+        self.linear_velocity + vec2(-r.y, r.x) * self.angular_velocity
+    }
+}
+
+/// Bodies touched by the constraints of a step, shared by the contact and joint solvers
+#[derive(Debug, Default)]
+pub(crate) struct BodySet {
+    states: Vec<SolverBody>,
+    entities: Vec<Option<Entity>>,
+    indices: HashMap<Entity, usize>,
+}
+
+impl BodySet {
+    /// Index of the entity's solver state, added on first use. `None` if it has no transform.
+    /// Entities without a `RigidBody2D` never move.
+    pub fn index<T, B>(&mut self, entity: Entity, transforms: &T, bodies: &mut B) -> Option<usize>
+    where
+        T: GenericReadStorage<Component = Transform2D>,
+        B: GenericWriteStorage<Component = RigidBody2D>,
+    {
+        if let Some(&index) = self.indices.get(&entity) {
+            return Some(index);
+        }
+
+        let transform = transforms.get(entity)?;
+        let mut state = SolverBody {
+            center: transform.position,
+            angle: transform.rotation,
+            ..SolverBody::ground()
+        };
+        // Sleeping bodies only touch static or sleeping bodies, they act as static ones
+        if let Some(body) = bodies.get_mut(entity)
+            && !body.is_sleeping()
+        {
+            state.linear_velocity = body.linear_velocity;
+            state.angular_velocity = body.angular_velocity;
+            state.inv_mass = body.inv_mass();
+            state.inv_inertia = body.inv_inertia();
+        }
+
+        self.indices.insert(entity, self.states.len());
+        Some(self.push(state, Some(entity)))
+    }
+
+    /// Adds a body that never moves and isn't backed by an entity
+    pub fn ground(&mut self) -> usize {
+        self.push(SolverBody::ground(), None)
+    }
+
+    fn push(&mut self, state: SolverBody, entity: Option<Entity>) -> usize {
+        self.states.push(state);
+        self.entities.push(entity);
+        self.states.len() - 1
+    }
+
+    pub fn get(&self, index: usize) -> &SolverBody {
+        &self.states[index]
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> &mut SolverBody {
+        &mut self.states[index]
+    }
+
+    /// Copies the solved velocities back into the dynamic bodies
+    pub fn write_back<B>(&self, bodies: &mut B)
+    where
+        B: GenericWriteStorage<Component = RigidBody2D>,
+    {
+        for (state, entity) in self.states.iter().zip(&self.entities) {
+            if let Some(body) = entity.and_then(|e| bodies.get_mut(e))
+                && body.is_dynamic()
+                && !body.is_sleeping()
+            {
+                body.linear_velocity = state.linear_velocity;
+                body.angular_velocity = state.angular_velocity;
+            }
+        }
+    }
+}
+
+/// Inverse of the mass seen by an impulse along `axis` applied at offsets `ra` and `rb`
+pub(crate) fn effective_mass(
+    body_a: &SolverBody,
+    body_b: &SolverBody,
+    ra: Vec2,
+    rb: Vec2,
+    axis: Vec2,
+) -> f32 {
+    let rna = cross_vec2(ra, axis);
+    let rnb = cross_vec2(rb, axis);
+    let k = body_a.inv_mass
+        + body_b.inv_mass
+        + body_a.inv_inertia * rna * rna
+        + body_b.inv_inertia * rnb * rnb;
+    if k > 0.0 { 1.0 / k } else { 0.0 }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ConstraintPoint {
+    id: u32,
+    /// Offsets from the centers of both bodies to the contact point
+    ra: Vec2,
+    rb: Vec2,
+    normal_mass: f32,
+    tangent_mass: f32,
+    normal_impulse: f32,
+    tangent_impulse: f32,
+    /// Separation speed the normal impulse aims for, from restitution or penetration recovery
+    target_velocity: f32,
+}
+
+#[derive(Debug, Clone)]
+struct ContactConstraint {
+    entity_a: Entity,
+    entity_b: Entity,
+    a: usize,
+    b: usize,
+    normal: Vec2,
+    friction: f32,
+    points: Vec<ConstraintPoint>,
+}
+
+/// Sequential impulse solver for the manifolds found by the `CollisionSystem`.
+/// Kept as a resource so the impulses of persistent contacts warm start the next step.
+#[derive(Debug, Default)]
+pub struct ContactSolver {
+    /// Accumulated normal and tangent impulses by entity pair and contact point id
+    impulses: HashMap<(Entity, Entity, u32), (f32, f32)>,
+    constraints: Vec<ContactConstraint>,
+}
+
+impl ContactSolver {
+    /// Builds the constraints of this step, skipping the pairs in `ignored`.
+    /// Colliders without a `RigidBody2D` act as static geometry.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn prepare<T, C, B>(
+        &mut self,
+        contacts: &Contacts,
+        ignored: &HashSet<(Entity, Entity)>,
+        settings: &SolverSettings,
+        materials: &PhysicsMaterials,
+        transforms: &T,
+        colliders: &C,
+        bodies: &mut B,
+        set: &mut BodySet,
+        dt: f32,
+    ) where
+        T: GenericReadStorage<Component = Transform2D>,
+        C: GenericReadStorage<Component = Collider>,
+        B: GenericWriteStorage<Component = RigidBody2D>,
+    {
+        self.constraints.clear();
+
+        for contact in contacts.iter() {
+            if ignored.contains(&ordered_pair(contact.entity_a, contact.entity_b)) {
+                continue;
+            }
+
+            let (Some(a), Some(b)) = (
+                set.index(contact.entity_a, transforms, bodies),
+                set.index(contact.entity_b, transforms, bodies),
+            ) else {
+                continue;
+            };
+            let (body_a, body_b) = (*set.get(a), *set.get(b));
+            if body_a.inv_mass + body_a.inv_inertia + body_b.inv_mass + body_b.inv_inertia == 0.0 {
+                continue;
+            }
+
+            let (friction, restitution) = match (
+                colliders.get(contact.entity_a),
+                colliders.get(contact.entity_b),
+            ) {
+                (Some(ca), Some(cb)) => {
+                    let (ma, mb) = (materials.get(ca.material), materials.get(cb.material));
+                    (ma.combined_friction(mb), ma.combined_restitution(mb))
+                }
+                _ => continue,
+            };
+
+            let normal = contact.manifold.normal;
+            let tangent = perp_vec2(normal);
+
+            let points = contact
+                .manifold
+                .points()
+                .iter()
+                .map(|point| {
+                    let ra = point.position - body_a.center;
+                    let rb = point.position - body_b.center;
+
+                    let approach = (body_b.velocity_at(rb) - body_a.velocity_at(ra)).dot(normal);
+                    let bounce = if approach < -settings.restitution_threshold {
+                        -restitution * approach
+                    } else {
+                        0.0
+                    };
+                    let recovery = settings.baumgarte / dt
+                        * (point.penetration - settings.linear_slop).max(0.0);
+
+                    let (normal_impulse, tangent_impulse) = if settings.warm_starting {
+                        self.impulses
+                            .get(&(contact.entity_a, contact.entity_b, point.id))
+                            .copied()
+                            .unwrap_or_default()
+                    } else {
+                        (0.0, 0.0)
+                    };
+
+                    ConstraintPoint {
+                        id: point.id,
+                        ra,
+                        rb,
+                        normal_mass: effective_mass(&body_a, &body_b, ra, rb, normal),
+                        tangent_mass: effective_mass(&body_a, &body_b, ra, rb, tangent),
+                        normal_impulse,
+                        tangent_impulse,
+                        target_velocity: bounce.max(recovery),
+                    }
+                })
+                .collect();
+
+            self.constraints.push(ContactConstraint {
+                entity_a: contact.entity_a,
+                entity_b: contact.entity_b,
+                a,
+                b,
+                normal,
+                friction,
+                points,
+            });
+        }
+    }
+
+    /// Applies the impulses carried over from the previous step
+    pub(crate) fn warm_start(&self, set: &mut BodySet) {
+        for constraint in &self.constraints {
+            let tangent = perp_vec2(constraint.normal);
+            for point in &constraint.points {
+                let impulse =
+                    constraint.normal * point.normal_impulse + tangent * point.tangent_impulse;
+                set.get_mut(constraint.a).apply_impulse(-impulse, point.ra);
+                set.get_mut(constraint.b).apply_impulse(impulse, point.rb);
+            }
+        }
+    }
+
+    /// One Gauss-Seidel pass over every contact
+    pub(crate) fn solve_velocities(&mut self, set: &mut BodySet) {
+        for constraint in &mut self.constraints {
+            solve_constraint(constraint, set);
+        }
+    }
+
+    /// Keeps the accumulated impulses to warm start the next step
+    pub(crate) fn store_impulses(&mut self) {
+        self.impulses.clear();
+        for constraint in &self.constraints {
+            for point in &constraint.points {
+                self.impulses.insert(
+                    (constraint.entity_a, constraint.entity_b, point.id),
+                    (point.normal_impulse, point.tangent_impulse),
+                );
+            }
+        }
+    }
+}
+
+/// Friction first so the non-penetration impulses get the last word
+fn solve_constraint(constraint: &mut ContactConstraint, set: &mut BodySet) {
+    let normal = constraint.normal;
+    let tangent = perp_vec2(normal);
+    let (a, b) = (constraint.a, constraint.b);
+
+    for point in &mut constraint.points {
+        let relative = set.get(b).velocity_at(point.rb) - set.get(a).velocity_at(point.ra);
+        let lambda = -relative.dot(tangent) * point.tangent_mass;
+
+        // Coulomb cone: friction can't exceed the normal impulse times the coefficient
+        let max_friction = constraint.friction * point.normal_impulse;
+        let accumulated = (point.tangent_impulse + lambda).clamp(-max_friction, max_friction);
+        let impulse = tangent * (accumulated - point.tangent_impulse);
+        point.tangent_impulse = accumulated;
+
+        set.get_mut(a).apply_impulse(-impulse, point.ra);
+        set.get_mut(b).apply_impulse(impulse, point.rb);
+    }
+
+    for point in &mut constraint.points {
+        let relative = set.get(b).velocity_at(point.rb) - set.get(a).velocity_at(point.ra);
+        let lambda = (point.target_velocity - relative.dot(normal)) * point.normal_mass;
+
+        // Contacts can push the bodies apart but never pull them together
+        let accumulated = (point.normal_impulse + lambda).max(0.0);
+        let impulse = normal * (accumulated - point.normal_impulse);
+        point.normal_impulse = accumulated;
+
+        set.get_mut(a).apply_impulse(-impulse, point.ra);
+        set.get_mut(b).apply_impulse(impulse, point.rb);
+    }
+}
@@ -0,0 +1,1429 @@
+use cgmath::{InnerSpace, MetricSpace};
+use specs::{Builder, Entity, RunNow, System, World, WorldExt};
+
+use crate::arith::{Vec2, point2, rad, rotate_vec2, vec2};
+use crate::collision::shapes::{AxisAlignedBox, Circle, ConvexPolygon, Shape};
+use crate::collision::{Collider, CollisionSystem};
+use crate::components::Transform2D;
+use crate::time::Time;
+
+use super::character::{CharacterController2D, CharacterControllerSystem};
+use super::effector::{AreaEffector2D, EffectorKind, Falloff};
+use super::island::{Islands, PhysicsStats};
+use super::joint::Joint2D;
+use super::material::{CombineRule, PhysicsMaterial, PhysicsMaterials};
+use super::rigid_body::RigidBody2D;
+use super::soft_body::{SoftBody2D, SoftBodySystem};
+use super::solver::SolverSettings;
+use super::{Gravity, Physics2DSystem};
+
+fn approx_eq(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-4
+}
+
+fn physics_world(gravity: f32) -> World {
+    let mut world = World::new();
+    System::setup(&mut CollisionSystem, &mut world);
+    System::setup(&mut Physics2DSystem, &mut world);
+    System::setup(&mut CharacterControllerSystem, &mut world);
+    System::setup(&mut SoftBodySystem, &mut world);
+    world.insert(Time::new());
+    world.insert(Gravity(vec2(0.0, gravity)));
+    world
+}
+
+fn add_body(world: &mut World, body: RigidBody2D) -> Entity {
+    world
+        .create_entity()
+        .with(Transform2D::default())
+        .with(body)
+        .build()
+}
+
+fn add_body_at(world: &mut World, x: f32, y: f32, body: RigidBody2D) -> Entity {
+    world
+        .create_entity()
+        .with(Transform2D {
+            position: point2(x, y),
+            ..Default::default()
+        })
+        .with(body)
+        .build()
+}
+
+fn add_anchor(world: &mut World, x: f32, y: f32) -> Entity {
+    add_body_at(world, x, y, RigidBody2D::fixed())
+}
+
+fn add_joint(world: &mut World, joint: Joint2D) -> Entity {
+    world.create_entity().with(joint).build()
+}
+
+fn position(world: &World, entity: Entity) -> Transform2D {
+    *world.read_storage::<Transform2D>().get(entity).unwrap()
+}
+
+fn step(world: &mut World, steps: usize) {
+    for _ in 0..steps {
+        CollisionSystem.run_now(world);
+        Physics2DSystem.run_now(world);
+        SoftBodySystem.run_now(world);
+        world.maintain();
+    }
+}
+
+#[test]
+fn test_semi_implicit_euler_gravity() {
+    let mut world = physics_world(-10.0);
+    let falling = add_body(&mut world, RigidBody2D::dynamic());
+    let floating = add_body(&mut world, RigidBody2D::dynamic().with_gravity_scale(0.0));
+
+    step(&mut world, 2);
+
+    let dt = world.read_resource::<Time>().fixed_timestep;
+    let bodies = world.read_storage::<RigidBody2D>();
+    let transforms = world.read_storage::<Transform2D>();
+
+    // The velocity is updated before the position: y = -g * dt^2 * (1 + 2)
+    assert!(approx_eq(
+        bodies.get(falling).unwrap().linear_velocity.y,
+        -20.0 * dt
+    ));
+    assert!(approx_eq(
+        transforms.get(falling).unwrap().position.y,
+        -30.0 * dt * dt
+    ));
+    assert_eq!(transforms.get(floating).unwrap().position, point2(0.0, 0.0));
+}
+
+#[test]
+fn test_body_types() {
+    let mut world = physics_world(-10.0);
+    let kinematic = add_body(
+        &mut world,
+        RigidBody2D::kinematic().with_velocity(vec2(60.0, 0.0), 0.0),
+    );
+    let fixed = add_body(
+        &mut world,
+        RigidBody2D::fixed().with_velocity(vec2(60.0, 0.0), 1.0),
+    );
+
+    {
+        let mut bodies = world.write_storage::<RigidBody2D>();
+        bodies
+            .get_mut(kinematic)
+            .unwrap()
+            .apply_force(vec2(0.0, 1000.0));
+        bodies.get_mut(fixed).unwrap().apply_impulse(vec2(5.0, 5.0));
+    }
+    step(&mut world, 60);
+
+    let transforms = world.read_storage::<Transform2D>();
+    let position = transforms.get(kinematic).unwrap().position;
+    assert!(approx_eq(position.x, 60.0) && approx_eq(position.y, 0.0));
+    assert_eq!(transforms.get(fixed).unwrap().position, point2(0.0, 0.0));
+    assert_eq!(transforms.get(fixed).unwrap().rotation, 0.0);
+}
+
+#[test]
+fn test_forces_torques_and_damping() {
+    let mut world = physics_world(0.0);
+    let pushed = add_body(&mut world, RigidBody2D::dynamic().with_mass(2.0));
+    let damped = add_body(
+        &mut world,
+        RigidBody2D::dynamic()
+            .with_velocity(vec2(10.0, 0.0), 4.0)
+            .with_damping(1.0, 1.0),
+    );
+
+    {
+        let mut bodies = world.write_storage::<RigidBody2D>();
+        let body = bodies.get_mut(pushed).unwrap();
+        // Pushing up on the right side spins the body counter-clockwise
+        body.apply_force_at_point(vec2(0.0, 120.0), point2(1.0, 0.0), point2(0.0, 0.0));
+    }
+    step(&mut world, 1);
+
+    let dt = world.read_resource::<Time>().fixed_timestep;
+    let bodies = world.read_storage::<RigidBody2D>();
+    let body = bodies.get(pushed).unwrap();
+    assert!(approx_eq(body.linear_velocity.y, 60.0 * dt));
+    assert!(approx_eq(body.angular_velocity, 120.0 * dt));
+    assert_eq!(body.force(), vec2(0.0, 0.0));
+    assert_eq!(body.torque(), 0.0);
+
+    let body = bodies.get(damped).unwrap();
+    assert!(approx_eq(body.linear_velocity.x, 10.0 / (1.0 + dt)));
+    assert!(approx_eq(body.angular_velocity, 4.0 / (1.0 + dt)));
+}
+
+#[test]
+fn test_impulses_and_mass_properties() {
+    let mut body = RigidBody2D::dynamic().with_mass(4.0).with_inertia(2.0);
+    body.apply_impulse_at_point(vec2(0.0, 8.0), point2(2.0, 1.0), point2(1.0, 1.0));
+    assert_eq!(body.linear_velocity, vec2(0.0, 2.0));
+    assert!(approx_eq(body.angular_velocity, 4.0));
+
+    let mut fixed_rotation = RigidBody2D::dynamic().with_fixed_rotation();
+    fixed_rotation.apply_angular_impulse(3.0);
+    assert_eq!(fixed_rotation.angular_velocity, 0.0);
+    assert_eq!(RigidBody2D::kinematic().inv_mass(), 0.0);
+    assert_eq!(RigidBody2D::dynamic().with_mass(0.0).inv_mass(), 0.0);
+
+    let circle: Shape = Circle::new(2.0).into();
+    assert!(approx_eq(circle.inertia(3.0), 6.0));
+    let square: Shape = AxisAlignedBox::new(vec2(1.0, 1.0)).into();
+    assert!(approx_eq(square.inertia(3.0), 2.0));
+    let polygon: Shape = ConvexPolygon::new(&[
+        point2(-1.0, -1.0),
+        point2(1.0, -1.0),
+        point2(1.0, 1.0),
+        point2(-1.0, 1.0),
+    ])
+    .unwrap()
+    .into();
+    assert!(approx_eq(polygon.inertia(3.0), 2.0));
+}
+
+fn add_box(
+    world: &mut World,
+    x: f32,
+    y: f32,
+    half_extents: (f32, f32),
+    body: Option<RigidBody2D>,
+) -> Entity {
+    let collider = Collider::new(AxisAlignedBox::new(vec2(half_extents.0, half_extents.1)));
+    let builder = world
+        .create_entity()
+        .with(Transform2D {
+            position: point2(x, y),
+            ..Default::default()
+        })
+        .with(collider);
+
+    match body {
+        Some(body) => builder.with(body).build(),
+        None => builder.build(),
+    }
+}
+
+#[test]
+fn test_box_stack_stays_stable() {
+    let mut world = physics_world(-10.0);
+    add_box(&mut world, 0.0, -0.5, (10.0, 0.5), None);
+
+    let inertia = Shape::from(AxisAlignedBox::new(vec2(0.5, 0.5))).inertia(1.0);
+    let boxes: Vec<_> = (0..5)
+        .map(|i| {
+            let body = RigidBody2D::dynamic().with_inertia(inertia);
+            add_box(&mut world, 0.0, 0.5 + i as f32, (0.5, 0.5), Some(body))
+        })
+        .collect();
+
+    step(&mut world, 300);
+
+    let transforms = world.read_storage::<Transform2D>();
+    let bodies = world.read_storage::<RigidBody2D>();
+    for (i, &entity) in boxes.iter().enumerate() {
+        let transform = transforms.get(entity).unwrap();
+        let body = bodies.get(entity).unwrap();
+        assert!(
+            transform.position.x.abs() < 0.01,
+            "Box {i} drifted to {:?}",
+            transform.position
+        );
+        assert!(
+            (transform.position.y - (0.5 + i as f32)).abs() < 0.05,
+            "Box {i} at {:?}",
+            transform.position
+        );
+        assert!(transform.rotation.abs() < 0.01);
+        assert!(
+            body.linear_velocity.magnitude() < 0.05,
+            "Box {i} moving at {:?}",
+            body.linear_velocity
+        );
+    }
+}
+
+#[test]
+fn test_friction_stops_sliding_box() {
+    let mut world = physics_world(-10.0);
+    let (rough, slick) = {
+        let mut materials = world.write_resource::<PhysicsMaterials>();
+        (
+            materials.add(PhysicsMaterial::new(1.0, 0.0, 1.0)),
+            materials.add(
+                PhysicsMaterial::new(0.0, 0.0, 1.0)
+                    .with_combine(CombineRule::Min, CombineRule::Max),
+            ),
+        )
+    };
+    world
+        .create_entity()
+        .with(Transform2D {
+            position: point2(0.0, -0.5),
+            ..Default::default()
+        })
+        .with(Collider::new(AxisAlignedBox::new(vec2(100.0, 0.5))).with_material(rough))
+        .build();
+
+    let sliding = add_box(
+        &mut world,
+        -20.0,
+        0.5,
+        (0.5, 0.5),
+        Some(
+            RigidBody2D::dynamic()
+                .with_velocity(vec2(5.0, 0.0), 0.0)
+                .with_fixed_rotation(),
+        ),
+    );
+    let gliding = world
+        .create_entity()
+        .with(Transform2D {
+            position: point2(20.0, 0.5),
+            ..Default::default()
+        })
+        .with(Collider::new(AxisAlignedBox::new(vec2(0.5, 0.5))).with_material(slick))
+        .with(
+            RigidBody2D::dynamic()
+                .with_velocity(vec2(5.0, 0.0), 0.0)
+                .with_fixed_rotation(),
+        )
+        .build();
+
+    step(&mut world, 120);
+
+    // Averaging 1 and 0.5 the box decelerates at 7.5 units/s^2 and stops in under a second,
+    // while the minimum of 0 and 1 lets the other one glide
+    let bodies = world.read_storage::<RigidBody2D>();
+    assert!(bodies.get(sliding).unwrap().linear_velocity.x.abs() < 0.01);
+    assert!(approx_eq(
+        bodies.get(gliding).unwrap().linear_velocity.x,
+        5.0
+    ));
+}
+
+#[test]
+fn test_restitution_bounces() {
+    let mut world = physics_world(0.0);
+    add_box(&mut world, 0.0, -0.5, (10.0, 0.5), None);
+
+    let ball = |world: &mut World, restitution| {
+        let material = PhysicsMaterial::new(0.5, restitution, 1.0);
+        let material = world.write_resource::<PhysicsMaterials>().add(material);
+        Collider::new(Circle::new(0.5)).with_material(material)
+    };
+    let (bouncy_ball, dull_ball) = (ball(&mut world, 1.0), ball(&mut world, 0.0));
+    let bouncy = world
+        .create_entity()
+        .with(Transform2D {
+            position: point2(-2.0, 0.6),
+            ..Default::default()
+        })
+        .with(bouncy_ball)
+        .with(RigidBody2D::dynamic().with_velocity(vec2(0.0, -6.0), 0.0))
+        .build();
+    let dull = world
+        .create_entity()
+        .with(Transform2D {
+            position: point2(2.0, 0.6),
+            ..Default::default()
+        })
+        .with(dull_ball)
+        .with(RigidBody2D::dynamic().with_velocity(vec2(0.0, -6.0), 0.0))
+        .build();
+
+    step(&mut world, 10);
+
+    let bodies = world.read_storage::<RigidBody2D>();
+    let bounced = bodies.get(bouncy).unwrap().linear_velocity.y;
+    assert!((bounced - 6.0).abs() < 0.1, "Got {bounced}");
+    assert!(bodies.get(dull).unwrap().linear_velocity.y.abs() < 0.5);
+}
+
+#[test]
+fn test_warm_starting_converges_with_fewer_iterations() {
+    let settle = |warm_starting| {
+        let mut world = physics_world(-10.0);
+        world.insert(SolverSettings {
+            velocity_iterations: 2,
+            warm_starting,
+            ..Default::default()
+        });
+        add_box(&mut world, 0.0, -0.5, (10.0, 0.5), None);
+        let boxes: Vec<_> = (0..5)
+            .map(|i| {
+                add_box(
+                    &mut world,
+                    0.0,
+                    0.5 + i as f32,
+                    (0.5, 0.5),
+                    Some(RigidBody2D::dynamic()),
+                )
+            })
+            .collect();
+
+        step(&mut world, 120);
+
+        let transforms = world.read_storage::<Transform2D>();
+        (transforms.get(boxes[4]).unwrap().position.y - 4.5).abs()
+    };
+
+    assert!(settle(true) < settle(false));
+}
+
+#[test]
+fn test_distance_and_rope_joints() {
+    let mut world = physics_world(-10.0);
+    let pivot = add_anchor(&mut world, 0.0, 0.0);
+    let pendulum = add_body_at(&mut world, 2.0, 0.0, RigidBody2D::dynamic());
+    add_joint(&mut world, Joint2D::distance(pivot, pendulum, 2.0));
+
+    let rope_pivot = add_anchor(&mut world, 10.0, 0.0);
+    let hanging = add_body_at(&mut world, 10.0, -1.0, RigidBody2D::dynamic());
+    add_joint(&mut world, Joint2D::rope(rope_pivot, hanging, 2.0));
+
+    // The slack rope lets the body fall freely
+    step(&mut world, 1);
+    let dt = world.read_resource::<Time>().fixed_timestep;
+    let velocity = world
+        .read_storage::<RigidBody2D>()
+        .get(hanging)
+        .unwrap()
+        .linear_velocity;
+    assert!(approx_eq(velocity.y, -10.0 * dt));
+
+    for _ in 0..120 {
+        step(&mut world, 1);
+        let length = position(&world, pendulum)
+            .position
+            .distance(point2(0.0, 0.0));
+        assert!((length - 2.0).abs() < 0.05, "Rod stretched to {length}");
+    }
+
+    let rope = position(&world, hanging).position;
+    assert!(
+        (rope.distance(point2(10.0, 0.0)) - 2.0).abs() < 0.05,
+        "Rope end at {rope:?}"
+    );
+}
+
+#[test]
+fn test_revolute_joint_motor_and_limits() {
+    let mut world = physics_world(0.0);
+    let frame = add_anchor(&mut world, 0.0, 0.0);
+    let door = add_body_at(&mut world, 1.0, 0.0, RigidBody2D::dynamic());
+    add_joint(
+        &mut world,
+        Joint2D::revolute(frame, door)
+            .with_anchors(vec2(0.0, 0.0), vec2(-1.0, 0.0))
+            .with_motor(1.0, 100.0)
+            .with_limits(-0.5, 0.5),
+    );
+
+    step(&mut world, 30);
+    let transform = position(&world, door);
+    assert!(
+        (transform.rotation - 0.5).abs() < 0.05,
+        "Door opened to {}",
+        transform.rotation
+    );
+
+    step(&mut world, 60);
+    let transform = position(&world, door);
+    assert!(
+        (transform.rotation - 0.5).abs() < 0.02,
+        "Door opened to {}",
+        transform.rotation
+    );
+
+    // The hinge stays in place: the door center is 1 unit away from it
+    let hinge = transform.position + rotate_vec2(vec2(-1.0, 0.0), rad(transform.rotation));
+    assert!(
+        hinge.distance(point2(0.0, 0.0)) < 0.02,
+        "Hinge moved to {hinge:?}"
+    );
+}
+
+#[test]
+fn test_prismatic_joint() {
+    let mut world = physics_world(-10.0);
+    let rail = add_anchor(&mut world, 0.0, 0.0);
+    let slider = add_body_at(&mut world, 0.0, 0.0, RigidBody2D::dynamic());
+    add_joint(
+        &mut world,
+        Joint2D::prismatic(rail, slider, vec2(1.0, 0.0))
+            .with_motor(2.0, 100.0)
+            .with_limits(-1.0, 1.0),
+    );
+
+    step(&mut world, 15);
+    let transform = position(&world, slider);
+    assert!(
+        (transform.position.x - 0.5).abs() < 0.05,
+        "Slider at {:?}",
+        transform.position
+    );
+    assert!(transform.position.y.abs() < 0.01);
+
+    step(&mut world, 60);
+    let transform = position(&world, slider);
+    assert!(
+        (transform.position.x - 1.0).abs() < 0.02,
+        "Slider at {:?}",
+        transform.position
+    );
+    assert!(transform.position.y.abs() < 0.01 && transform.rotation.abs() < 0.01);
+}
+
+#[test]
+fn test_weld_joint_ignores_connected_collisions() {
+    let mut world = physics_world(0.0);
+    let a = add_box(
+        &mut world,
+        0.0,
+        0.0,
+        (0.5, 0.5),
+        Some(RigidBody2D::dynamic()),
+    );
+    let b = add_box(
+        &mut world,
+        0.5,
+        0.0,
+        (0.5, 0.5),
+        Some(RigidBody2D::dynamic()),
+    );
+    add_joint(
+        &mut world,
+        Joint2D::weld(a, b).with_anchors(vec2(0.25, 0.0), vec2(-0.25, 0.0)),
+    );
+
+    world
+        .write_storage::<RigidBody2D>()
+        .get_mut(a)
+        .unwrap()
+        .apply_impulse(vec2(2.0, 0.0));
+    step(&mut world, 60);
+
+    // Both boxes move together at half the speed of the pushed box alone
+    let (ta, tb) = (position(&world, a), position(&world, b));
+    assert!(
+        (ta.position.x - 1.0).abs() < 0.02,
+        "Box at {:?}",
+        ta.position
+    );
+    assert!((tb.position - ta.position - vec2(0.5, 0.0)).magnitude() < 0.01);
+    assert!(ta.rotation.abs() < 0.01 && tb.rotation.abs() < 0.01);
+}
+
+#[test]
+fn test_spring_and_mouse_drag() {
+    let mut world = physics_world(-10.0);
+    let ceiling = add_anchor(&mut world, 0.0, 0.0);
+    let weight = add_body_at(&mut world, 0.0, -1.0, RigidBody2D::dynamic().with_mass(2.0));
+    add_joint(
+        &mut world,
+        Joint2D::spring(ceiling, weight, 1.0, 100.0, 10.0),
+    );
+
+    let dragged = add_body_at(
+        &mut world,
+        5.0,
+        0.0,
+        RigidBody2D::dynamic().with_gravity_scale(0.0),
+    );
+    add_joint(
+        &mut world,
+        Joint2D::mouse_drag(dragged, point2(8.0, 2.0), 1000.0),
+    );
+
+    step(&mut world, 300);
+
+    // At rest the spring stretches by m * g / k
+    let y = position(&world, weight).position.y;
+    assert!((y + 1.2).abs() < 0.01, "Weight at {y}");
+
+    let p = position(&world, dragged).position;
+    assert!(p.distance(point2(8.0, 2.0)) < 0.01, "Dragged to {p:?}");
+}
+
+#[test]
+fn test_resting_bodies_fall_asleep_and_wake_up() {
+    let mut world = physics_world(-10.0);
+    add_box(&mut world, 0.0, -0.5, (10.0, 0.5), None);
+    let a = add_box(
+        &mut world,
+        -3.0,
+        0.5,
+        (0.5, 0.5),
+        Some(RigidBody2D::dynamic()),
+    );
+    let b = add_box(
+        &mut world,
+        3.0,
+        0.5,
+        (0.5, 0.5),
+        Some(RigidBody2D::dynamic()),
+    );
+    let awake = add_box(
+        &mut world,
+        6.0,
+        0.5,
+        (0.5, 0.5),
+        Some(RigidBody2D::dynamic().never_sleep()),
+    );
+
+    step(&mut world, 120);
+
+    let is_sleeping = |world: &World, e| {
+        world
+            .read_storage::<RigidBody2D>()
+            .get(e)
+            .unwrap()
+            .is_sleeping()
+    };
+    assert!(is_sleeping(&world, a) && is_sleeping(&world, b));
+    assert!(!is_sleeping(&world, awake));
+    assert_eq!(
+        *world.read_resource::<PhysicsStats>(),
+        PhysicsStats {
+            awake_bodies: 1,
+            sleeping_bodies: 2,
+            islands: 3
+        }
+    );
+
+    // Forces wake the body up
+    world
+        .write_storage::<RigidBody2D>()
+        .get_mut(a)
+        .unwrap()
+        .apply_force(vec2(100.0, 0.0));
+    step(&mut world, 1);
+    assert!(!is_sleeping(&world, a));
+    assert!(position(&world, a).position.x > -3.0);
+
+    // So does moving the transform
+    world
+        .write_storage::<Transform2D>()
+        .get_mut(b)
+        .unwrap()
+        .position
+        .y = 2.0;
+    step(&mut world, 1);
+    assert!(!is_sleeping(&world, b));
+    assert!(position(&world, b).position.y < 2.0);
+}
+
+#[test]
+fn test_contact_wakes_sleeping_island() {
+    let mut world = physics_world(-10.0);
+    add_box(&mut world, 0.0, -0.5, (10.0, 0.5), None);
+    let stack: Vec<_> = (0..2)
+        .map(|i| {
+            add_box(
+                &mut world,
+                0.0,
+                0.5 + i as f32,
+                (0.5, 0.5),
+                Some(RigidBody2D::dynamic()),
+            )
+        })
+        .collect();
+
+    step(&mut world, 120);
+    let bodies = world.read_storage::<RigidBody2D>();
+    assert!(stack.iter().all(|&e| bodies.get(e).unwrap().is_sleeping()));
+    drop(bodies);
+
+    // A box dropped on the stack joins its island and wakes it up
+    let falling = add_box(
+        &mut world,
+        0.0,
+        4.0,
+        (0.5, 0.5),
+        Some(RigidBody2D::dynamic()),
+    );
+    let mut woken = false;
+    for _ in 0..60 {
+        step(&mut world, 1);
+        let bodies = world.read_storage::<RigidBody2D>();
+        woken |= stack.iter().all(|&e| !bodies.get(e).unwrap().is_sleeping());
+    }
+    assert!(woken);
+
+    let islands = world.read_resource::<Islands>();
+    let island = islands.island_of(falling).unwrap();
+    assert!(stack.iter().all(|e| island.contains(e)));
+    assert!((position(&world, falling).position.y - 2.5).abs() < 0.05);
+}
+
+#[test]
+fn test_islands_group_linked_bodies() {
+    let mut world = World::new();
+    let entities: Vec<_> = (0..5).map(|_| world.create_entity().build()).collect();
+    let outside = world.create_entity().build();
+    let [a, b, c, d, e] = entities[..] else {
+        unreachable!()
+    };
+
+    let islands = Islands::build(
+        entities.clone(),
+        [(a, b), (c, b), (d, outside), (outside, e)],
+    );
+
+    assert_eq!(islands.len(), 3);
+    let mut island = islands.island_of(c).unwrap().to_vec();
+    island.sort();
+    assert_eq!(island, vec![a, b, c]);
+    assert_eq!(islands.island_of(d).unwrap(), &[d]);
+    assert_eq!(islands.island_of(e).unwrap(), &[e]);
+    assert!(islands.island_of(outside).is_none());
+}
+
+#[test]
+fn test_ccd_stops_fast_bodies_at_thin_walls() {
+    let mut world = physics_world(0.0);
+    add_box(&mut world, 5.0, 0.0, (0.05, 5.0), None);
+
+    let bullet = |ccd: bool| {
+        let body = RigidBody2D::dynamic().with_velocity(vec2(300.0, 0.0), 0.0);
+        if ccd { body.with_ccd() } else { body }
+    };
+    let tunnelling = add_box(&mut world, 0.0, -2.0, (0.1, 0.1), Some(bullet(false)));
+    let stopped = add_box(&mut world, 0.0, 2.0, (0.1, 0.1), Some(bullet(true)));
+
+    step(&mut world, 2);
+
+    assert!(position(&world, tunnelling).position.x > 5.0);
+    let x = position(&world, stopped).position.x;
+    assert!((x - 4.85).abs() < 0.01, "Bullet at {x}");
+    let bodies = world.read_storage::<RigidBody2D>();
+    assert!(bodies.get(stopped).unwrap().linear_velocity.x.abs() < 1e-3);
+}
+
+#[test]
+fn test_ccd_bounces_and_slides_off_walls() {
+    let fire = |restitution: f32, velocity| {
+        let mut world = physics_world(0.0);
+        let wall = add_box(&mut world, 5.0, 0.0, (0.05, 50.0), None);
+        let material = world
+            .write_resource::<PhysicsMaterials>()
+            .add(PhysicsMaterial::new(0.5, restitution, 1.0));
+        world
+            .write_storage::<Collider>()
+            .get_mut(wall)
+            .unwrap()
+            .material = material;
+
+        let body = RigidBody2D::dynamic()
+            .with_velocity(velocity, 0.0)
+            .with_ccd();
+        let bullet = add_box(&mut world, 0.0, 0.0, (0.1, 0.1), Some(body));
+        step(&mut world, 1);
+
+        let velocity = world
+            .read_storage::<RigidBody2D>()
+            .get(bullet)
+            .unwrap()
+            .linear_velocity;
+        (position(&world, bullet).position, velocity)
+    };
+
+    // Bounces back over the rest of the step without losing speed
+    let (p, v) = fire(1.0, vec2(600.0, 0.0));
+    assert!(approx_eq(v.x, -600.0) && approx_eq(v.y, 0.0));
+    assert!((p.x + 0.3).abs() < 0.01, "Bounced to {p:?}");
+
+    // Slides along the wall, keeping the tangential velocity
+    let (p, v) = fire(0.0, vec2(600.0, 600.0));
+    assert!(
+        v.x.abs() < 0.5 && (v.y - 600.0).abs() < 0.5,
+        "Sliding at {v:?}"
+    );
+    assert!(
+        (p.x - 4.85).abs() < 0.01 && (p.y - 10.0).abs() < 0.01,
+        "Slid to {p:?}"
+    );
+}
+
+fn add_character(world: &mut World, x: f32, y: f32, controller: CharacterController2D) -> Entity {
+    world
+        .create_entity()
+        .with(Transform2D {
+            position: point2(x, y),
+            ..Default::default()
+        })
+        .with(Collider::new(AxisAlignedBox::new(vec2(0.25, 0.5))))
+        .with(controller)
+        .build()
+}
+
+fn controller(world: &World, e: Entity) -> CharacterController2D {
+    world
+        .read_storage::<CharacterController2D>()
+        .get(e)
+        .unwrap()
+        .clone()
+}
+
+/// Steps the world, letting `control` drive the character before each step
+fn run_character(
+    world: &mut World,
+    character: Entity,
+    steps: usize,
+    mut control: impl FnMut(&mut CharacterController2D),
+) {
+    for _ in 0..steps {
+        control(
+            world
+                .write_storage::<CharacterController2D>()
+                .get_mut(character)
+                .unwrap(),
+        );
+        CharacterControllerSystem.run_now(world);
+        step(world, 1);
+    }
+}
+
+fn walk(speed: f32) -> impl FnMut(&mut CharacterController2D) {
+    move |controller| {
+        controller.velocity.x = speed;
+        controller.velocity.y -= 10.0 / 60.0;
+    }
+}
+
+#[test]
+fn test_character_lands_and_walks() {
+    let mut world = physics_world(-10.0);
+    let floor = add_box(&mut world, 0.0, -0.5, (20.0, 0.5), None);
+    let character = add_character(&mut world, 0.0, 2.0, CharacterController2D::new());
+
+    run_character(&mut world, character, 60, walk(0.0));
+    let state = controller(&world, character);
+    assert!(state.is_on_floor() && state.is_grounded());
+    assert_eq!(state.floor_entity(), Some(floor));
+    assert!(approx_eq(state.velocity.y, 0.0));
+    let p = position(&world, character).position;
+    assert!((p.y - 0.51).abs() < 0.01, "Landed at {p:?}");
+
+    run_character(&mut world, character, 60, walk(3.0));
+    let p = position(&world, character).position;
+    assert!(
+        (p.x - 3.0).abs() < 0.01 && (p.y - 0.51).abs() < 0.01,
+        "Walked to {p:?}"
+    );
+    assert!(controller(&world, character).is_on_floor());
+}
+
+#[test]
+fn test_character_walks_up_gentle_slopes_only() {
+    let ramp = |world: &mut World, x: f32, angle: f32| {
+        let top = point2(4.0, 4.0 * angle.tan());
+        let shape = ConvexPolygon::new(&[point2(0.0, 0.0), point2(4.0, 0.0), top]).unwrap();
+        world
+            .create_entity()
+            .with(Transform2D {
+                position: point2(x, 0.0),
+                ..Default::default()
+            })
+            .with(Collider::new(shape))
+            .build();
+    };
+
+    let mut world = physics_world(-10.0);
+    add_box(&mut world, 0.0, -0.5, (20.0, 0.5), None);
+    ramp(&mut world, 1.0, 30f32.to_radians());
+    let character = add_character(&mut world, 0.0, 0.51, CharacterController2D::new());
+    run_character(&mut world, character, 60, walk(3.0));
+
+    let state = controller(&world, character);
+    let p = position(&world, character).position;
+    assert!(p.y > 1.0 && state.is_on_floor(), "Climbed to {p:?}");
+    let normal = state.floor_normal().unwrap();
+    assert!((normal - vec2(-0.5, 0.75f32.sqrt())).magnitude() < 0.01);
+
+    let mut world = physics_world(-10.0);
+    add_box(&mut world, 0.0, -0.5, (20.0, 0.5), None);
+    ramp(&mut world, 1.0, 60f32.to_radians());
+    let character = add_character(&mut world, 0.0, 0.51, CharacterController2D::new());
+    run_character(&mut world, character, 60, walk(3.0));
+
+    let state = controller(&world, character);
+    let p = position(&world, character).position;
+    assert!(p.y < 0.6 && p.x < 1.0, "Climbed to {p:?}");
+    assert!(state.is_on_floor() && state.is_on_wall());
+}
+
+#[test]
+fn test_character_steps_up_ledges() {
+    let climb = |height: f32| {
+        let mut world = physics_world(-10.0);
+        add_box(&mut world, 0.0, -0.5, (20.0, 0.5), None);
+        add_box(&mut world, 3.0, height / 2.0, (1.0, height / 2.0), None);
+        let character = add_character(&mut world, 0.0, 0.51, CharacterController2D::new());
+        run_character(&mut world, character, 60, walk(3.0));
+        (
+            position(&world, character).position,
+            controller(&world, character),
+        )
+    };
+
+    let (p, state) = climb(0.2);
+    assert!(
+        (p.x - 3.0).abs() < 0.05 && (p.y - 0.71).abs() < 0.01,
+        "Stepped to {p:?}"
+    );
+    assert!(state.is_on_floor() && !state.is_on_wall());
+
+    let (p, state) = climb(0.5);
+    assert!(
+        (p.x - 1.74).abs() < 0.01 && (p.y - 0.51).abs() < 0.01,
+        "Stopped at {p:?}"
+    );
+    assert!(state.is_on_floor() && state.is_on_wall());
+    assert_eq!(state.wall_normal(), Some(vec2(-1.0, 0.0)));
+}
+
+#[test]
+fn test_character_bumps_into_ceilings() {
+    let mut world = physics_world(-10.0);
+    add_box(&mut world, 0.0, -0.5, (20.0, 0.5), None);
+    add_box(&mut world, 0.0, 2.5, (20.0, 0.5), None);
+    let character = add_character(&mut world, 0.0, 0.51, CharacterController2D::new());
+
+    let mut bumped = false;
+    run_character(&mut world, character, 1, |c| {
+        c.velocity.y = 20.0;
+        c.leave_ground();
+    });
+    for _ in 0..10 {
+        run_character(&mut world, character, 1, walk(0.0));
+        bumped |= controller(&world, character).is_on_ceiling();
+    }
+
+    assert!(bumped);
+    let state = controller(&world, character);
+    assert!(state.velocity.y <= 0.0);
+    assert!(position(&world, character).position.y < 1.5);
+}
+
+#[test]
+fn test_character_rides_moving_platforms() {
+    let mut world = physics_world(-10.0);
+    let platform = add_box(
+        &mut world,
+        0.0,
+        0.0,
+        (2.0, 0.25),
+        Some(RigidBody2D::kinematic().with_velocity(vec2(2.0, 1.0), 0.0)),
+    );
+    // Puts the platform in the broad phase, so the character lands on it right away
+    CollisionSystem.run_now(&world);
+    let character = add_character(&mut world, 0.0, 0.76, CharacterController2D::new());
+
+    run_character(&mut world, character, 60, walk(0.0));
+
+    let state = controller(&world, character);
+    assert_eq!(state.floor_entity(), Some(platform));
+    let offset = position(&world, character).position - position(&world, platform).position;
+    assert!(offset.x.abs() < 0.05, "Left behind by {offset:?}");
+    assert!((offset.y - 0.76).abs() < 0.05, "Left behind by {offset:?}");
+}
+
+#[test]
+fn test_character_coyote_time() {
+    let mut world = physics_world(-10.0);
+    add_box(&mut world, -10.0, -0.5, (10.0, 0.5), None);
+    let character = add_character(&mut world, -1.0, 0.51, CharacterController2D::new());
+
+    // Walking off the ledge keeps the character grounded for a moment
+    let mut coyote_steps = 0;
+    for _ in 0..60 {
+        run_character(&mut world, character, 1, walk(3.0));
+        let state = controller(&world, character);
+        if !state.is_on_floor() && state.is_grounded() {
+            coyote_steps += 1;
+        }
+    }
+    assert_eq!(coyote_steps, 6);
+    assert!(!controller(&world, character).is_grounded());
+
+    // Jumping ends it right away
+    let character = add_character(&mut world, -5.0, 0.51, CharacterController2D::new());
+    run_character(&mut world, character, 10, walk(0.0));
+    assert!(controller(&world, character).is_grounded());
+    run_character(&mut world, character, 1, |c| {
+        c.velocity.y = 5.0;
+        c.leave_ground();
+    });
+    assert!(!controller(&world, character).is_grounded());
+}
+
+#[test]
+fn test_top_down_character_slides_along_walls() {
+    let mut world = physics_world(0.0);
+    add_box(&mut world, 3.0, 0.0, (1.0, 20.0), None);
+    let character = add_character(&mut world, 0.0, 0.0, CharacterController2D::top_down());
+
+    run_character(&mut world, character, 60, |c| c.velocity = vec2(3.0, 3.0));
+
+    let state = controller(&world, character);
+    assert!(state.is_on_wall() && !state.is_on_floor() && !state.is_grounded());
+    let p = position(&world, character).position;
+    assert!(
+        (p.x - 1.74).abs() < 0.01 && (p.y - 3.0).abs() < 0.01,
+        "Slid to {p:?}"
+    );
+}
+
+#[test]
+fn test_material_combine_rules() {
+    let material =
+        |value: f32, rule| PhysicsMaterial::new(value, value, 1.0).with_combine(rule, rule);
+    let average = material(0.2, CombineRule::Average);
+    let min = material(0.4, CombineRule::Min);
+    let multiply = material(0.5, CombineRule::Multiply);
+    let max = material(0.8, CombineRule::Max);
+
+    assert!(approx_eq(average.combined_friction(&average), 0.2));
+    assert!(approx_eq(average.combined_friction(&max), 0.8));
+    assert!(approx_eq(average.combined_restitution(&min), 0.2));
+    assert!(approx_eq(min.combined_friction(&multiply), 0.2));
+    assert!(approx_eq(multiply.combined_restitution(&average), 0.1));
+    assert!(approx_eq(max.combined_friction(&min), 0.8));
+
+    // Unknown handles and the default handle resolve to the editable default material
+    let mut materials = PhysicsMaterials::default();
+    let handle = materials.add(max);
+    assert_eq!(*materials.get(handle), max);
+    materials.get_mut(Default::default()).unwrap().friction = 0.1;
+    assert_eq!(materials.get(Default::default()).friction, 0.1);
+}
+
+#[test]
+fn test_mass_from_collider_density() {
+    let mut world = physics_world(-10.0);
+    let heavy = world
+        .write_resource::<PhysicsMaterials>()
+        .add(PhysicsMaterial::new(0.5, 0.0, 4.0));
+
+    let computed = world
+        .create_entity()
+        .with(Transform2D::default())
+        .with(Collider::new(AxisAlignedBox::new(vec2(0.5, 1.0))).with_material(heavy))
+        .with(RigidBody2D::dynamic().with_gravity_scale(0.0))
+        .build();
+    let custom = world
+        .create_entity()
+        .with(Transform2D::default())
+        .with(Collider::new(Circle::new(1.0)).with_material(heavy))
+        .with(
+            RigidBody2D::dynamic()
+                .with_mass(3.0)
+                .with_gravity_scale(0.0),
+        )
+        .build();
+    let scaled = world
+        .create_entity()
+        .with(Transform2D {
+            scale: vec2(2.0, 3.0),
+            ..Default::default()
+        })
+        .with(Collider::new(AxisAlignedBox::new(vec2(0.5, 1.0))).with_material(heavy))
+        .with(RigidBody2D::dynamic().with_gravity_scale(0.0))
+        .build();
+
+    step(&mut world, 1);
+
+    let bodies = world.read_storage::<RigidBody2D>();
+    let body = bodies.get(computed).unwrap();
+    assert!(approx_eq(body.mass(), 8.0));
+    assert!(approx_eq(body.inertia(), 8.0 * 1.25 / 3.0));
+
+    // The mass follows the collider scaled by the transform, like its contacts
+    let body = bodies.get(scaled).unwrap();
+    assert!(approx_eq(body.mass(), 48.0));
+    assert!(approx_eq(body.inertia() / 160.0, 1.0));
+
+    // Masses set by hand are kept, the inertia still follows the collider
+    let body = bodies.get(custom).unwrap();
+    assert!(approx_eq(body.mass(), 3.0));
+    assert!(approx_eq(body.inertia(), 1.5));
+}
+
+fn add_zone(
+    world: &mut World,
+    x: f32,
+    y: f32,
+    shape: impl Into<Shape>,
+    effector: AreaEffector2D,
+) -> Entity {
+    world
+        .create_entity()
+        .with(Transform2D {
+            position: point2(x, y),
+            ..Default::default()
+        })
+        .with(Collider::new(shape).trigger())
+        .with(effector)
+        .build()
+}
+
+fn velocity(world: &World, e: Entity) -> Vec2 {
+    world
+        .read_storage::<RigidBody2D>()
+        .get(e)
+        .unwrap()
+        .linear_velocity
+}
+
+#[test]
+fn test_gravity_zones() {
+    let mut world = physics_world(-10.0);
+    add_zone(
+        &mut world,
+        0.0,
+        0.0,
+        AxisAlignedBox::new(vec2(5.0, 50.0)),
+        AreaEffector2D::gravity(vec2(0.0, 10.0)),
+    );
+    add_zone(
+        &mut world,
+        20.0,
+        0.0,
+        AxisAlignedBox::new(vec2(5.0, 50.0)),
+        AreaEffector2D::new(EffectorKind::Gravity {
+            acceleration: vec2(5.0, 0.0),
+            replace_global: false,
+        }),
+    );
+
+    let body = || Some(RigidBody2D::dynamic().never_sleep());
+    let inside = add_box(&mut world, 0.0, 0.0, (0.5, 0.5), body());
+    let sideways = add_box(&mut world, 20.0, 0.0, (0.5, 0.5), body());
+    let outside = add_box(&mut world, 40.0, 0.0, (0.5, 0.5), body());
+
+    step(&mut world, 60);
+
+    assert!((velocity(&world, inside) - vec2(0.0, 10.0)).magnitude() < 0.01);
+    assert!((velocity(&world, sideways) - vec2(5.0, -10.0)).magnitude() < 0.01);
+    assert!((velocity(&world, outside) - vec2(0.0, -10.0)).magnitude() < 0.01);
+}
+
+#[test]
+fn test_radial_effectors_attract_and_repel() {
+    let mut world = physics_world(0.0);
+    let field = |strength, falloff| AreaEffector2D::radial(strength, falloff);
+    add_zone(
+        &mut world,
+        0.0,
+        0.0,
+        Circle::new(5.0),
+        field(6.0, Falloff::Constant),
+    );
+    add_zone(
+        &mut world,
+        20.0,
+        0.0,
+        Circle::new(5.0),
+        field(-6.0, Falloff::Linear { range: 4.0 }),
+    );
+    add_zone(
+        &mut world,
+        40.0,
+        0.0,
+        Circle::new(5.0),
+        field(8.0, Falloff::InverseSquare),
+    );
+
+    let body =
+        |world: &mut World, x| add_box(world, x, 0.0, (0.1, 0.1), Some(RigidBody2D::dynamic()));
+    let attracted = body(&mut world, 3.0);
+    let repelled = body(&mut world, 21.0);
+    let far = body(&mut world, 42.0);
+
+    step(&mut world, 1);
+
+    let dt = 1.0 / 60.0;
+    assert!((velocity(&world, attracted) - vec2(-6.0 * dt, 0.0)).magnitude() < 1e-4);
+    assert!((velocity(&world, repelled) - vec2(4.5 * dt, 0.0)).magnitude() < 1e-4);
+    assert!((velocity(&world, far) - vec2(-2.0 * dt, 0.0)).magnitude() < 1e-4);
+}
+
+#[test]
+fn test_wind_drags_bodies_along() {
+    let mut world = physics_world(0.0);
+    add_zone(
+        &mut world,
+        0.0,
+        0.0,
+        AxisAlignedBox::new(vec2(100.0, 10.0)),
+        AreaEffector2D::wind(vec2(4.0, 0.0), 2.0),
+    );
+    add_zone(
+        &mut world,
+        0.0,
+        30.0,
+        AxisAlignedBox::new(vec2(100.0, 10.0)),
+        AreaEffector2D::wind(vec2(4.0, 0.0), 2.0).with_turbulence(0.5, 2.0),
+    );
+    let steady = add_box(
+        &mut world,
+        0.0,
+        0.0,
+        (0.5, 0.5),
+        Some(RigidBody2D::dynamic()),
+    );
+    let gusty = add_box(
+        &mut world,
+        0.0,
+        30.0,
+        (0.5, 0.5),
+        Some(RigidBody2D::dynamic()),
+    );
+
+    step(&mut world, 300);
+    assert!((velocity(&world, steady) - vec2(4.0, 0.0)).magnitude() < 0.01);
+
+    let mut speeds = Vec::new();
+    for _ in 0..120 {
+        step(&mut world, 1);
+        speeds.push(velocity(&world, gusty).magnitude());
+    }
+    let (min, max) = speeds.iter().fold((f32::MAX, f32::MIN), |(min, max), &s| {
+        (min.min(s), max.max(s))
+    });
+    assert!(max - min > 0.2, "Steady wind between {min} and {max}");
+    assert!(min > 1.0 && max < 7.0, "Wind between {min} and {max}");
+}
+
+#[test]
+fn test_effectors_wake_sleeping_bodies() {
+    let mut world = physics_world(-10.0);
+    add_box(&mut world, 0.0, -0.5, (10.0, 0.5), None);
+    let pushed = add_box(
+        &mut world,
+        -3.0,
+        0.5,
+        (0.5, 0.5),
+        Some(RigidBody2D::dynamic()),
+    );
+    let calm = add_box(
+        &mut world,
+        3.0,
+        0.5,
+        (0.5, 0.5),
+        Some(RigidBody2D::dynamic()),
+    );
+    step(&mut world, 120);
+
+    let is_sleeping = |world: &World, e| {
+        world
+            .read_storage::<RigidBody2D>()
+            .get(e)
+            .unwrap()
+            .is_sleeping()
+    };
+    assert!(is_sleeping(&world, pushed) && is_sleeping(&world, calm));
+    let resting = position(&world, calm).position;
+
+    // Still air pushes nothing and lets the body sleep
+    add_zone(
+        &mut world,
+        -3.0,
+        0.5,
+        AxisAlignedBox::new(vec2(2.0, 2.0)),
+        AreaEffector2D::wind(vec2(4.0, 0.0), 2.0),
+    );
+    add_zone(
+        &mut world,
+        3.0,
+        0.5,
+        AxisAlignedBox::new(vec2(2.0, 2.0)),
+        AreaEffector2D::wind(vec2(0.0, 0.0), 2.0),
+    );
+    step(&mut world, 1);
+    assert!(!is_sleeping(&world, pushed));
+    assert!(is_sleeping(&world, calm));
+
+    step(&mut world, 10);
+    assert!(position(&world, pushed).position.x > -3.0);
+    assert_eq!(position(&world, calm).position, resting);
+}
+
+#[test]
+fn test_buoyancy_floats_light_bodies() {
+    let mut world = physics_world(-10.0);
+    add_zone(
+        &mut world,
+        0.0,
+        -10.0,
+        AxisAlignedBox::new(vec2(20.0, 10.0)),
+        AreaEffector2D::buoyancy(2.0, 2.0, 1.0),
+    );
+
+    // Half as dense as the fluid, floats half submerged
+    let cork = add_box(
+        &mut world,
+        -5.0,
+        2.0,
+        (0.5, 0.5),
+        Some(RigidBody2D::dynamic()),
+    );
+    let ball = add_body_at(&mut world, 5.0, 2.0, RigidBody2D::dynamic());
+    let heavy = world
+        .write_resource::<PhysicsMaterials>()
+        .add(PhysicsMaterial::new(0.5, 0.0, 3.0));
+    world
+        .write_storage::<Collider>()
+        .insert(ball, Collider::new(Circle::new(0.5)).with_material(heavy))
+        .unwrap();
+
+    step(&mut world, 600);
+
+    let y = position(&world, cork).position.y;
+    assert!(y.abs() < 0.02, "Floating at {y}");
+    assert!(position(&world, ball).position.y < -5.0);
+}
+
+fn soft_body(world: &World, e: Entity) -> SoftBody2D {
+    world.read_storage::<SoftBody2D>().get(e).unwrap().clone()
+}
+
+fn stretch(body: &SoftBody2D) -> f32 {
+    let particles = body.particles();
+    body.constraints()
+        .iter()
+        .map(|c| {
+            let length = particles[c.a].position.distance(particles[c.b].position);
+            (length / c.rest_length - 1.0).abs()
+        })
+        .fold(0.0, f32::max)
+}
+
+#[test]
+fn test_rope_hangs_from_pin() {
+    let mut world = physics_world(-10.0);
+    let rope = SoftBody2D::rope(point2(0.0, 0.0), point2(5.0, 0.0), 10)
+        .with_damping(2.0)
+        .pinned(0);
+    let rope = world.create_entity().with(rope).build();
+
+    step(&mut world, 600);
+
+    let rope = soft_body(&world, rope);
+    let particles = rope.particles();
+    assert_eq!(particles[0].position, point2(0.0, 0.0));
+    let end = particles[10].position;
+    assert!(
+        end.x.abs() < 0.05 && (end.y + 5.0).abs() < 0.1,
+        "Rope end at {end:?}"
+    );
+    assert!(stretch(&rope) < 0.02);
+}
+
+#[test]
+fn test_cloth_hangs_from_corners() {
+    let mut world = physics_world(-10.0);
+    let cloth = SoftBody2D::cloth(point2(0.0, 0.0), 6, 6, 0.2)
+        .pinned(0)
+        .pinned(5);
+    let cloth = world.create_entity().with(cloth).build();
+    let links = soft_body(&world, cloth).constraints().len();
+    assert_eq!(links, 2 * 6 * 5 + 2 * 5 * 5);
+
+    step(&mut world, 300);
+
+    let cloth = soft_body(&world, cloth);
+    let particles = cloth.particles();
+    assert_eq!(particles[0].position, point2(0.0, 0.0));
+    assert_eq!(particles[5].position, point2(1.0, 0.0));
+    assert!(particles[30..].iter().all(|p| p.position.y < -0.9));
+    assert_eq!(cloth.constraints().len(), links);
+}
+
+#[test]
+fn test_soft_body_rests_on_colliders() {
+    let mut world = physics_world(-10.0);
+    world
+        .create_entity()
+        .with(Transform2D {
+            position: point2(0.0, -1.5),
+            ..Default::default()
+        })
+        .with(Collider::new(AxisAlignedBox::new(vec2(5.0, 0.5))))
+        .build();
+    let rope = SoftBody2D::rope(point2(-2.0, 0.0), point2(2.0, 0.0), 8).with_radius(0.1);
+    let rope = world.create_entity().with(rope).build();
+
+    step(&mut world, 300);
+
+    let rope = soft_body(&world, rope);
+    let dt = world.read_resource::<Time>().fixed_timestep;
+    for particle in rope.particles() {
+        assert!(
+            (particle.position.y + 0.9).abs() < 0.01,
+            "Particle at {:?}",
+            particle.position
+        );
+        assert!(particle.velocity(dt).magnitude() < 0.01);
+    }
+}
+
+#[test]
+fn test_soft_body_pins_follow_entities() {
+    let mut world = physics_world(-10.0);
+    let hook = add_body(
+        &mut world,
+        RigidBody2D::kinematic().with_velocity(vec2(1.0, 0.0), 0.0),
+    );
+    let rope = SoftBody2D::rope(point2(0.5, 0.0), point2(0.5, -2.0), 4).pinned_to(
+        0,
+        hook,
+        point2(0.5, 0.0),
+    );
+    let rope = world.create_entity().with(rope).build();
+
+    step(&mut world, 60);
+
+    let hook_position = position(&world, hook).position;
+    assert!((hook_position.x - 1.0).abs() < 1e-4);
+    let start = soft_body(&world, rope).particles()[0].position;
+    assert!(start.distance(point2(1.5, 0.0)) < 1e-4);
+
+    // Deleting the entity releases the pin
+    world.delete_entity(hook).unwrap();
+    step(&mut world, 30);
+    let rope = soft_body(&world, rope);
+    assert!(rope.pins().is_empty());
+    assert!(rope.particles()[0].position.y < -0.5);
+}
+
+#[test]
+fn test_soft_body_tears_when_overstretched() {
+    let mut world = physics_world(0.0);
+    let hook = add_body(
+        &mut world,
+        RigidBody2D::kinematic().with_velocity(vec2(30.0, 0.0), 0.0),
+    );
+    let make_rope = || {
+        SoftBody2D::rope(point2(-4.0, 0.0), point2(0.0, 0.0), 8)
+            .pinned(0)
+            .pinned_to(8, hook, point2(0.0, 0.0))
+    };
+    let tearing = world
+        .create_entity()
+        .with(make_rope().with_tearing(1.5))
+        .build();
+    let elastic = world.create_entity().with(make_rope()).build();
+
+    step(&mut world, 20);
+
+    let tearing = soft_body(&world, tearing);
+    assert!(tearing.constraints().len() < 8);
+    assert!(stretch(&tearing) < 0.5);
+    assert_eq!(soft_body(&world, elastic).constraints().len(), 8);
+}
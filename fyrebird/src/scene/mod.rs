@@ -1,9 +1,9 @@
 use anyhow::Result;
-use specs::{
-    Builder, Component, Entity, EntityBuilder, World, WorldExt, storage::GenericWriteStorage,
-};
+use specs::{Builder, Component, Entity, EntityBuilder, SystemData, World, WorldExt};
 
-use crate::{components::Transform2D, game::GameEngine, time::Time};
+use crate::{
+    collision::query::PhysicsQuery, components::Transform2D, game::GameEngine, time::Time,
+};
 
 #[derive(Debug)]
 pub struct TimeRef<'a>(&'a Time);
@@ -19,6 +19,7 @@ impl Scene {
         let mut world = World::new();
 
         world.insert(time);
+        PhysicsQuery::setup(&mut world);
 
         Self { world }
     }
@@ -38,4 +39,9 @@ impl Scene {
         self.world.write_component::<C>().insert(entity, comp)?;
         Ok(())
     }
+
+    /// Ray casts, shape casts and overlap tests against the colliders of this scene
+    pub fn query(&self) -> PhysicsQuery<'_> {
+        self.world.system_data()
+    }
 }
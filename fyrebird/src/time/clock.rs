@@ -0,0 +1,141 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use std::{hint, thread};
+
+/// Source of the time `Time::update` measures frames with.
+/// Swap it for a `ManualClock` or `FixedStepClock` to drive the game deterministically.
+pub trait Clock: Debug + Send + Sync {
+    /// Monotonic time since the clock started
+    fn now(&self) -> Duration;
+
+    /// Current time without moving clocks that advance on each read
+    fn peek(&self) -> Duration {
+        self.now()
+    }
+
+    /// Blocks until `deadline`, sleeping while more than `spin` is left then spinning for
+    /// the rest, as sleeps tend to overshoot
+    fn wait_until(&self, deadline: Duration, spin: Duration) {
+        loop {
+            let now = self.now();
+            if now >= deadline {
+                break;
+            }
+            let left = deadline - now;
+            if left > spin {
+                thread::sleep(left - spin);
+            } else {
+                hint::spin_loop();
+            }
+        }
+    }
+}
+
+/// Wall clock time
+#[derive(Debug, Clone, Copy)]
+pub struct RealClock {
+    start: Instant,
+}
+
+impl Default for RealClock {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl RealClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Clock for RealClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Clock standing still until told to move. Clones share the same time, so a test can keep
+/// one to advance the clock given to `Time`.
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    nanos: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub fn advance_ms(&self, millis: u64) {
+        self.advance(Duration::from_millis(millis));
+    }
+
+    /// Jumps to `time` since the start, backwards jumps are ignored to stay monotonic
+    pub fn set(&self, time: Duration) {
+        self.nanos
+            .fetch_max(time.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+    }
+
+    /// Jumps straight to `deadline`
+    fn wait_until(&self, deadline: Duration, _spin: Duration) {
+        self.set(deadline);
+    }
+}
+
+/// Clock moving forward by the same step every time it's read, so each `Time::update`
+/// measures a frame of exactly `step`. Useful for headless runs and replays.
+#[derive(Debug, Clone)]
+pub struct FixedStepClock {
+    step: Duration,
+    reads: Arc<AtomicU64>,
+}
+
+impl FixedStepClock {
+    pub fn new(step: Duration) -> Self {
+        Self {
+            step,
+            reads: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Clock ticking `frames_per_second` frames per second
+    pub fn from_hz(frames_per_second: f64) -> Self {
+        Self::new(Duration::from_secs_f64(1.0 / frames_per_second))
+    }
+
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+}
+
+impl Clock for FixedStepClock {
+    fn now(&self) -> Duration {
+        let reads = self.reads.fetch_add(1, Ordering::Relaxed);
+        Duration::from_nanos((self.step.as_nanos() as u64).saturating_mul(reads))
+    }
+
+    /// Time of the last read
+    fn peek(&self) -> Duration {
+        let reads = self.reads.load(Ordering::Relaxed).saturating_sub(1);
+        Duration::from_nanos((self.step.as_nanos() as u64).saturating_mul(reads))
+    }
+
+    /// Frames are already a step apart, there is nothing to wait for
+    fn wait_until(&self, _deadline: Duration, _spin: Duration) {}
+}
@@ -1,35 +1,122 @@
-use std::time::{Duration, Instant};
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
 
 use specs::{Component, Read, VecStorage, World, WorldExt};
-use specs::{Entities, Join, System, Write, WriteStorage};
+use specs::{Entities, Entity, Join, System, Write, WriteStorage};
 use specs_derive::Component;
 
+use crate::event::EventSystem;
+
+pub mod clock;
+pub mod pacing;
+pub mod scheduler;
+pub mod stats;
+
+#[cfg(test)]
+mod tests;
+
+use clock::{Clock, RealClock};
+
+/// Temporary change of the time scale eased over real time, see `Time::slow_motion`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeScaleEffect {
+    /// Scale multiplier reached after easing in
+    pub scale: f32,
+    /// Real seconds spent easing from 1 to `scale`
+    pub ease_in: f32,
+    /// Real seconds spent at `scale`
+    pub hold: f32,
+    /// Real seconds spent easing back to 1
+    pub ease_out: f32,
+    /// Real seconds since the effect started
+    elapsed: f32,
+}
+
+impl TimeScaleEffect {
+    pub fn new(scale: f32, ease_in: f32, hold: f32, ease_out: f32) -> Self {
+        Self {
+            scale,
+            ease_in: ease_in.max(0.0),
+            hold: hold.max(0.0),
+            ease_out: ease_out.max(0.0),
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.ease_in + self.hold + self.ease_out
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration()
+    }
+
+    /// Current multiplier of the time scale
+    pub fn multiplier(&self) -> f32 {
+        let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+        let t = self.elapsed;
+
+        if t < self.ease_in {
+            1.0 + (self.scale - 1.0) * smooth(t / self.ease_in)
+        } else if t < self.ease_in + self.hold {
+            self.scale
+        } else if t < self.duration() {
+            let t = (t - self.ease_in - self.hold) / self.ease_out;
+            self.scale + (1.0 - self.scale) * smooth(t)
+        } else {
+            1.0
+        }
+    }
+}
+
 /// Global time resource to track delta time between frames
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Time {
-    /// Program start time
-    start_time: Instant,
-
-    /// Last Frame Instant
-    last_frame: Instant,
+    /// Where frame times are measured from
+    clock: Arc<dyn Clock>,
+    /// Clock reading at the last frame
+    last_frame: Duration,
 
-    /// Time elapsed since last frame in seconds
+    /// Time elapsed since last frame in seconds, scaled by the time scale and 0 while paused
     pub delta_time: f32,
+    /// Real time elapsed since last frame in seconds
+    pub unscaled_delta_time: f32,
+    /// Multiplier of the game time, 0.5 runs the game at half speed
+    pub time_scale: f32,
+    /// Stops the game time, the unscaled time keeps running
+    pub paused: bool,
+    /// Slow motion or hitstop currently easing the time scale
+    effect: Option<TimeScaleEffect>,
+    /// Scaled seconds since the start
+    total: f64,
+    /// Real seconds since the start
+    unscaled_total: f64,
     /// Fixed timestep for physics and fixed updates
     pub fixed_timestep: f32,
     /// Accumulated time for fixed updates
     pub fixed_time_accumulator: f32,
+    /// Fixed updates run per frame at most, the time left over past them is dropped so a
+    /// slow frame can't snowball into ever more fixed updates
+    pub max_fixed_steps: u32,
 }
 
 impl Default for Time {
     fn default() -> Self {
         Self {
-            start_time: Instant::now(),
-            last_frame: Instant::now(),
+            clock: Arc::new(RealClock::new()),
+            last_frame: Duration::ZERO,
 
             delta_time: 0.0,
+            unscaled_delta_time: 0.0,
+            time_scale: 1.0,
+            paused: false,
+            effect: None,
+            total: 0.0,
+            unscaled_total: 0.0,
             fixed_timestep: 1.0 / 60.0,
             fixed_time_accumulator: 0.0,
+            max_fixed_steps: 5,
         }
     }
 }
@@ -49,24 +136,133 @@ impl Time {
         self.fixed_time_accumulator -= self.fixed_timestep;
     }
 
-    /// Updates the time resource with a new delta time
+    /// Drops the accumulated time past the next fixed update, keeping only the fraction
+    /// used for interpolation
+    pub fn drop_fixed_backlog(&mut self) {
+        self.fixed_time_accumulator = self.fixed_time_accumulator.rem_euclid(self.fixed_timestep);
+    }
+
+    /// How far the accumulator is between two fixed updates, from 0 to 1
+    pub fn interpolation_alpha(&self) -> f32 {
+        (self.fixed_time_accumulator / self.fixed_timestep).clamp(0.0, 1.0)
+    }
+
+    /// Time measured by the given clock instead of the wall clock
+    pub fn with_clock(clock: impl Clock + 'static) -> Self {
+        let mut time = Self::default();
+        time.set_clock(clock);
+        time
+    }
+
+    /// Clock the frames are measured with
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    /// Replaces the clock, the next frame is measured from its current reading
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.last_frame = clock.now();
+        self.clock = Arc::new(clock);
+    }
+
+    /// Updates the time resource with the delta time measured by the clock
     pub fn update(&mut self) {
-        let now = Instant::now();
+        let now = self.clock.now();
 
-        self.delta_time = now.duration_since(self.last_frame).as_secs_f32();
+        self.advance(now.saturating_sub(self.last_frame).as_secs_f32());
         self.last_frame = now;
+    }
+
+    /// Starts a frame lasting `dt` real seconds, scaled into the game time
+    pub fn advance(&mut self, dt: f32) {
+        let scale = self.current_time_scale();
+        if let Some(effect) = &mut self.effect {
+            effect.elapsed += dt;
+            if effect.is_finished() {
+                self.effect = None;
+            }
+        }
+
+        self.unscaled_delta_time = dt;
+        self.unscaled_total += dt as f64;
+        self.delta_time = dt * scale;
+        self.total += self.delta_time as f64;
         self.fixed_time_accumulator += self.delta_time;
     }
 
+    /// Scaled seconds since the start
     pub fn total_time(&self) -> f32 {
-        let now = Instant::now();
+        self.total as f32
+    }
+
+    /// Real seconds since the start
+    pub fn unscaled_total_time(&self) -> f32 {
+        self.unscaled_total as f32
+    }
+
+    /// Time scale applied to the frames, including pause and the running effect
+    pub fn current_time_scale(&self) -> f32 {
+        if self.paused {
+            return 0.0;
+        }
+
+        let multiplier = self.effect.map_or(1.0, |effect| effect.multiplier());
+        (self.time_scale * multiplier).max(0.0)
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
 
-        now.duration_since(self.start_time).as_secs_f32()
+    /// Freezes the game time for `duration` real seconds, for heavy impacts
+    pub fn hitstop(&mut self, duration: f32) {
+        self.start_effect(TimeScaleEffect::new(0.0, 0.0, duration, 0.0));
     }
+
+    /// Slows the game down to `scale` for `duration` real seconds, easing in and out
+    /// of it over `ease` real seconds on both ends
+    pub fn slow_motion(&mut self, scale: f32, duration: f32, ease: f32) {
+        self.start_effect(TimeScaleEffect::new(scale, ease, duration, ease));
+    }
+
+    /// Replaces the running effect
+    pub fn start_effect(&mut self, effect: TimeScaleEffect) {
+        self.effect = Some(effect);
+    }
+
+    pub fn effect(&self) -> Option<&TimeScaleEffect> {
+        self.effect.as_ref()
+    }
+
+    pub fn cancel_effect(&mut self) {
+        self.effect = None;
+    }
+}
+
+/// Resource with the interpolation alpha of the current frame, how far the rendered frame is
+/// between the last two fixed updates. Blend `PreviousTransform2D` and `Transform2D` with it.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Interpolation {
+    pub alpha: f32,
 }
 
+/// Sent through the `EventSystem` by the `TimeSystem` when a timer reaches its duration.
+/// Looping timers send it once per frame with the number of cycles completed in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerFinished {
+    pub entity: Entity,
+    pub loops: u32,
+}
+
+/// Called with the timer's entity and the loops completed, see `TimerFinished`
+pub type TimerCallback = Box<dyn FnMut(Entity, u32) + Send + Sync>;
+
 /// Timer component for entities that need to track time
-#[derive(Component, Debug)]
+#[derive(Component)]
 #[storage(VecStorage)]
 pub struct Timer {
     /// Is the timer currently running
@@ -79,6 +275,26 @@ pub struct Timer {
     pub looping: bool,
     /// Has the timer completed (reached duration)
     pub completed: bool,
+    /// Runs on real time, ignoring the time scale and pause
+    pub unscaled: bool,
+    /// Cycles completed since the timer started or was reset
+    pub loops: u32,
+    callbacks: Vec<TimerCallback>,
+}
+
+impl Debug for Timer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Timer")
+            .field("running", &self.running)
+            .field("duration", &self.duration)
+            .field("elapsed", &self.elapsed)
+            .field("looping", &self.looping)
+            .field("completed", &self.completed)
+            .field("unscaled", &self.unscaled)
+            .field("loops", &self.loops)
+            .field("callbacks", &self.callbacks.len())
+            .finish()
+    }
 }
 
 impl Default for Timer {
@@ -89,6 +305,9 @@ impl Default for Timer {
             elapsed: 0.0,
             looping: false,
             completed: false,
+            unscaled: false,
+            loops: 0,
+            callbacks: Vec::new(),
         }
     }
 }
@@ -99,9 +318,7 @@ impl Timer {
         Self {
             running: true,
             duration,
-            elapsed: 0.0,
-            looping: false,
-            completed: false,
+            ..Default::default()
         }
     }
 
@@ -110,12 +327,31 @@ impl Timer {
         Self {
             running: true,
             duration,
-            elapsed: 0.0,
             looping: true,
-            completed: false,
+            ..Default::default()
         }
     }
 
+    /// Make the timer run on real time, ignoring the time scale and pause
+    pub fn with_unscaled_time(mut self) -> Self {
+        self.unscaled = true;
+        self
+    }
+
+    /// Add a callback run by the `TimeSystem` whenever the timer finishes
+    pub fn with_callback(
+        mut self,
+        callback: impl FnMut(Entity, u32) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_finished(callback);
+        self
+    }
+
+    /// Add a callback run by the `TimeSystem` whenever the timer finishes
+    pub fn on_finished(&mut self, callback: impl FnMut(Entity, u32) + Send + Sync + 'static) {
+        self.callbacks.push(Box::new(callback));
+    }
+
     /// Start or resume the timer
     pub fn start(&mut self) {
         self.running = true;
@@ -130,36 +366,70 @@ impl Timer {
     pub fn reset(&mut self) {
         self.elapsed = 0.0;
         self.completed = false;
+        self.loops = 0;
     }
 
     /// Get normalized progress (0.0 to 1.0)
     pub fn progress(&self) -> f32 {
         self.elapsed / self.duration
     }
+
+    /// Advances the timer by `dt` and returns the cycles completed, 0 if it didn't finish
+    pub fn tick(&mut self, dt: f32) -> u32 {
+        if !self.running || self.completed {
+            return 0;
+        }
+
+        self.elapsed += dt;
+        if self.elapsed < self.duration {
+            return 0;
+        }
+
+        let loops = if !self.looping {
+            self.completed = true;
+            self.elapsed = self.duration;
+            1
+        } else if self.duration > 0.0 {
+            let loops = (self.elapsed / self.duration).floor();
+            self.elapsed -= loops * self.duration;
+            loops as u32
+        } else {
+            // A looping timer without duration completes once per tick
+            self.elapsed = 0.0;
+            1
+        };
+
+        self.loops += loops;
+        loops
+    }
 }
 
-/// System that updates all timers based on the global time
+/// System that updates all timers based on the global time, sending `TimerFinished`
+/// and running the timer callbacks when they finish
 pub struct TimeSystem;
 
 impl<'a> System<'a> for TimeSystem {
-    type SystemData = (Entities<'a>, Read<'a, Time>, WriteStorage<'a, Timer>);
-
-    fn run(&mut self, (entities, time, mut timers): Self::SystemData) {
-        // Update all timers using the global delta time
-        for (_entity, timer) in (&entities, &mut timers).join() {
-            if timer.running && !timer.completed {
-                timer.elapsed += time.delta_time;
-
-                // Check for completion
-                if timer.elapsed >= timer.duration {
-                    timer.completed = true;
-
-                    // Handle looping timers
-                    if timer.looping {
-                        timer.elapsed %= timer.duration;
-                        timer.completed = false;
-                    }
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        Read<'a, EventSystem>,
+        WriteStorage<'a, Timer>,
+    );
+
+    fn run(&mut self, (entities, time, events, mut timers): Self::SystemData) {
+        for (entity, timer) in (&entities, &mut timers).join() {
+            let dt = if timer.unscaled {
+                time.unscaled_delta_time
+            } else {
+                time.delta_time
+            };
+
+            let loops = timer.tick(dt);
+            if loops > 0 {
+                for callback in &mut timer.callbacks {
+                    callback(entity, loops);
                 }
+                events.dispatch(TimerFinished { entity, loops });
             }
         }
     }
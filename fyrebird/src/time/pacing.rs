@@ -0,0 +1,133 @@
+use std::time::Duration;
+
+use super::clock::Clock;
+
+/// What `FrameLimiter::wait` did at the end of a frame
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FramePace {
+    /// Time spent waiting for the deadline
+    pub waited: Duration,
+    /// The frame ended past its deadline
+    pub missed: bool,
+    /// Time left until the deadline of the next frame, `None` when uncapped
+    pub next_frame_in: Option<Duration>,
+}
+
+/// Caps the frame rate by waiting at the end of each frame until its deadline.
+/// Deadlines are a frame budget apart so the frame rate holds steady even when frames take
+/// different times. A frame late by more than a whole budget starts a new cadence instead of
+/// rushing the following frames to catch up.
+#[derive(Debug, Clone)]
+pub struct FrameLimiter {
+    /// Frame rate cap, uncapped if `None`
+    pub target_fps: Option<f32>,
+    /// Frame rate cap while the window is unfocused, the target is used if `None`
+    pub background_fps: Option<f32>,
+    /// Time left to the deadline below which the wait spins instead of sleeping
+    pub spin_threshold: Duration,
+    focused: bool,
+    deadline: Option<Duration>,
+    missed: u64,
+}
+
+impl Default for FrameLimiter {
+    fn default() -> Self {
+        Self {
+            target_fps: None,
+            background_fps: None,
+            spin_threshold: Duration::from_millis(2),
+            focused: true,
+            deadline: None,
+            missed: 0,
+        }
+    }
+}
+
+impl FrameLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_target_fps(mut self, fps: f32) -> Self {
+        self.target_fps = Some(fps);
+        self
+    }
+
+    pub fn with_background_fps(mut self, fps: f32) -> Self {
+        self.background_fps = Some(fps);
+        self
+    }
+
+    pub fn with_spin_threshold(mut self, spin_threshold: Duration) -> Self {
+        self.spin_threshold = spin_threshold;
+        self
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    /// Switches between the target and background caps
+    pub fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    /// Frame rate currently capped at
+    pub fn current_fps(&self) -> Option<f32> {
+        let fps = if self.focused {
+            self.target_fps
+        } else {
+            self.background_fps.or(self.target_fps)
+        };
+        fps.filter(|fps| *fps > 0.0)
+    }
+
+    /// Time a frame is given, `None` when uncapped
+    pub fn frame_budget(&self) -> Option<Duration> {
+        self.current_fps()
+            .map(|fps| Duration::from_secs_f64(1.0 / fps as f64))
+    }
+
+    /// Deadlines missed since the start
+    pub fn missed_deadlines(&self) -> u64 {
+        self.missed
+    }
+
+    /// Waits on `clock` until the deadline of the frame that just ended.
+    /// The clock is only peeked at, so clocks moving on each read keep their frames a step
+    /// apart.
+    pub fn wait(&mut self, clock: &dyn Clock) -> FramePace {
+        let Some(budget) = self.frame_budget() else {
+            self.deadline = None;
+            return FramePace::default();
+        };
+
+        let now = clock.peek();
+        // A deadline set under a lower cap is pulled in
+        let deadline = self
+            .deadline
+            .map_or(now, |deadline| deadline.min(now + budget));
+        if now <= deadline {
+            clock.wait_until(deadline, self.spin_threshold);
+            self.deadline = Some(deadline + budget);
+            return FramePace {
+                waited: deadline - now,
+                missed: false,
+                next_frame_in: Some(budget),
+            };
+        }
+
+        self.missed += 1;
+        let next = if now - deadline > budget {
+            now + budget
+        } else {
+            deadline + budget
+        };
+        self.deadline = Some(next);
+        FramePace {
+            waited: Duration::ZERO,
+            missed: true,
+            next_frame_in: Some(next - now),
+        }
+    }
+}
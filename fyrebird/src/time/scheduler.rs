@@ -0,0 +1,288 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Formatter};
+
+use specs::{World, WorldExt};
+
+use super::Time;
+
+/// Work run by the `Scheduler` with access to the whole world
+pub type TaskAction = Box<dyn FnMut(&mut World) + Send + Sync>;
+
+/// Identifies a scheduled task, used to cancel it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskHandle(u64);
+
+enum Step {
+    /// Waits for scaled seconds
+    Seconds(f32),
+    /// Waits for frames
+    Frames(u32),
+    Run(TaskAction),
+}
+
+impl Step {
+    fn waits(&self) -> bool {
+        match self {
+            Step::Seconds(seconds) => *seconds > 0.0,
+            Step::Frames(frames) => *frames > 0,
+            Step::Run(_) => false,
+        }
+    }
+}
+
+impl Debug for Step {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Seconds(seconds) => write!(f, "Seconds({seconds})"),
+            Step::Frames(frames) => write!(f, "Frames({frames})"),
+            Step::Run(_) => write!(f, "Run"),
+        }
+    }
+}
+
+/// Chain of waits and actions run one after the other
+#[derive(Debug, Default)]
+pub struct Sequence {
+    steps: Vec<Step>,
+    repeat: bool,
+}
+
+impl Sequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Waits for `seconds` of game time
+    pub fn wait(mut self, seconds: f32) -> Self {
+        self.steps.push(Step::Seconds(seconds));
+        self
+    }
+
+    /// Waits for `frames` frames
+    pub fn wait_frames(mut self, frames: u32) -> Self {
+        self.steps.push(Step::Frames(frames));
+        self
+    }
+
+    pub fn then(mut self, action: impl FnMut(&mut World) + Send + Sync + 'static) -> Self {
+        self.steps.push(Step::Run(Box::new(action)));
+        self
+    }
+
+    /// Starts over once the last step is done, until cancelled
+    pub fn repeating(mut self) -> Self {
+        self.repeat = true;
+        self
+    }
+}
+
+#[derive(Debug)]
+struct Task {
+    sequence: Sequence,
+    /// Step being waited on or run
+    cursor: usize,
+    /// Seconds or frames waited on the current step
+    waited_seconds: f32,
+    waited_frames: u32,
+    /// Frame the task was scheduled in
+    scheduled_frame: u64,
+}
+
+impl Task {
+    /// Runs the steps the `dt` of `frame` reaches. Returns false once the task is over.
+    fn advance(&mut self, handle: TaskHandle, frame: u64, dt: f32, world: &mut World) -> bool {
+        let mut budget = dt;
+        // Frame waits count the frames after the one they were reached or scheduled in
+        let mut frame_used = frame == self.scheduled_frame;
+
+        loop {
+            if self.cursor == self.sequence.steps.len() {
+                if !self.sequence.repeat {
+                    return false;
+                }
+                self.cursor = 0;
+                // Without any wait the sequence would spin forever, it runs once per frame
+                if !self.sequence.steps.iter().any(Step::waits) {
+                    return true;
+                }
+            }
+
+            match &mut self.sequence.steps[self.cursor] {
+                Step::Seconds(seconds) => {
+                    let left = *seconds - self.waited_seconds;
+                    if left > budget {
+                        self.waited_seconds += budget;
+                        return true;
+                    }
+                    budget -= left.max(0.0);
+                }
+                Step::Frames(frames) => {
+                    if self.waited_frames < *frames {
+                        if frame_used {
+                            return true;
+                        }
+                        self.waited_frames += 1;
+                        if self.waited_frames < *frames {
+                            return true;
+                        }
+                    }
+                }
+                Step::Run(action) => {
+                    action(world);
+                    if world.read_resource::<Scheduler>().was_cancelled(handle) {
+                        return false;
+                    }
+                }
+            }
+
+            self.cursor += 1;
+            self.waited_seconds = 0.0;
+            self.waited_frames = 0;
+            frame_used = true;
+        }
+    }
+}
+
+/// Resource running delayed, repeating and chained work on the game time of `Time`.
+/// Tasks run in the order they were scheduled, once per frame right after the update systems,
+/// with the scaled delta time. They hold while the time is paused. Frame waits start counting
+/// on the frame after the one the task was scheduled in, and tasks scheduled while others run
+/// start waiting on the next frame.
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    tasks: BTreeMap<TaskHandle, Task>,
+    next: u64,
+    /// Frames run so far, paused ones excluded
+    frame: u64,
+    /// Task taken out to run, and whether it was cancelled meanwhile
+    running: Option<(TaskHandle, bool)>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `action` once, `seconds` from now
+    pub fn after(
+        &mut self,
+        seconds: f32,
+        action: impl FnMut(&mut World) + Send + Sync + 'static,
+    ) -> TaskHandle {
+        self.schedule(Sequence::new().wait(seconds).then(action))
+    }
+
+    /// Runs `action` once, `frames` frames after the current one
+    pub fn after_frames(
+        &mut self,
+        frames: u32,
+        action: impl FnMut(&mut World) + Send + Sync + 'static,
+    ) -> TaskHandle {
+        self.schedule(Sequence::new().wait_frames(frames).then(action))
+    }
+
+    /// Runs `action` every `interval` seconds until cancelled, catching up on long frames
+    pub fn every(
+        &mut self,
+        interval: f32,
+        action: impl FnMut(&mut World) + Send + Sync + 'static,
+    ) -> TaskHandle {
+        self.schedule(Sequence::new().wait(interval).then(action).repeating())
+    }
+
+    pub fn schedule(&mut self, sequence: Sequence) -> TaskHandle {
+        let handle = TaskHandle(self.next);
+        self.next += 1;
+        self.tasks.insert(
+            handle,
+            Task {
+                sequence,
+                cursor: 0,
+                waited_seconds: 0.0,
+                waited_frames: 0,
+                scheduled_frame: self.frame,
+            },
+        );
+        handle
+    }
+
+    /// Stops a task, returns false if it already finished or was cancelled
+    pub fn cancel(&mut self, handle: TaskHandle) -> bool {
+        match &mut self.running {
+            Some((running, cancelled)) if *running == handle => !std::mem::replace(cancelled, true),
+            _ => self.tasks.remove(&handle).is_some(),
+        }
+    }
+
+    pub fn is_scheduled(&self, handle: TaskHandle) -> bool {
+        self.tasks.contains_key(&handle) || self.running == Some((handle, false))
+    }
+
+    /// Number of tasks waiting
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Cancels every task
+    pub fn clear(&mut self) {
+        self.tasks.clear();
+        if let Some((_, cancelled)) = &mut self.running {
+            *cancelled = true;
+        }
+    }
+
+    fn take(&mut self, handle: TaskHandle) -> Option<Task> {
+        let task = self.tasks.remove(&handle)?;
+        self.running = Some((handle, false));
+        Some(task)
+    }
+
+    /// Returns true if the running task was cancelled while running
+    fn was_cancelled(&self, handle: TaskHandle) -> bool {
+        matches!(self.running, Some((running, true)) if running == handle)
+    }
+
+    fn put_back(&mut self, handle: TaskHandle, task: Task) {
+        if !self.was_cancelled(handle) {
+            self.tasks.insert(handle, task);
+        }
+        self.running = None;
+    }
+
+    /// Runs the tasks of the `Scheduler` resource due this frame, called by the `GameLoop`.
+    /// Does nothing if the world has no scheduler.
+    pub fn run(world: &mut World) {
+        let time = world.read_resource::<Time>();
+        if time.paused {
+            return;
+        }
+        let dt = time.delta_time;
+        drop(time);
+
+        let Some(scheduler) = world.try_fetch::<Scheduler>() else {
+            return;
+        };
+        let handles: Vec<_> = scheduler.tasks.keys().copied().collect();
+        let frame = scheduler.frame;
+        drop(scheduler);
+        for handle in handles {
+            // Skips the tasks cancelled by the ones run before them
+            let Some(mut task) = world.write_resource::<Scheduler>().take(handle) else {
+                continue;
+            };
+
+            let keep = task.advance(handle, frame, dt, world);
+            let mut scheduler = world.write_resource::<Scheduler>();
+            if keep {
+                scheduler.put_back(handle, task);
+            } else {
+                scheduler.running = None;
+            }
+        }
+        world.write_resource::<Scheduler>().frame += 1;
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+
+use super::Time;
+
+/// Counts of frame times falling in buckets of equal width, the last bucket also counts
+/// every frame slower than it
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameHistogram {
+    /// Width of a bucket in seconds
+    bucket_width: f32,
+    counts: Vec<u64>,
+}
+
+impl FrameHistogram {
+    pub fn new(bucket_width: f32, buckets: usize) -> Self {
+        Self {
+            bucket_width,
+            counts: vec![0; buckets.max(1)],
+        }
+    }
+
+    pub fn record(&mut self, frame_time: f32) {
+        let last = self.counts.len() - 1;
+        let bucket = (frame_time.max(0.0) / self.bucket_width) as usize;
+        self.counts[bucket.min(last)] += 1;
+    }
+
+    pub fn bucket_width(&self) -> f32 {
+        self.bucket_width
+    }
+
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Range of frame times in seconds counted by a bucket
+    pub fn bucket_range(&self, bucket: usize) -> (f32, f32) {
+        let start = bucket as f32 * self.bucket_width;
+        if bucket + 1 == self.counts.len() {
+            (start, f32::INFINITY)
+        } else {
+            (start, start + self.bucket_width)
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.fill(0);
+    }
+}
+
+/// Resource tracking how the game performs, fed with the real frame times of `Time` by the
+/// `GameLoop`. Averages, extremes and lows cover the last `window` frames, the histogram
+/// and frame count everything since the last reset. Frame times are in seconds.
+#[derive(Debug, Clone)]
+pub struct FrameStats {
+    window: usize,
+    frame_times: VecDeque<f32>,
+    fixed_steps: VecDeque<u32>,
+    histogram: FrameHistogram,
+    frame_count: u64,
+    /// Frames that ended past the deadline of the `FrameLimiter`
+    missed_deadlines: u64,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        // Buckets of 1ms up to 100ms
+        Self::new(1000).with_histogram(0.001, 100)
+    }
+}
+
+impl FrameStats {
+    /// Stats over the last `window` frames
+    pub fn new(window: usize) -> Self {
+        let window = window.max(1);
+        Self {
+            window,
+            frame_times: VecDeque::with_capacity(window),
+            fixed_steps: VecDeque::with_capacity(window),
+            histogram: FrameHistogram::new(0.001, 100),
+            frame_count: 0,
+            missed_deadlines: 0,
+        }
+    }
+
+    pub fn with_histogram(mut self, bucket_width: f32, buckets: usize) -> Self {
+        self.histogram = FrameHistogram::new(bucket_width, buckets);
+        self
+    }
+
+    /// Records the frame `time` just measured
+    pub fn record_time(&mut self, time: &Time, fixed_steps: u32) {
+        self.record(time.unscaled_delta_time, fixed_steps);
+    }
+
+    pub fn record(&mut self, frame_time: f32, fixed_steps: u32) {
+        if self.frame_times.len() == self.window {
+            self.frame_times.pop_front();
+            self.fixed_steps.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+        self.fixed_steps.push_back(fixed_steps);
+        self.histogram.record(frame_time);
+        self.frame_count += 1;
+    }
+
+    /// Frames recorded since the last reset
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    pub fn record_missed_deadline(&mut self) {
+        self.missed_deadlines += 1;
+    }
+
+    /// Deadlines of the frame limiter missed since the last reset
+    pub fn missed_deadlines(&self) -> u64 {
+        self.missed_deadlines
+    }
+
+    /// Frame times of the window, oldest first
+    pub fn frame_times(&self) -> impl Iterator<Item = f32> + '_ {
+        self.frame_times.iter().copied()
+    }
+
+    /// Fixed updates run by each frame of the window, oldest first
+    pub fn fixed_steps(&self) -> impl Iterator<Item = u32> + '_ {
+        self.fixed_steps.iter().copied()
+    }
+
+    pub fn last_frame_time(&self) -> Option<f32> {
+        self.frame_times.back().copied()
+    }
+
+    pub fn last_fixed_steps(&self) -> Option<u32> {
+        self.fixed_steps.back().copied()
+    }
+
+    /// Frames per second over the window
+    pub fn fps(&self) -> f32 {
+        Self::rate(self.average_frame_time())
+    }
+
+    pub fn average_frame_time(&self) -> f32 {
+        if self.frame_times.is_empty() {
+            return 0.0;
+        }
+        self.frame_times.iter().sum::<f32>() / self.frame_times.len() as f32
+    }
+
+    pub fn min_frame_time(&self) -> f32 {
+        self.frame_times
+            .iter()
+            .copied()
+            .reduce(f32::min)
+            .unwrap_or(0.0)
+    }
+
+    pub fn max_frame_time(&self) -> f32 {
+        self.frame_times
+            .iter()
+            .copied()
+            .reduce(f32::max)
+            .unwrap_or(0.0)
+    }
+
+    pub fn average_fixed_steps(&self) -> f32 {
+        if self.fixed_steps.is_empty() {
+            return 0.0;
+        }
+        self.fixed_steps.iter().sum::<u32>() as f32 / self.fixed_steps.len() as f32
+    }
+
+    /// Frames per second of the slowest `fraction` of the frames in the window, at least one
+    pub fn low_fps(&self, fraction: f32) -> f32 {
+        if self.frame_times.is_empty() {
+            return 0.0;
+        }
+        let mut slowest: Vec<f32> = self.frame_times.iter().copied().collect();
+        slowest.sort_by(|a, b| b.total_cmp(a));
+        let count = ((slowest.len() as f32 * fraction).ceil() as usize).clamp(1, slowest.len());
+        Self::rate(slowest[..count].iter().sum::<f32>() / count as f32)
+    }
+
+    /// Frames per second of the slowest 1% of the frames
+    pub fn one_percent_low(&self) -> f32 {
+        self.low_fps(0.01)
+    }
+
+    /// Frames per second of the slowest 0.1% of the frames
+    pub fn point_one_percent_low(&self) -> f32 {
+        self.low_fps(0.001)
+    }
+
+    pub fn histogram(&self) -> &FrameHistogram {
+        &self.histogram
+    }
+
+    pub fn reset(&mut self) {
+        self.frame_times.clear();
+        self.fixed_steps.clear();
+        self.histogram.clear();
+        self.frame_count = 0;
+        self.missed_deadlines = 0;
+    }
+
+    /// Writes the frames of the window as CSV, one frame per row
+    pub fn write_csv(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "frame,frame_time_ms,fixed_steps")?;
+        let first = self.frame_count - self.frame_times.len() as u64;
+        for (i, (frame_time, steps)) in self.frame_times().zip(self.fixed_steps()).enumerate() {
+            writeln!(
+                writer,
+                "{},{:.3},{}",
+                first + i as u64,
+                frame_time * 1000.0,
+                steps
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes the histogram as CSV, one bucket per row
+    pub fn write_histogram_csv(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "from_ms,to_ms,frames")?;
+        for (bucket, count) in self.histogram.counts().iter().enumerate() {
+            let (from, to) = self.histogram.bucket_range(bucket);
+            writeln!(writer, "{:.3},{:.3},{}", from * 1000.0, to * 1000.0, count)?;
+        }
+        Ok(())
+    }
+
+    fn rate(frame_time: f32) -> f32 {
+        if frame_time > 0.0 {
+            1.0 / frame_time
+        } else {
+            0.0
+        }
+    }
+}
@@ -0,0 +1,534 @@
+use std::time::Duration;
+
+use std::sync::{Arc, Mutex};
+
+use specs::{Builder, DispatcherBuilder, RunNow, System, World, WorldExt, Write};
+
+use super::clock::{Clock, FixedStepClock, ManualClock, RealClock};
+use super::pacing::FrameLimiter;
+use super::scheduler::{Scheduler, Sequence};
+use super::stats::FrameStats;
+use super::{Time, TimeScaleEffect, TimeSystem, Timer, TimerFinished};
+use crate::event::EventSystem;
+use crate::game::game_loop::GameLoop;
+
+fn approx_eq(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-4
+}
+
+fn timer_world() -> World {
+    let mut world = World::new();
+    System::setup(&mut TimeSystem, &mut world);
+    world
+}
+
+fn run_frame(world: &mut World, dt: f32) {
+    world.write_resource::<Time>().advance(dt);
+    TimeSystem.run_now(world);
+}
+
+#[test]
+fn test_time_scale_and_pause() {
+    let mut time = Time::new();
+    time.time_scale = 0.5;
+    time.advance(0.1);
+
+    assert!(approx_eq(time.delta_time, 0.05));
+    assert!(approx_eq(time.unscaled_delta_time, 0.1));
+    assert!(approx_eq(time.fixed_time_accumulator, 0.05));
+
+    time.pause();
+    time.advance(0.1);
+    assert_eq!(time.delta_time, 0.0);
+    assert!(approx_eq(time.unscaled_delta_time, 0.1));
+    assert!(approx_eq(time.fixed_time_accumulator, 0.05));
+
+    time.resume();
+    time.advance(0.1);
+    assert!(approx_eq(time.total_time(), 0.1));
+    assert!(approx_eq(time.unscaled_total_time(), 0.3));
+}
+
+#[test]
+fn test_hitstop_freezes_for_real_time() {
+    let mut time = Time::new();
+    time.hitstop(0.1);
+
+    time.advance(0.05);
+    assert_eq!(time.delta_time, 0.0);
+    time.advance(0.05);
+    assert_eq!(time.delta_time, 0.0);
+    assert!(time.effect().is_none());
+
+    time.advance(0.05);
+    assert!(approx_eq(time.delta_time, 0.05));
+}
+
+#[test]
+fn test_slow_motion_eases_in_and_out() {
+    let mut effect = TimeScaleEffect::new(0.2, 0.5, 1.0, 0.5);
+    let mut scales = Vec::new();
+    while !effect.is_finished() {
+        scales.push(effect.multiplier());
+        effect.elapsed += 0.25;
+    }
+
+    assert_eq!(scales.len(), 8);
+    assert_eq!(scales[0], 1.0);
+    assert!(approx_eq(scales[1], 0.6));
+    assert!(scales[2..6].iter().all(|&s| approx_eq(s, 0.2)));
+    assert!(approx_eq(scales[7], 0.6));
+    assert_eq!(effect.multiplier(), 1.0);
+
+    // Combined with the global scale
+    let mut time = Time::new();
+    time.time_scale = 0.5;
+    time.slow_motion(0.2, 1.0, 0.0);
+    time.advance(0.1);
+    assert!(approx_eq(time.delta_time, 0.01));
+}
+
+#[test]
+fn test_unscaled_timers_ignore_pause() {
+    let mut world = timer_world();
+    let mut time = Time::new();
+    time.pause();
+    time.advance(0.5);
+    world.insert(time);
+
+    let scaled = world.create_entity().with(Timer::new(1.0)).build();
+    let unscaled = world
+        .create_entity()
+        .with(Timer::new(1.0).with_unscaled_time())
+        .build();
+
+    TimeSystem.run_now(&world);
+
+    let timers = world.read_storage::<Timer>();
+    assert_eq!(timers.get(scaled).unwrap().elapsed, 0.0);
+    assert_eq!(timers.get(unscaled).unwrap().elapsed, 0.5);
+}
+
+#[test]
+fn test_manual_clock_drives_time() {
+    let clock = ManualClock::new();
+    let mut time = Time::with_clock(clock.clone());
+
+    time.update();
+    assert_eq!(time.delta_time, 0.0);
+
+    clock.advance_ms(250);
+    time.update();
+    assert_eq!(time.delta_time, 0.25);
+
+    clock.set(Duration::from_secs(1));
+    clock.set(Duration::from_millis(500));
+    assert_eq!(clock.now(), Duration::from_secs(1));
+    time.update();
+    assert_eq!(time.delta_time, 0.75);
+    assert_eq!(time.unscaled_total_time(), 1.0);
+}
+
+#[test]
+fn test_fixed_step_clock_timers() {
+    let mut world = timer_world();
+    let mut time = Time::with_clock(FixedStepClock::new(Duration::from_millis(100)));
+    let timer = world.create_entity().with(Timer::new(1.0)).build();
+
+    for frame in 1..=10 {
+        time.update();
+        assert!(approx_eq(time.delta_time, 0.1));
+        world.insert(time.clone());
+        TimeSystem.run_now(&world);
+
+        let timers = world.read_storage::<Timer>();
+        assert_eq!(timers.get(timer).unwrap().completed, frame == 10);
+    }
+}
+
+#[test]
+fn test_timers_send_finished_events() {
+    let mut world = timer_world();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let sink = received.clone();
+    world
+        .read_resource::<EventSystem>()
+        .subscribe(move |e: &TimerFinished| sink.lock().unwrap().push(*e))
+        .detach();
+
+    let once = world.create_entity().with(Timer::new(0.5)).build();
+    let looping = world.create_entity().with(Timer::new_looping(0.2)).build();
+
+    run_frame(&mut world, 0.3);
+    assert_eq!(
+        *received.lock().unwrap(),
+        vec![TimerFinished {
+            entity: looping,
+            loops: 1
+        }]
+    );
+
+    // 0.1 left over plus 0.7 spans four cycles
+    received.lock().unwrap().clear();
+    run_frame(&mut world, 0.7);
+    let events = received.lock().unwrap().clone();
+    assert_eq!(events.len(), 2);
+    assert!(events.contains(&TimerFinished {
+        entity: once,
+        loops: 1
+    }));
+    assert!(events.contains(&TimerFinished {
+        entity: looping,
+        loops: 4
+    }));
+
+    {
+        let timers = world.read_storage::<Timer>();
+        let cycling = timers.get(looping).unwrap();
+        assert_eq!(cycling.loops, 5);
+        assert!(approx_eq(cycling.elapsed, 0.0));
+        assert_eq!(timers.get(once).unwrap().progress(), 1.0);
+    }
+
+    // Finished and paused timers stay quiet
+    received.lock().unwrap().clear();
+    world
+        .write_storage::<Timer>()
+        .get_mut(looping)
+        .unwrap()
+        .pause();
+    run_frame(&mut world, 1.0);
+    assert!(received.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_timer_callbacks() {
+    let mut world = timer_world();
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let sink = calls.clone();
+    let entity = world
+        .create_entity()
+        .with(
+            Timer::new_looping(0.25).with_callback(move |entity, loops| {
+                sink.lock().unwrap().push((entity, loops));
+            }),
+        )
+        .build();
+
+    run_frame(&mut world, 0.1);
+    run_frame(&mut world, 0.5);
+    run_frame(&mut world, 0.25);
+
+    assert_eq!(*calls.lock().unwrap(), vec![(entity, 2), (entity, 1)]);
+}
+
+#[derive(Default)]
+struct Log(Vec<&'static str>);
+
+fn scheduler_world() -> World {
+    let mut world = World::new();
+    world.insert(Time::new());
+    world.insert(Scheduler::new());
+    world.insert(Log::default());
+    world
+}
+
+fn run_tasks(world: &mut World, dt: f32) {
+    world.write_resource::<Time>().advance(dt);
+    Scheduler::run(world);
+}
+
+fn log(entry: &'static str) -> impl FnMut(&mut World) + Send + Sync + 'static {
+    move |world: &mut World| world.write_resource::<Log>().0.push(entry)
+}
+
+fn entries(world: &World) -> Vec<&'static str> {
+    world.read_resource::<Log>().0.clone()
+}
+
+#[test]
+fn test_scheduler_delays() {
+    let mut world = scheduler_world();
+    let (delayed, framed) = {
+        let mut scheduler = world.write_resource::<Scheduler>();
+        (
+            scheduler.after(0.25, log("delay")),
+            scheduler.after_frames(3, log("frames")),
+        )
+    };
+
+    run_tasks(&mut world, 0.2);
+    run_tasks(&mut world, 0.1);
+    assert_eq!(entries(&world), vec!["delay"]);
+    assert!(!world.read_resource::<Scheduler>().is_scheduled(delayed));
+
+    // The frame the task was scheduled in doesn't count
+    run_tasks(&mut world, 0.0);
+    assert_eq!(entries(&world), vec!["delay"]);
+    run_tasks(&mut world, 0.0);
+    assert_eq!(entries(&world), vec!["delay", "frames"]);
+    assert!(world.read_resource::<Scheduler>().is_empty());
+    assert!(!world.write_resource::<Scheduler>().cancel(framed));
+
+    // Paused time holds tasks, frame delays included
+    world
+        .write_resource::<Scheduler>()
+        .after_frames(1, log("late"));
+    world.write_resource::<Time>().pause();
+    run_tasks(&mut world, 0.1);
+    assert_eq!(entries(&world).len(), 2);
+}
+
+/// Schedules a task on the frame given
+struct ScheduleSystem {
+    frame: u32,
+    schedule_on: u32,
+}
+
+impl<'a> System<'a> for ScheduleSystem {
+    type SystemData = Write<'a, Scheduler>;
+
+    fn run(&mut self, mut scheduler: Self::SystemData) {
+        self.frame += 1;
+        if self.frame == self.schedule_on {
+            scheduler.after_frames(1, log("task"));
+        }
+    }
+}
+
+#[test]
+fn test_tasks_scheduled_by_systems_wait_for_the_next_frame() {
+    let mut world = World::new();
+    let schedule = ScheduleSystem {
+        frame: 0,
+        schedule_on: 2,
+    };
+    let update = DispatcherBuilder::new()
+        .with(schedule, "schedule", &[])
+        .build();
+    let mut game_loop = GameLoop::new(update, DispatcherBuilder::new().build());
+    game_loop.setup(&mut world);
+    world.insert(Log::default());
+
+    // The scheduler runs after the systems, the task waits for the frame after
+    let mut time = Time::new();
+    for frame in 1..=4 {
+        time.advance(0.01);
+        game_loop.run_frame(&mut time, &mut world);
+        let expected = if frame >= 3 { vec!["task"] } else { vec![] };
+        assert_eq!(entries(&world), expected, "frame {frame}");
+    }
+}
+
+#[test]
+fn test_scheduler_repeats_until_cancelled() {
+    let mut world = scheduler_world();
+    let handle = world.write_resource::<Scheduler>().every(0.1, log("tick"));
+
+    run_tasks(&mut world, 0.05);
+    assert!(entries(&world).is_empty());
+    // Catches up on a long frame
+    run_tasks(&mut world, 0.36);
+    assert_eq!(entries(&world).len(), 4);
+
+    assert!(world.write_resource::<Scheduler>().cancel(handle));
+    run_tasks(&mut world, 1.0);
+    assert_eq!(entries(&world).len(), 4);
+}
+
+#[test]
+fn test_scheduler_sequences() {
+    let mut world = scheduler_world();
+    world.write_resource::<Scheduler>().schedule(
+        Sequence::new()
+            .then(log("start"))
+            .wait(0.5)
+            .then(log("middle"))
+            .wait_frames(1)
+            .then(log("end")),
+    );
+
+    run_tasks(&mut world, 0.0);
+    assert_eq!(entries(&world), vec!["start"]);
+    // The frame wait only starts counting on the next frame
+    run_tasks(&mut world, 1.0);
+    assert_eq!(entries(&world), vec!["start", "middle"]);
+    run_tasks(&mut world, 0.0);
+    assert_eq!(entries(&world), vec!["start", "middle", "end"]);
+    assert!(world.read_resource::<Scheduler>().is_empty());
+}
+
+#[test]
+fn test_tasks_schedule_and_cancel_tasks() {
+    let mut world = scheduler_world();
+    let handle = Arc::new(Mutex::new(None));
+    let own = handle.clone();
+    let mut runs = 0;
+    let repeating = world.write_resource::<Scheduler>().schedule(
+        Sequence::new()
+            .then(move |world: &mut World| {
+                runs += 1;
+                world.write_resource::<Log>().0.push("repeat");
+                let mut scheduler = world.write_resource::<Scheduler>();
+                scheduler.after(0.0, log("spawned"));
+                // Stops itself on the second run
+                if runs == 2 {
+                    let own = own.lock().unwrap().unwrap();
+                    assert!(scheduler.cancel(own));
+                    assert!(!scheduler.is_scheduled(own));
+                }
+            })
+            .repeating(),
+    );
+    *handle.lock().unwrap() = Some(repeating);
+
+    // Tasks scheduled by others wait for the next frame
+    run_tasks(&mut world, 0.1);
+    assert_eq!(entries(&world), vec!["repeat"]);
+    run_tasks(&mut world, 0.1);
+    assert_eq!(entries(&world), vec!["repeat", "repeat", "spawned"]);
+    assert!(!world.read_resource::<Scheduler>().is_scheduled(repeating));
+
+    run_tasks(&mut world, 0.1);
+    assert_eq!(
+        entries(&world),
+        vec!["repeat", "repeat", "spawned", "spawned"]
+    );
+    assert!(world.read_resource::<Scheduler>().is_empty());
+}
+
+#[test]
+fn test_frame_stats() {
+    let mut stats = FrameStats::new(100).with_histogram(0.01, 5);
+    assert_eq!(stats.fps(), 0.0);
+
+    // 98 frames at 100 FPS, a hitch of 50ms and one of 100ms
+    for _ in 0..98 {
+        stats.record(0.01, 1);
+    }
+    stats.record(0.05, 3);
+    stats.record(0.1, 6);
+
+    assert_eq!(stats.frame_count(), 100);
+    assert!((stats.average_frame_time() - 0.0113).abs() < 1e-5);
+    assert_eq!(stats.min_frame_time(), 0.01);
+    assert_eq!(stats.max_frame_time(), 0.1);
+    assert!((stats.one_percent_low() - 10.0).abs() < 1e-3);
+    assert!((stats.low_fps(0.02) - 1.0 / 0.075).abs() < 1e-3);
+    assert_eq!(stats.point_one_percent_low(), stats.one_percent_low());
+    assert!((stats.average_fixed_steps() - 1.07).abs() < 1e-5);
+    assert_eq!(stats.last_fixed_steps(), Some(6));
+    // The hitch of 100ms lands in the last bucket
+    assert_eq!(stats.histogram().counts(), &[0, 98, 0, 0, 2]);
+
+    // The window rolls over, the histogram keeps counting
+    for _ in 0..100 {
+        stats.record(0.02, 1);
+    }
+    assert!((stats.fps() - 50.0).abs() < 1e-3);
+    assert_eq!(stats.max_frame_time(), 0.02);
+    assert_eq!(stats.histogram().counts(), &[0, 98, 100, 0, 2]);
+
+    stats.record_missed_deadline();
+    assert_eq!(stats.missed_deadlines(), 1);
+
+    stats.reset();
+    assert_eq!(stats.frame_count(), 0);
+    assert_eq!(stats.missed_deadlines(), 0);
+    assert_eq!(stats.last_frame_time(), None);
+}
+
+#[test]
+fn test_frame_stats_csv() {
+    let mut stats = FrameStats::new(2).with_histogram(0.01, 2);
+    stats.record(0.01, 1);
+    stats.record(0.015, 0);
+    stats.record(0.02, 2);
+
+    let mut frames = Vec::new();
+    stats.write_csv(&mut frames).unwrap();
+    assert_eq!(
+        String::from_utf8(frames).unwrap(),
+        "frame,frame_time_ms,fixed_steps\n1,15.000,0\n2,20.000,2\n"
+    );
+
+    let mut histogram = Vec::new();
+    stats.write_histogram_csv(&mut histogram).unwrap();
+    assert_eq!(
+        String::from_utf8(histogram).unwrap(),
+        "from_ms,to_ms,frames\n0.000,10.000,0\n10.000,inf,3\n"
+    );
+}
+
+#[test]
+fn test_frame_limiter_keeps_cadence() {
+    let clock = ManualClock::new();
+    let mut limiter = FrameLimiter::new().with_target_fps(50.0);
+
+    // The first frame sets the cadence
+    assert!(!limiter.wait(&clock).missed);
+    clock.advance_ms(5);
+    let pace = limiter.wait(&clock);
+    assert_eq!(pace.waited, Duration::from_millis(15));
+    assert_eq!(pace.next_frame_in, Some(Duration::from_millis(20)));
+    assert_eq!(clock.now(), Duration::from_millis(20));
+
+    // Late by less than a frame, the next deadline stays on the cadence
+    clock.advance_ms(30);
+    let pace = limiter.wait(&clock);
+    assert!(pace.missed);
+    assert_eq!(pace.next_frame_in, Some(Duration::from_millis(10)));
+    clock.advance_ms(5);
+    assert_eq!(limiter.wait(&clock).waited, Duration::from_millis(5));
+    assert_eq!(clock.now(), Duration::from_millis(60));
+
+    // Late by more than a frame, a new cadence starts
+    clock.advance_ms(100);
+    assert!(limiter.wait(&clock).missed);
+    assert_eq!(limiter.wait(&clock).waited, Duration::from_millis(20));
+    assert_eq!(clock.now(), Duration::from_millis(180));
+    assert_eq!(limiter.missed_deadlines(), 2);
+}
+
+#[test]
+fn test_frame_limiter_background_cap() {
+    let clock = ManualClock::new();
+    let mut limiter = FrameLimiter::new();
+    limiter.wait(&clock);
+    assert_eq!(limiter.wait(&clock).waited, Duration::ZERO);
+
+    limiter = limiter.with_target_fps(100.0).with_background_fps(10.0);
+    assert_eq!(limiter.frame_budget(), Some(Duration::from_millis(10)));
+    limiter.set_focused(false);
+    assert_eq!(limiter.current_fps(), Some(10.0));
+    limiter.wait(&clock);
+    assert_eq!(limiter.wait(&clock).waited, Duration::from_millis(100));
+
+    limiter.set_focused(true);
+    assert_eq!(limiter.wait(&clock).waited, Duration::from_millis(10));
+}
+
+#[test]
+fn test_frame_limiter_keeps_fixed_steps() {
+    let clock = FixedStepClock::from_hz(60.0);
+    let mut time = Time::with_clock(clock.clone());
+    let mut limiter = FrameLimiter::new().with_target_fps(30.0);
+
+    for _ in 0..10 {
+        time.update();
+        limiter.wait(time.clock());
+        assert_eq!(time.delta_time, clock.step().as_secs_f32());
+    }
+}
+
+#[test]
+fn test_frame_limiter_waits_on_the_wall_clock() {
+    let clock = RealClock::new();
+    let mut limiter = FrameLimiter::new().with_target_fps(200.0);
+    let start = clock.now();
+    limiter.wait(&clock);
+    for _ in 0..4 {
+        limiter.wait(&clock);
+    }
+    assert!(clock.now() - start >= Duration::from_millis(20));
+}